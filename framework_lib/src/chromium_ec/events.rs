@@ -0,0 +1,302 @@
+//! Watch the EC for lid, power button, AC, battery and chassis changes
+//!
+//! The EC keeps its switches, the battery state and pending host events in the memory map, see
+//! [`EcMemmap`]. [`EventMonitor`] polls them together with the chassis intrusion switch and turns
+//! differences between two polls into [`EcEvent`]s.
+
+use alloc::vec::Vec;
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+
+use crate::chromium_ec::memmap::EcMemmap;
+use crate::chromium_ec::CrosEc;
+
+/// Host events as numbered by the EC, event N is bit N - 1 of the host event mask
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum HostEvent {
+    LidClosed = 1,
+    LidOpen = 2,
+    PowerButton = 3,
+    AcConnected = 4,
+    AcDisconnected = 5,
+    BatteryLow = 6,
+    BatteryCritical = 7,
+    Battery = 8,
+    ThermalThreshold = 9,
+    Device = 10,
+    Thermal = 11,
+    UsbCharger = 12,
+    KeyPressed = 13,
+    InterfaceReady = 14,
+    KeyboardRecovery = 15,
+    ThermalShutdown = 16,
+    BatteryShutdown = 17,
+    ThrottleStart = 18,
+    ThrottleStop = 19,
+    HangDetect = 20,
+    HangReboot = 21,
+    PdMcu = 22,
+    BatteryStatus = 23,
+    Panic = 24,
+    KeyboardFastboot = 25,
+    Rtc = 26,
+    Mkbp = 27,
+    UsbMux = 28,
+    ModeChange = 29,
+    KeyboardRecoveryHwReinit = 30,
+    Wov = 31,
+    Invalid = 32,
+}
+
+impl HostEvent {
+    /// Bit of the event in the host event mask
+    pub fn mask(self) -> u64 {
+        1 << (self as u64 - 1)
+    }
+
+    /// Known events that are set in `mask`, in order of their number
+    pub fn from_mask(mask: u64) -> Vec<HostEvent> {
+        (1..=64)
+            .filter(|event| mask & (1 << (event - 1)) != 0)
+            .filter_map(HostEvent::from_u64)
+            .collect()
+    }
+}
+
+/// State of the switches and the power supply at one point in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcStatus {
+    pub lid_open: bool,
+    pub power_button_pressed: bool,
+    pub ac_present: bool,
+    /// `false` without a battery
+    pub battery_critical: bool,
+    /// `None` if the EC has no chassis intrusion switch
+    pub chassis_open: Option<bool>,
+    /// Pending host events, see [`HostEvent::mask`]
+    pub host_events: u64,
+}
+
+impl EcStatus {
+    pub fn read(ec: &CrosEc) -> Option<EcStatus> {
+        let memmap = EcMemmap::read(ec)?;
+        let chassis_open = ec
+            .get_intrusion_status()
+            .ok()
+            .map(|status| status.currently_open);
+        Some(EcStatus::from_memmap(&memmap, chassis_open))
+    }
+
+    pub fn from_memmap(memmap: &EcMemmap, chassis_open: Option<bool>) -> EcStatus {
+        EcStatus {
+            lid_open: memmap.switches.lid_open,
+            power_button_pressed: memmap.switches.power_button_pressed,
+            ac_present: memmap.power.ac_present,
+            battery_critical: memmap
+                .power
+                .battery
+                .as_ref()
+                .is_some_and(|battery| battery.level_critical),
+            chassis_open,
+            host_events: memmap.host_events,
+        }
+    }
+
+    /// What changed since `old`, host events only once they are raised
+    pub fn changes_since(&self, old: &EcStatus) -> Vec<EcEvent> {
+        let mut events = Vec::new();
+        if self.lid_open != old.lid_open {
+            events.push(EcEvent::Lid {
+                open: self.lid_open,
+            });
+        }
+        if self.power_button_pressed != old.power_button_pressed {
+            events.push(EcEvent::PowerButton {
+                pressed: self.power_button_pressed,
+            });
+        }
+        if self.ac_present != old.ac_present {
+            events.push(EcEvent::Ac {
+                present: self.ac_present,
+            });
+        }
+        if self.battery_critical != old.battery_critical {
+            events.push(EcEvent::BatteryCritical {
+                critical: self.battery_critical,
+            });
+        }
+        if let (Some(open), Some(was_open)) = (self.chassis_open, old.chassis_open) {
+            if open != was_open {
+                events.push(EcEvent::Chassis { open });
+            }
+        }
+        let raised = self.host_events & !old.host_events;
+        events.extend(HostEvent::from_mask(raised).into_iter().map(EcEvent::Host));
+        events
+    }
+}
+
+/// A change reported by [`EventMonitor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcEvent {
+    Lid {
+        open: bool,
+    },
+    PowerButton {
+        pressed: bool,
+    },
+    Ac {
+        present: bool,
+    },
+    BatteryCritical {
+        critical: bool,
+    },
+    Chassis {
+        open: bool,
+    },
+    /// A host event was raised
+    Host(HostEvent),
+}
+
+/// Polls the EC and reports what changed
+pub struct EventMonitor {
+    ec: CrosEc,
+    status: Option<EcStatus>,
+}
+
+impl EventMonitor {
+    pub fn new(ec: &CrosEc) -> EventMonitor {
+        EventMonitor {
+            ec: ec.clone(),
+            status: None,
+        }
+    }
+
+    /// State at the last successful poll
+    pub fn status(&self) -> Option<EcStatus> {
+        self.status
+    }
+
+    /// Read the EC and return what changed since the last poll
+    ///
+    /// The first successful poll only records the state and returns no events. If the EC can't
+    /// be read, nothing is returned and the next poll compares against the last good state.
+    pub fn poll(&mut self) -> Vec<EcEvent> {
+        let Some(status) = EcStatus::read(&self.ec) else {
+            return Vec::new();
+        };
+        let events = match &self.status {
+            Some(old) => status.changes_since(old),
+            None => Vec::new(),
+        };
+        self.status = Some(status);
+        events
+    }
+
+    /// Poll every `interval` and yield events as they happen, never ends
+    ///
+    /// Only the first poll happens right away, also while the EC can't be read.
+    #[cfg(not(feature = "uefi"))]
+    pub fn watch(mut self, interval: std::time::Duration) -> impl Iterator<Item = EcEvent> {
+        let mut pending = alloc::collections::VecDeque::new();
+        let mut polled = false;
+        core::iter::from_fn(move || loop {
+            if let Some(event) = pending.pop_front() {
+                return Some(event);
+            }
+            if polled {
+                std::thread::sleep(interval);
+            }
+            polled = true;
+            pending.extend(self.poll());
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chromium_ec::memmap::{EC_MEMMAP_BATT_FLAG, EC_MEMMAP_HOST_EVENTS};
+    use crate::chromium_ec::{mock, CrosEcDriverType};
+
+    #[test]
+    fn host_event_mask() {
+        assert_eq!(HostEvent::LidClosed.mask(), 1);
+        assert_eq!(HostEvent::BatteryCritical.mask(), 0x40);
+        assert_eq!(
+            HostEvent::from_mask(0x8000_0042),
+            [
+                HostEvent::LidOpen,
+                HostEvent::BatteryCritical,
+                HostEvent::Invalid
+            ]
+        );
+        // Bits beyond the known events are ignored
+        assert_eq!(HostEvent::from_mask(1 << 40), []);
+    }
+
+    #[test]
+    fn monitor_mock() {
+        let _mock = mock::lock_for_test();
+        let ec = CrosEc::with(CrosEcDriverType::Mock).unwrap();
+        let mut monitor = EventMonitor::new(&ec);
+        assert_eq!(monitor.poll(), []);
+        let status = monitor.status().unwrap();
+        assert!(status.lid_open && status.ac_present && !status.battery_critical);
+        assert_eq!(status.chassis_open, Some(false));
+
+        mock::set_chassis_open(true);
+        mock::set_lid_open(false);
+        // On battery, which is critical
+        mock::write_memory(EC_MEMMAP_BATT_FLAG, &[0x02 | 0x04 | 0x10]);
+        mock::write_memory(
+            EC_MEMMAP_HOST_EVENTS,
+            &(HostEvent::LidClosed.mask() | HostEvent::AcDisconnected.mask()).to_le_bytes(),
+        );
+        assert_eq!(
+            monitor.poll(),
+            [
+                EcEvent::Lid { open: false },
+                EcEvent::Ac { present: false },
+                EcEvent::BatteryCritical { critical: true },
+                EcEvent::Chassis { open: true },
+                EcEvent::Host(HostEvent::LidClosed),
+                EcEvent::Host(HostEvent::AcDisconnected),
+            ]
+        );
+        assert_eq!(monitor.poll(), []);
+
+        mock::set_lid_open(true);
+        assert_eq!(monitor.poll(), [EcEvent::Lid { open: true }]);
+    }
+
+    #[test]
+    fn watch_waits_between_polls() {
+        use std::time::Duration;
+
+        let _mock = mock::lock_for_test();
+        let ec = CrosEc::with(CrosEcDriverType::Mock).unwrap();
+        mock::set_memory_unreadable(true);
+        let changes = std::thread::spawn(|| {
+            std::thread::sleep(Duration::from_millis(100));
+            mock::set_memory_unreadable(false);
+            std::thread::sleep(Duration::from_millis(100));
+            mock::set_lid_open(false);
+        });
+
+        let reads_before = mock::memory_reads();
+        let started = std::time::Instant::now();
+        let mut events = EventMonitor::new(&ec).watch(Duration::from_millis(20));
+        // Nothing is reported while the EC can't be read
+        assert_eq!(events.next(), Some(EcEvent::Lid { open: false }));
+        let elapsed = started.elapsed();
+        changes.join().unwrap();
+        // Only the first poll is immediate, so at most one read per interval after it.
+        // Not checked from below, a busy machine may poll less often.
+        let reads = mock::memory_reads() - reads_before;
+        assert!(
+            reads as u128 <= 1 + elapsed.as_millis() / 20,
+            "{reads} reads in {elapsed:?}"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Keep EC transactions from interleaving
+//!
+//! A host command is several reads and writes on the LPC ports (or an ioctl on the `cros_ec`
+//! device) that must not be interrupted by another command. Threads of one process take turns
+//! through [`transaction`]. Drivers that talk to hardware additionally take an advisory lock on
+//! [`LOCK_FILE_PATH`] with [`across_processes`], so separate processes like a GUI, a CLI and a
+//! daemon take turns as well.
+//!
+//! The lock file is created by root in a directory only root can write to, so other users can't
+//! plant one they hold forever. A lock that isn't released within [`LOCK_TIMEOUT`] fails the
+//! command instead of hanging it.
+
+#[cfg(not(feature = "uefi"))]
+use std::sync::{Mutex, MutexGuard};
+
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use alloc::format;
+
+#[cfg(unix)]
+use crate::chromium_ec::{EcError, EcResult};
+
+/// Lock file shared by all processes that talk to the EC
+#[cfg(unix)]
+pub const LOCK_FILE_PATH: &str = "/run/framework_ec.lock";
+
+/// How long to wait for another process to finish its EC command
+#[cfg(unix)]
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[cfg(unix)]
+const RETRY_INTERVAL: Duration = Duration::from_millis(5);
+
+#[cfg(not(feature = "uefi"))]
+static TRANSACTION: Mutex<()> = Mutex::new(());
+
+#[cfg(unix)]
+lazy_static! {
+    /// `None` if the lock file can't be opened, then only threads are kept apart
+    static ref LOCK_FILE: Option<File> = open_lock_file();
+}
+
+/// Exclusive use of the EC within this process until dropped
+pub struct Transaction {
+    #[cfg(not(feature = "uefi"))]
+    _guard: MutexGuard<'static, ()>,
+}
+
+/// Wait until no other thread of this process uses the EC
+pub fn transaction() -> Transaction {
+    Transaction {
+        #[cfg(not(feature = "uefi"))]
+        _guard: TRANSACTION.lock().unwrap_or_else(|err| err.into_inner()),
+    }
+}
+
+/// Exclusive use of the EC on this machine until dropped
+pub struct ProcessLock {
+    #[cfg(unix)]
+    file: &'static File,
+}
+
+/// Wait until no other process uses the EC, at most [`LOCK_TIMEOUT`]
+///
+/// Must be called within a [`Transaction`], threads share the lock of their process. Returns
+/// `None` if there is no lock file to take turns on.
+#[cfg(unix)]
+pub fn across_processes() -> EcResult<Option<ProcessLock>> {
+    let Some(file) = LOCK_FILE.as_ref() else {
+        return Ok(None);
+    };
+    let deadline = Instant::now() + LOCK_TIMEOUT;
+    loop {
+        match flock(file, libc::LOCK_EX | libc::LOCK_NB) {
+            Ok(()) => return Ok(Some(ProcessLock { file })),
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(EcError::DeviceError(format!(
+                        "Another process held {} for more than {:?}",
+                        LOCK_FILE_PATH, LOCK_TIMEOUT
+                    )));
+                }
+                std::thread::sleep(RETRY_INTERVAL);
+            }
+            Err(err) => {
+                debug!("Failed to lock {}: {:?}", LOCK_FILE_PATH, err);
+                return Ok(None);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn across_processes() -> EcResult<Option<ProcessLock>> {
+    Ok(None)
+}
+
+impl Drop for ProcessLock {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        let _ = flock(self.file, libc::LOCK_UN);
+    }
+}
+
+#[cfg(unix)]
+fn open_lock_file() -> Option<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    // Never follow a link planted in place of the lock file
+    let open = |create| {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(create)
+            .create(create)
+            .mode(0o644)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(LOCK_FILE_PATH)
+    };
+    // Only root can create the file, locking only needs read access
+    let file = open(true).or_else(|_| open(false));
+    match file {
+        Ok(file) => Some(file),
+        Err(err) => {
+            debug!("Not locking {}: {:?}", LOCK_FILE_PATH, err);
+            None
+        }
+    }
+}
+
+#[cfg(unix)]
+fn flock(file: &File, operation: libc::c_int) -> std::io::Result<()> {
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_file_excludes_other_processes() {
+        if LOCK_FILE.is_none() {
+            // Not running as root and no lock file yet
+            return;
+        }
+        // A separate open file description, like another process would have
+        let other = File::open(LOCK_FILE_PATH).unwrap();
+        let try_lock = || flock(&other, libc::LOCK_EX | libc::LOCK_NB).is_ok();
+
+        let first = transaction();
+        let lock = across_processes().unwrap().unwrap();
+        assert!(!try_lock());
+        drop(lock);
+        drop(first);
+        assert!(try_lock());
+
+        // Held by the other process, so waiting gives up
+        let second = transaction();
+        let start = Instant::now();
+        assert!(matches!(across_processes(), Err(EcError::DeviceError(_))));
+        assert!(start.elapsed() >= LOCK_TIMEOUT);
+        drop(second);
+        flock(&other, libc::LOCK_UN).unwrap();
+    }
+}
@@ -0,0 +1,424 @@
+//! Typed snapshot of the EC memory map
+//!
+//! The EC mirrors temperatures, fan speeds, switches, host events, the battery state and the
+//! motion sensors into a small memory region that the host can read at any time.
+//! [`EcMemmap::parse`] decodes a raw copy of it, e.g. from [`CrosEc::dump_mem_region`], so it can
+//! be tested against captured dumps as well.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+use crate::chromium_ec::{CrosEc, EC_MEMMAP_ID};
+use crate::power::{
+    AccelData, BatteryInformation, FanSpeed, LidAngle, PowerInfo, TempSensor, EC_FAN_SPEED_ENTRIES,
+};
+
+// The offset address of each type of data in mapped memory.
+pub(crate) const EC_MEMMAP_TEMP_SENSOR: u16 = 0x00; // Temp sensors 0x00 - 0x0f
+pub(crate) const EC_MEMMAP_FAN: u16 = 0x10; // Fan speeds 0x10 - 0x17
+pub(crate) const EC_MEMMAP_TEMP_SENSOR_B: u16 = 0x18; // More temp sensors 0x18 - 0x1f
+pub(crate) const EC_MEMMAP_ID_VERSION: u16 = 0x22; // Version of data in 0x20 - 0x2f
+pub(crate) const EC_MEMMAP_THERMAL_VERSION: u16 = 0x23; // Version of data in 0x00 - 0x1f
+pub(crate) const EC_MEMMAP_BATTERY_VERSION: u16 = 0x24; // Version of data in 0x40 - 0x7f
+pub(crate) const EC_MEMMAP_SWITCHES_VERSION: u16 = 0x25; // Version of data in 0x30 - 0x33
+pub(crate) const EC_MEMMAP_EVENTS_VERSION: u16 = 0x26; // Version of data in 0x34 - 0x3f
+pub(crate) const EC_MEMMAP_HOST_CMD_FLAGS: u16 = 0x27; // Host cmd interface flags (8 bits)
+                                                       // Unused 0x28 - 0x2f
+pub(crate) const EC_MEMMAP_SWITCHES: u16 = 0x30; // 8 bits
+                                                 // Unused 0x31 - 0x33
+pub(crate) const EC_MEMMAP_HOST_EVENTS: u16 = 0x34; // 64 bits
+                                                    // Battery values are all 32 bits, unless otherwise noted.
+pub(crate) const EC_MEMMAP_BATT_VOLT: u16 = 0x40; // Battery Present Voltage
+pub(crate) const EC_MEMMAP_BATT_RATE: u16 = 0x44; // Battery Present Rate
+pub(crate) const EC_MEMMAP_BATT_CAP: u16 = 0x48; // Battery Remaining Capacity
+pub(crate) const EC_MEMMAP_BATT_FLAG: u16 = 0x4c; // Battery State, see below (8-bit)
+pub(crate) const EC_MEMMAP_BATT_COUNT: u16 = 0x4d; // Battery Count (8-bit)
+pub(crate) const EC_MEMMAP_BATT_INDEX: u16 = 0x4e; // Current Battery Data Index (8-bit)
+                                                   // Unused 0x4f
+pub(crate) const EC_MEMMAP_BATT_DCAP: u16 = 0x50; // Battery Design Capacity
+pub(crate) const EC_MEMMAP_BATT_DVLT: u16 = 0x54; // Battery Design Voltage
+pub(crate) const EC_MEMMAP_BATT_LFCC: u16 = 0x58; // Battery Last Full Charge Capacity
+pub(crate) const EC_MEMMAP_BATT_CCNT: u16 = 0x5c; // Battery Cycle Count
+                                                  // Strings are all 8 bytes (EC_MEMMAP_TEXT_MAX)
+pub(crate) const EC_MEMMAP_BATT_MFGR: u16 = 0x60; // Battery Manufacturer String
+pub(crate) const EC_MEMMAP_BATT_MODEL: u16 = 0x68; // Battery Model Number String
+pub(crate) const EC_MEMMAP_BATT_SERIAL: u16 = 0x70; // Battery Serial Number String
+pub(crate) const EC_MEMMAP_BATT_TYPE: u16 = 0x78; // Battery Type String
+pub(crate) const EC_MEMMAP_ALS: u16 = 0x80; // ALS readings in lux (2 X 16 bits)
+                                            // Unused 0x84 - 0x8f
+pub(crate) const EC_MEMMAP_ACC_STATUS: u16 = 0x90; // Accelerometer status (8 bits )
+                                                   // Unused 0x91
+pub(crate) const EC_MEMMAP_ACC_DATA: u16 = 0x92; // Accelerometers data 0x92 - 0x9f
+                                                 // 0x92: u16Lid Angle if available, LID_ANGLE_UNRELIABLE otherwise
+                                                 // 0x94 - 0x99: u161st Accelerometer
+                                                 // 0x9a - 0x9f: u162nd Accelerometer
+const _EC_MEMMAP_GYRO_DATA: u16 = 0xa0; // Gyroscope data 0xa0 - 0xa5
+                                        // Unused 0xa6 - 0xdf
+
+/// Maximum length of strings in memmap
+pub(crate) const EC_MEMMAP_TEXT_MAX: u16 = 8;
+
+// Battery bit flags at EC_MEMMAP_BATT_FLAG.
+pub(crate) const EC_BATT_FLAG_AC_PRESENT: u8 = 0x01;
+pub(crate) const EC_BATT_FLAG_BATT_PRESENT: u8 = 0x02;
+pub(crate) const EC_BATT_FLAG_DISCHARGING: u8 = 0x04;
+pub(crate) const EC_BATT_FLAG_CHARGING: u8 = 0x08;
+pub(crate) const EC_BATT_FLAG_LEVEL_CRITICAL: u8 = 0x10;
+
+// Switch flags at EC_MEMMAP_SWITCHES
+pub(crate) const EC_SWITCH_LID_OPEN: u8 = 0x01;
+const EC_SWITCH_POWER_BUTTON_PRESSED: u8 = 0x02;
+const EC_SWITCH_WRITE_PROTECT_DISABLED: u8 = 0x04;
+const EC_SWITCH_DEDICATED_RECOVERY: u8 = 0x10;
+
+// Accelerometer status bits at EC_MEMMAP_ACC_STATUS
+const EC_MEMMAP_ACC_STATUS_SAMPLE_ID_MASK: u8 = 0x0F;
+const EC_MEMMAP_ACC_STATUS_BUSY_BIT: u8 = 0x10;
+const EC_MEMMAP_ACC_STATUS_PRESENCE_BIT: u8 = 0x80;
+
+/// Number of temperature sensor slots in the first and second block
+const EC_TEMP_SENSOR_ENTRIES: usize = 8;
+const EC_TEMP_SENSOR_B_ENTRIES: usize = 8;
+
+/// Versions of the data in the blocks of the memory map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemmapVersions {
+    pub id: u8,
+    pub thermal: u8,
+    pub battery: u8,
+    pub switches: u8,
+    pub events: u8,
+}
+
+/// State of the switches the EC reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Switches {
+    pub lid_open: bool,
+    pub power_button_pressed: bool,
+    pub write_protect_disabled: bool,
+    /// Recovery mode was requested with a dedicated signal
+    pub dedicated_recovery: bool,
+}
+impl From<u8> for Switches {
+    fn from(flags: u8) -> Self {
+        Self {
+            lid_open: 0 != (flags & EC_SWITCH_LID_OPEN),
+            power_button_pressed: 0 != (flags & EC_SWITCH_POWER_BUTTON_PRESSED),
+            write_protect_disabled: 0 != (flags & EC_SWITCH_WRITE_PROTECT_DISABLED),
+            dedicated_recovery: 0 != (flags & EC_SWITCH_DEDICATED_RECOVERY),
+        }
+    }
+}
+
+/// Lid angle and accelerometer readings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accelerometers {
+    pub present: bool,
+    /// The EC is updating the readings, they might be inconsistent
+    pub busy: bool,
+    /// Incremented with every new sample, wraps at 16
+    pub sample_id: u8,
+    pub lid_angle: LidAngle,
+    pub sensors: [AccelData; 2],
+}
+
+/// Everything the EC memory map holds, decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EcMemmap {
+    /// Magic bytes, `EC` if the memory map is valid
+    pub id: [u8; 2],
+    pub versions: MemmapVersions,
+    pub host_cmd_flags: u8,
+    /// All temperature sensor slots, including ones that are not present. The second block of
+    /// 8 sensors is only included if the thermal data is version 2 or later.
+    pub temperatures: Vec<TempSensor>,
+    /// All fan slots, including stalled and not present fans
+    pub fans: Vec<FanSpeed>,
+    pub switches: Switches,
+    /// Host events that are pending, one bit per event
+    pub host_events: u64,
+    pub power: PowerInfo,
+    /// Ambient light sensor readings in lux
+    pub als: [u16; 2],
+    pub accelerometers: Accelerometers,
+}
+
+impl EcMemmap {
+    /// Bytes of the memory map that are needed to decode it
+    pub const MIN_LEN: usize = 0xA0;
+
+    /// Read the whole memory map at once
+    pub fn read(ec: &CrosEc) -> Option<EcMemmap> {
+        EcMemmap::parse(&ec.dump_mem_region()?)
+    }
+
+    /// Decode a copy of the memory map, starting at offset 0
+    ///
+    /// Returns `None` if `data` is shorter than [`EcMemmap::MIN_LEN`].
+    pub fn parse(data: &[u8]) -> Option<EcMemmap> {
+        if data.len() < Self::MIN_LEN {
+            return None;
+        }
+        let byte = |offset: u16| data[offset as usize];
+        let bytes = |offset: u16, len: usize| &data[offset as usize..offset as usize + len];
+        let u16_at = |offset: u16| u16::from_le_bytes(bytes(offset, 2).try_into().unwrap());
+        let u32_at = |offset: u16| u32::from_le_bytes(bytes(offset, 4).try_into().unwrap());
+        let string_at = |offset: u16| {
+            String::from_utf8_lossy(bytes(offset, EC_MEMMAP_TEXT_MAX as usize)).replace('\0', "")
+        };
+
+        // The second block of sensors only exists since version 2
+        let thermal_version = byte(EC_MEMMAP_THERMAL_VERSION);
+        let sensors_b = if thermal_version >= 2 {
+            bytes(EC_MEMMAP_TEMP_SENSOR_B, EC_TEMP_SENSOR_B_ENTRIES)
+        } else {
+            &[]
+        };
+        let temperatures = bytes(EC_MEMMAP_TEMP_SENSOR, EC_TEMP_SENSOR_ENTRIES)
+            .iter()
+            .chain(sensors_b)
+            .map(|temp| TempSensor::from(*temp))
+            .collect();
+        let fans = (0..EC_FAN_SPEED_ENTRIES as u16)
+            .map(|fan| FanSpeed::from(u16_at(EC_MEMMAP_FAN + 2 * fan)))
+            .collect();
+
+        let battery_flag = byte(EC_MEMMAP_BATT_FLAG);
+        debug!("AC/Battery flag: {:#X}", battery_flag);
+        let remaining_capacity = u32_at(EC_MEMMAP_BATT_CAP);
+        let last_full_charge_capacity = u32_at(EC_MEMMAP_BATT_LFCC);
+        let battery = if 0 != (battery_flag & EC_BATT_FLAG_BATT_PRESENT) {
+            Some(BatteryInformation {
+                present_voltage: u32_at(EC_MEMMAP_BATT_VOLT),
+                present_rate: u32_at(EC_MEMMAP_BATT_RATE),
+                remaining_capacity,
+                battery_count: byte(EC_MEMMAP_BATT_COUNT),
+                current_battery_index: byte(EC_MEMMAP_BATT_INDEX),
+                design_capacity: u32_at(EC_MEMMAP_BATT_DCAP),
+                design_voltage: u32_at(EC_MEMMAP_BATT_DVLT),
+                last_full_charge_capacity,
+                cycle_count: u32_at(EC_MEMMAP_BATT_CCNT),
+                charge_percentage: remaining_capacity
+                    .checked_mul(100)
+                    .and_then(|cap| cap.checked_div(last_full_charge_capacity))
+                    .unwrap_or(0),
+                manufacturer: string_at(EC_MEMMAP_BATT_MFGR),
+                model_number: string_at(EC_MEMMAP_BATT_MODEL),
+                serial_number: string_at(EC_MEMMAP_BATT_SERIAL),
+                battery_type: string_at(EC_MEMMAP_BATT_TYPE),
+                // TODO: Can both be true/falses at the same time?
+                discharging: 0 != (battery_flag & EC_BATT_FLAG_DISCHARGING),
+                charging: 0 != (battery_flag & EC_BATT_FLAG_CHARGING),
+                level_critical: 0 != (battery_flag & EC_BATT_FLAG_LEVEL_CRITICAL),
+            })
+        } else {
+            None
+        };
+
+        let acc_status = byte(EC_MEMMAP_ACC_STATUS);
+
+        Some(EcMemmap {
+            id: [byte(EC_MEMMAP_ID), byte(EC_MEMMAP_ID + 1)],
+            versions: MemmapVersions {
+                id: byte(EC_MEMMAP_ID_VERSION),
+                thermal: thermal_version,
+                battery: byte(EC_MEMMAP_BATTERY_VERSION),
+                switches: byte(EC_MEMMAP_SWITCHES_VERSION),
+                events: byte(EC_MEMMAP_EVENTS_VERSION),
+            },
+            host_cmd_flags: byte(EC_MEMMAP_HOST_CMD_FLAGS),
+            temperatures,
+            fans,
+            switches: Switches::from(byte(EC_MEMMAP_SWITCHES)),
+            host_events: u64::from_le_bytes(bytes(EC_MEMMAP_HOST_EVENTS, 8).try_into().unwrap()),
+            power: PowerInfo {
+                ac_present: 0 != (battery_flag & EC_BATT_FLAG_AC_PRESENT),
+                battery,
+            },
+            als: [u16_at(EC_MEMMAP_ALS), u16_at(EC_MEMMAP_ALS + 2)],
+            accelerometers: Accelerometers {
+                present: 0 != (acc_status & EC_MEMMAP_ACC_STATUS_PRESENCE_BIT),
+                busy: 0 != (acc_status & EC_MEMMAP_ACC_STATUS_BUSY_BIT),
+                sample_id: acc_status & EC_MEMMAP_ACC_STATUS_SAMPLE_ID_MASK,
+                lid_angle: LidAngle::from(u16_at(EC_MEMMAP_ACC_DATA)),
+                sensors: [
+                    AccelData::from(bytes(EC_MEMMAP_ACC_DATA + 2, 6).to_vec()),
+                    AccelData::from(bytes(EC_MEMMAP_ACC_DATA + 8, 6).to_vec()),
+                ],
+            },
+        })
+    }
+
+    /// Whether the magic bytes show that the EC mapped its memory
+    pub fn is_valid(&self) -> bool {
+        self.id == *b"EC"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chromium_ec::{mock, CrosEcDriverType, EC_MEMMAP_SIZE};
+
+    #[test]
+    fn parse_dump() {
+        let mut dump = [0u8; EC_MEMMAP_SIZE as usize - 1];
+        let mut write = |offset: u16, data: &[u8]| {
+            dump[offset as usize..offset as usize + data.len()].copy_from_slice(data)
+        };
+        write(EC_MEMMAP_TEMP_SENSOR, &[73 + 42, 0xFF, 0xFE, 0xFD, 0xFC]);
+        write(EC_MEMMAP_TEMP_SENSOR + 5, &[0xFF; 3]);
+        write(EC_MEMMAP_TEMP_SENSOR_B, &[73 + 30, 0xFF, 0xFF, 0xFF, 0xFF]);
+        write(EC_MEMMAP_TEMP_SENSOR_B + 5, &[0xFF; 3]);
+        write(
+            EC_MEMMAP_FAN,
+            &[0xD0, 0x07, 0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+        );
+        write(EC_MEMMAP_ID, b"EC\x01\x02\x01\x01\x01\x03");
+        write(
+            EC_MEMMAP_SWITCHES,
+            &[EC_SWITCH_LID_OPEN | EC_SWITCH_WRITE_PROTECT_DISABLED],
+        );
+        write(
+            EC_MEMMAP_HOST_EVENTS,
+            &0x8000_0000_0000_0402u64.to_le_bytes(),
+        );
+        write(
+            EC_MEMMAP_BATT_FLAG,
+            &[
+                EC_BATT_FLAG_AC_PRESENT | EC_BATT_FLAG_BATT_PRESENT | EC_BATT_FLAG_CHARGING,
+                1,
+                0,
+            ],
+        );
+        write(EC_MEMMAP_BATT_VOLT, &17_200u32.to_le_bytes());
+        write(EC_MEMMAP_BATT_RATE, &1_500u32.to_le_bytes());
+        write(EC_MEMMAP_BATT_CAP, &2_000u32.to_le_bytes());
+        write(EC_MEMMAP_BATT_DCAP, &3_915u32.to_le_bytes());
+        write(EC_MEMMAP_BATT_DVLT, &15_480u32.to_le_bytes());
+        write(EC_MEMMAP_BATT_LFCC, &4_000u32.to_le_bytes());
+        write(EC_MEMMAP_BATT_CCNT, &42u32.to_le_bytes());
+        write(EC_MEMMAP_BATT_MFGR, b"NVT\0\0\0\0\0");
+        write(EC_MEMMAP_BATT_MODEL, b"FRANGWA");
+        write(EC_MEMMAP_BATT_SERIAL, b"0123");
+        write(EC_MEMMAP_BATT_TYPE, b"LION");
+        write(EC_MEMMAP_ALS, &[0x2C, 0x01, 0x05, 0x00]);
+        write(
+            EC_MEMMAP_ACC_STATUS,
+            &[EC_MEMMAP_ACC_STATUS_PRESENCE_BIT | 0x03],
+        );
+        write(EC_MEMMAP_ACC_DATA, &[0xF4, 0x01, 1, 0, 0xFF, 0xFF, 0, 0x40]);
+
+        let memmap = EcMemmap::parse(&dump).unwrap();
+        assert!(memmap.is_valid());
+        assert_eq!(
+            memmap.versions,
+            MemmapVersions {
+                id: 1,
+                thermal: 2,
+                battery: 1,
+                switches: 1,
+                events: 1,
+            }
+        );
+        assert_eq!(memmap.host_cmd_flags, 3);
+        assert_eq!(
+            memmap.temperatures[..6],
+            [
+                TempSensor::Ok(42),
+                TempSensor::NotPresent,
+                TempSensor::Error,
+                TempSensor::NotPowered,
+                TempSensor::NotCalibrated,
+                TempSensor::NotPresent
+            ]
+        );
+        assert_eq!(memmap.temperatures.len(), 16);
+        assert_eq!(
+            EcMemmap::parse(&[&dump[..0x23], &[1], &dump[0x24..]].concat())
+                .unwrap()
+                .temperatures
+                .len(),
+            8
+        );
+        assert_eq!(memmap.temperatures[8], TempSensor::Ok(30));
+        assert_eq!(
+            memmap.fans,
+            [
+                FanSpeed::Rpm(2000),
+                FanSpeed::Stalled,
+                FanSpeed::NotPresent,
+                FanSpeed::NotPresent
+            ]
+        );
+        assert_eq!(
+            memmap.switches,
+            Switches {
+                lid_open: true,
+                power_button_pressed: false,
+                write_protect_disabled: true,
+                dedicated_recovery: false,
+            }
+        );
+        assert_eq!(memmap.host_events, 0x8000_0000_0000_0402);
+
+        assert!(memmap.power.ac_present);
+        let battery = memmap.power.battery.unwrap();
+        assert_eq!(battery.charge_percentage, 50);
+        assert_eq!(battery.present_voltage, 17_200);
+        assert_eq!(battery.design_capacity, 3_915);
+        assert_eq!(battery.cycle_count, 42);
+        assert_eq!(battery.battery_count, 1);
+        assert_eq!(battery.manufacturer, "NVT");
+        assert_eq!(battery.model_number, "FRANGWA");
+        assert_eq!(battery.serial_number, "0123");
+        assert!(battery.charging && !battery.discharging && !battery.level_critical);
+        let cap = EC_MEMMAP_BATT_CAP as usize;
+        let unknown_cap = [&dump[..cap], &u32::MAX.to_le_bytes(), &dump[cap + 4..]].concat();
+        assert_eq!(
+            EcMemmap::parse(&unknown_cap)
+                .unwrap()
+                .power
+                .battery
+                .unwrap()
+                .charge_percentage,
+            0
+        );
+
+        assert_eq!(memmap.als, [300, 5]);
+        let accel = memmap.accelerometers;
+        assert!(accel.present && !accel.busy);
+        assert_eq!(accel.sample_id, 3);
+        assert_eq!(accel.lid_angle, LidAngle::Unreliable);
+        assert_eq!(
+            accel.sensors[0],
+            AccelData {
+                x: 1,
+                y: -1,
+                z: 0x4000
+            }
+        );
+
+        assert_eq!(EcMemmap::parse(&dump[..EcMemmap::MIN_LEN - 1]), None);
+    }
+
+    #[test]
+    fn read_from_mock() {
+        let _mock = mock::lock_for_test();
+        let ec = CrosEc::with(CrosEcDriverType::Mock).unwrap();
+
+        let memmap = EcMemmap::read(&ec).unwrap();
+        assert!(memmap.is_valid());
+        assert_eq!(memmap.temperatures.len(), 8);
+        assert_eq!(memmap.temperatures[0], TempSensor::Ok(45));
+        assert_eq!(memmap.fans[0], FanSpeed::Rpm(mock::MOCK_FAN_AUTO_RPM));
+        assert_eq!(
+            memmap.power,
+            PowerInfo {
+                ac_present: true,
+                battery: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,413 @@
+//! Simulated EC that lives entirely in-process
+//!
+//! Answers the host commands needed to drive RGB lighting and fans, serves a fake memory map
+//! and records every request it receives. Useful to test code on machines without a Framework EC.
+
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use num_traits::FromPrimitive;
+use spin::Mutex;
+
+use crate::chromium_ec::command::EcCommands;
+use crate::chromium_ec::commands::{RgbS, EC_RGBKBD_MAX_KEY_COUNT};
+use crate::chromium_ec::memmap::{
+    EC_BATT_FLAG_AC_PRESENT, EC_MEMMAP_BATT_FLAG, EC_MEMMAP_FAN, EC_MEMMAP_SWITCHES,
+    EC_MEMMAP_TEMP_SENSOR, EC_SWITCH_LID_OPEN,
+};
+use crate::chromium_ec::{EcError, EcResponseStatus, EcResult, EC_MEMMAP_ID, EC_MEMMAP_SIZE};
+use crate::power::{EC_FAN_SPEED_ENTRIES, EC_FAN_SPEED_NOT_PRESENT};
+
+/// Number of RGB keys the simulated EC keeps colors for
+pub const MOCK_RGB_KEY_COUNT: usize = 256;
+/// RPM the simulated fans run at with 100% duty
+pub const MOCK_FAN_MAX_RPM: u32 = 6000;
+/// RPM the simulated fans run at under automatic control
+pub const MOCK_FAN_AUTO_RPM: u16 = 1200;
+/// Number of fans that are present in the simulated EC
+const MOCK_FAN_COUNT: usize = 1;
+
+/// Temperatures (in Celsius) reported in the first sensor slots of the memory map
+const MOCK_TEMPS: [u8; 4] = [45, 40, 30, 50];
+
+/// A host command as received by the simulated EC
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockRequest {
+    pub command: u16,
+    pub command_version: u8,
+    pub data: Vec<u8>,
+}
+
+/// How a simulated fan is currently being driven
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MockFanMode {
+    /// EC controls the fan on its own (default after reset or `autofanctrl`)
+    Auto,
+    /// Fixed duty cycle in percent
+    Duty(u32),
+    /// Fixed target RPM
+    Rpm(u32),
+}
+
+struct MockEc {
+    memmap: [u8; EC_MEMMAP_SIZE as usize],
+    rgb: [RgbS; MOCK_RGB_KEY_COUNT],
+    fans: [MockFanMode; MOCK_FAN_COUNT],
+    requests: Vec<MockRequest>,
+    failures: Vec<(u16, EcResponseStatus)>,
+    chassis_open: bool,
+    chassis_open_count: u8,
+    memory_reads: usize,
+    memory_unreadable: bool,
+}
+
+impl MockEc {
+    fn new() -> Self {
+        let mut ec = MockEc {
+            memmap: [0; EC_MEMMAP_SIZE as usize],
+            rgb: [RgbS::default(); MOCK_RGB_KEY_COUNT],
+            fans: [MockFanMode::Auto; MOCK_FAN_COUNT],
+            requests: vec![],
+            failures: vec![],
+            chassis_open: false,
+            chassis_open_count: 0,
+            memory_reads: 0,
+            memory_unreadable: false,
+        };
+
+        let id = EC_MEMMAP_ID as usize;
+        ec.memmap[id] = b'E';
+        ec.memmap[id + 1] = b'C';
+        // Version bytes of the ID, thermal, battery, switches and events blocks
+        for version in &mut ec.memmap[id + 2..id + 7] {
+            *version = 1;
+        }
+
+        let temps = EC_MEMMAP_TEMP_SENSOR as usize;
+        for slot in &mut ec.memmap[temps..temps + 0x10] {
+            *slot = 0xFF;
+        }
+        for (slot, temp) in MOCK_TEMPS.iter().enumerate() {
+            // Temperatures are stored with an offset of 73 (200K)
+            ec.memmap[temps + slot] = temp + 73;
+        }
+
+        for fan in 0..EC_FAN_SPEED_ENTRIES {
+            let rpm = if fan < MOCK_FAN_COUNT {
+                MOCK_FAN_AUTO_RPM
+            } else {
+                EC_FAN_SPEED_NOT_PRESENT
+            };
+            ec.set_fan_rpm(fan, rpm);
+        }
+
+        // Desktop-like: running on AC, no battery
+        ec.memmap[EC_MEMMAP_BATT_FLAG as usize] = EC_BATT_FLAG_AC_PRESENT;
+        // No lid, which the EC reports as always open
+        ec.memmap[EC_MEMMAP_SWITCHES as usize] = EC_SWITCH_LID_OPEN;
+
+        ec
+    }
+
+    fn set_fan_rpm(&mut self, fan: usize, rpm: u16) {
+        let offset = EC_MEMMAP_FAN as usize + fan * 2;
+        self.memmap[offset..offset + 2].copy_from_slice(&rpm.to_le_bytes());
+    }
+
+    fn set_fan_mode(&mut self, fan: Option<usize>, mode: MockFanMode) -> EcResult<()> {
+        let fans = match fan {
+            Some(fan) if fan >= MOCK_FAN_COUNT => {
+                return Err(EcError::Response(EcResponseStatus::InvalidParameter))
+            }
+            Some(fan) => fan..fan + 1,
+            None => 0..MOCK_FAN_COUNT,
+        };
+        for fan in fans {
+            self.fans[fan] = mode;
+            let rpm = match mode {
+                MockFanMode::Auto => MOCK_FAN_AUTO_RPM,
+                MockFanMode::Duty(percent) => (MOCK_FAN_MAX_RPM * percent / 100) as u16,
+                MockFanMode::Rpm(rpm) => rpm.min(MOCK_FAN_MAX_RPM) as u16,
+            };
+            self.set_fan_rpm(fan, rpm);
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, command: u16, command_version: u8, data: &[u8]) -> EcResult<Vec<u8>> {
+        if let Some((_, status)) = self.failures.iter().find(|(cmd, _)| *cmd == command) {
+            return Err(EcError::Response(*status));
+        }
+
+        let invalid_param = Err(EcError::Response(EcResponseStatus::InvalidParameter));
+        match (FromPrimitive::from_u16(command), command_version) {
+            (Some(EcCommands::GetVersion), 0) => {
+                let mut response = vec![0; 32 * 3 + 4];
+                copy_str(&mut response[0..32], "mock-ec-ro");
+                copy_str(&mut response[32..64], "mock-ec-rw");
+                // Running from RW image
+                response[96..100].copy_from_slice(&2u32.to_le_bytes());
+                Ok(response)
+            }
+            (Some(EcCommands::GetBuildInfo), 0) => {
+                let mut response = b"mock-ec 0.0.0".to_vec();
+                response.push(0);
+                Ok(response)
+            }
+            (Some(EcCommands::GetCmdVersions), version @ (0 | 1)) => {
+                let cmd = if version == 0 {
+                    read_u8(data, 0).map(u16::from)
+                } else {
+                    read_u32(data, 0).map(|cmd| cmd as u16)
+                };
+                let Some(cmd) = cmd else {
+                    return invalid_param;
+                };
+                let mask: u32 = match FromPrimitive::from_u16(cmd) {
+                    Some(EcCommands::GetCmdVersions)
+                    | Some(EcCommands::PwmSetFanTargetRpm)
+                    | Some(EcCommands::PwmSetFanDuty)
+                    | Some(EcCommands::AutoFanCtrl) => 0b11,
+                    Some(EcCommands::GetVersion)
+                    | Some(EcCommands::GetBuildInfo)
+                    | Some(EcCommands::ReadMemMap)
+                    | Some(EcCommands::RgbKbdSetColor)
+                    | Some(EcCommands::ChassisOpenCheck)
+                    | Some(EcCommands::ChassisIntrusion) => 0b01,
+                    _ => return invalid_param,
+                };
+                Ok(mask.to_le_bytes().to_vec())
+            }
+            (Some(EcCommands::ReadMemMap), 0) => {
+                let (Some(offset), Some(size)) = (read_u8(data, 0), read_u8(data, 1)) else {
+                    return invalid_param;
+                };
+                let (offset, size) = (offset as usize, size as usize);
+                if offset + size > self.memmap.len() {
+                    return invalid_param;
+                }
+                Ok(self.memmap[offset..offset + size].to_vec())
+            }
+            (Some(EcCommands::RgbKbdSetColor), 0) => {
+                let (Some(start), Some(length)) = (read_u8(data, 0), read_u8(data, 1)) else {
+                    return invalid_param;
+                };
+                let (start, length) = (start as usize, length as usize);
+                if length > EC_RGBKBD_MAX_KEY_COUNT
+                    || start + length > MOCK_RGB_KEY_COUNT
+                    || data.len() < 2 + 3 * length
+                {
+                    return invalid_param;
+                }
+                for (i, rgb) in data[2..2 + 3 * length].chunks_exact(3).enumerate() {
+                    self.rgb[start + i] = RgbS {
+                        r: rgb[0],
+                        g: rgb[1],
+                        b: rgb[2],
+                    };
+                }
+                Ok(vec![])
+            }
+            (Some(EcCommands::PwmSetFanTargetRpm), version @ (0 | 1)) => {
+                let Some(rpm) = read_u32(data, 0) else {
+                    return invalid_param;
+                };
+                let fan = if version == 1 {
+                    match read_u32(data, 4) {
+                        Some(fan) => Some(fan as usize),
+                        None => return invalid_param,
+                    }
+                } else {
+                    None
+                };
+                self.set_fan_mode(fan, MockFanMode::Rpm(rpm))?;
+                Ok(vec![])
+            }
+            (Some(EcCommands::PwmSetFanDuty), version @ (0 | 1)) => {
+                let Some(percent) = read_u32(data, 0) else {
+                    return invalid_param;
+                };
+                if percent > 100 {
+                    return invalid_param;
+                }
+                let fan = if version == 1 {
+                    match read_u32(data, 4) {
+                        Some(fan) => Some(fan as usize),
+                        None => return invalid_param,
+                    }
+                } else {
+                    None
+                };
+                self.set_fan_mode(fan, MockFanMode::Duty(percent))?;
+                Ok(vec![])
+            }
+            (Some(EcCommands::AutoFanCtrl), version @ (0 | 1)) => {
+                let fan = if version == 1 {
+                    match read_u8(data, 0) {
+                        Some(fan) => Some(fan as usize),
+                        None => return invalid_param,
+                    }
+                } else {
+                    None
+                };
+                self.set_fan_mode(fan, MockFanMode::Auto)?;
+                Ok(vec![])
+            }
+            (Some(EcCommands::ChassisOpenCheck), 0) => Ok(vec![self.chassis_open as u8]),
+            (Some(EcCommands::ChassisIntrusion), 0) => {
+                // Clearing the counters isn't simulated
+                let ever_opened = self.chassis_open_count > 0;
+                Ok(vec![ever_opened as u8, 0, self.chassis_open_count, 0])
+            }
+            (Some(_), _) if self.supports(command) => {
+                Err(EcError::Response(EcResponseStatus::InvalidVersion))
+            }
+            _ => Err(EcError::Response(EcResponseStatus::InvalidCommand)),
+        }
+    }
+
+    fn supports(&self, command: u16) -> bool {
+        matches!(
+            FromPrimitive::from_u16(command),
+            Some(EcCommands::GetVersion)
+                | Some(EcCommands::GetBuildInfo)
+                | Some(EcCommands::GetCmdVersions)
+                | Some(EcCommands::ReadMemMap)
+                | Some(EcCommands::RgbKbdSetColor)
+                | Some(EcCommands::PwmSetFanTargetRpm)
+                | Some(EcCommands::PwmSetFanDuty)
+                | Some(EcCommands::AutoFanCtrl)
+                | Some(EcCommands::ChassisOpenCheck)
+                | Some(EcCommands::ChassisIntrusion)
+        )
+    }
+}
+
+fn copy_str(buffer: &mut [u8], s: &str) {
+    let len = core::cmp::min(buffer.len() - 1, s.len());
+    buffer[..len].copy_from_slice(&s.as_bytes()[..len]);
+}
+
+fn read_u8(data: &[u8], offset: usize) -> Option<u8> {
+    data.get(offset).copied()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+lazy_static! {
+    static ref MOCK_EC: Mutex<MockEc> = Mutex::new(MockEc::new());
+}
+
+pub fn send_command(command: u16, command_version: u8, data: &[u8]) -> EcResult<Vec<u8>> {
+    let mut ec = MOCK_EC.lock();
+    ec.requests.push(MockRequest {
+        command,
+        command_version,
+        data: data.to_vec(),
+    });
+    ec.handle(command, command_version, data)
+}
+
+pub fn read_memory(offset: u16, length: u16) -> EcResult<Vec<u8>> {
+    let mut ec = MOCK_EC.lock();
+    ec.memory_reads += 1;
+    if ec.memory_unreadable {
+        return Err(EcError::DeviceError("Memory map unreadable".to_string()));
+    }
+    let (offset, length) = (offset as usize, length as usize);
+    if offset + length > ec.memmap.len() {
+        return Err(EcError::DeviceError(
+            "Read beyond end of memory map".to_string(),
+        ));
+    }
+    Ok(ec.memmap[offset..offset + length].to_vec())
+}
+
+/// The simulated EC is process-global, so tests using it must not run concurrently
+#[cfg(test)]
+static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Take the simulated EC for a test and reset it, until the guard is dropped
+#[cfg(test)]
+pub(crate) fn lock_for_test() -> std::sync::MutexGuard<'static, ()> {
+    let guard = TEST_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    reset();
+    guard
+}
+
+/// Put the simulated EC back into its power-on state
+///
+/// Clears recorded requests, injected failures, LED colors and fan overrides.
+pub fn reset() {
+    *MOCK_EC.lock() = MockEc::new();
+}
+
+/// All host commands received since the last reset or [`clear_requests`]
+pub fn requests() -> Vec<MockRequest> {
+    MOCK_EC.lock().requests.clone()
+}
+
+/// Forget the recorded requests, but keep the rest of the state
+pub fn clear_requests() {
+    MOCK_EC.lock().requests.clear();
+}
+
+/// Make every following invocation of `command` fail with `status`
+pub fn fail_command(command: EcCommands, status: EcResponseStatus) {
+    MOCK_EC.lock().failures.push((command as u16, status));
+}
+
+/// Colors currently shown on `count` keys, beginning at `start_key`
+pub fn rgb_colors(start_key: u8, count: usize) -> Vec<RgbS> {
+    let ec = MOCK_EC.lock();
+    let start = start_key as usize;
+    let end = core::cmp::min(start + count, MOCK_RGB_KEY_COUNT);
+    ec.rgb[start..end].to_vec()
+}
+
+/// How a fan is currently driven, `None` if the fan doesn't exist
+pub fn fan_mode(fan: usize) -> Option<MockFanMode> {
+    MOCK_EC.lock().fans.get(fan).copied()
+}
+
+/// Number of memory map reads since the last reset
+pub fn memory_reads() -> usize {
+    MOCK_EC.lock().memory_reads
+}
+
+/// Make every following memory map read fail, until called again with `false`
+pub fn set_memory_unreadable(unreadable: bool) {
+    MOCK_EC.lock().memory_unreadable = unreadable;
+}
+
+/// Overwrite part of the memory map, e.g. to simulate a temperature change
+pub fn write_memory(offset: u16, data: &[u8]) {
+    let mut ec = MOCK_EC.lock();
+    let offset = core::cmp::min(offset as usize, ec.memmap.len());
+    let end = core::cmp::min(offset + data.len(), ec.memmap.len());
+    ec.memmap[offset..end].copy_from_slice(&data[..end - offset]);
+}
+
+/// Open or close the chassis, opening counts towards the intrusion statistics
+pub fn set_chassis_open(open: bool) {
+    let mut ec = MOCK_EC.lock();
+    if open && !ec.chassis_open {
+        ec.chassis_open_count = ec.chassis_open_count.saturating_add(1);
+    }
+    ec.chassis_open = open;
+}
+
+/// Flip the lid switch in the memory map
+pub fn set_lid_open(open: bool) {
+    let mut ec = MOCK_EC.lock();
+    let switches = &mut ec.memmap[EC_MEMMAP_SWITCHES as usize];
+    if open {
+        *switches |= EC_SWITCH_LID_OPEN;
+    } else {
+        *switches &= !EC_SWITCH_LID_OPEN;
+    }
+}
@@ -7,6 +7,9 @@
 //! - `cros_ec` - It uses the `cros_ec` kernel module in Linux
 //! - `portio` - It uses raw port I/O. This works on UEFI and on Linux if the system isn't in lockdown mode (SecureBoot disabled).
 //! - `windows` - It uses [DHowett's Windows driver](https://github.com/DHowett/FrameworkWindowsUtils)
+//!
+//! Additionally the `mock` driver simulates an EC in-process, for testing without hardware, and
+//! the `replay` driver serves EC traffic that was recorded before, see [`replay`].
 
 use crate::ec_binary;
 use crate::os_specific;
@@ -23,20 +26,27 @@ pub mod command;
 pub mod commands;
 #[cfg(target_os = "linux")]
 mod cros_ec;
+pub mod events;
 pub mod i2c_passthrough;
 pub mod input_deck;
+pub mod lock;
+pub mod memmap;
+pub mod mock;
 #[cfg(not(windows))]
 mod portio;
 #[cfg(not(windows))]
 mod portio_mec;
 #[allow(dead_code)]
 mod protocol;
+pub mod replay;
 #[cfg(windows)]
 mod windows;
 
+use alloc::boxed::Box;
 use alloc::format;
 use alloc::string::String;
 use alloc::string::ToString;
+use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
 
@@ -87,7 +97,7 @@ pub enum MecFlashNotify {
 
 pub type EcResult<T> = Result<T, EcError>;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum EcError {
     Response(EcResponseStatus),
     UnknownResponseCode(u32),
@@ -206,14 +216,28 @@ const BOARD_VERSION_NPC_DB: [i32; BOARD_VERSION_COUNT] = [
     100, 311, 521, 721, 931, 1131, 1341, 1551, 1751, 1961, 2171, 2370, 2580, 2780, 2990, 3200,
 ];
 
+/// Transport to the EC
+///
+/// Implemented by the drivers in this crate, see [`CrosEcDriverType`], and by [`CrosEc`] itself.
+/// Other crates can implement it to plug their own transport into [`CrosEc::from_driver`].
 pub trait CrosEcDriver {
     fn read_memory(&self, offset: u16, length: u16) -> Option<Vec<u8>>;
+    /// Like [`CrosEcDriver::read_memory`], but with the reason a read failed
+    ///
+    /// Drivers that know the reason should implement it, it ends up in recorded traces.
+    fn try_read_memory(&self, offset: u16, length: u16) -> EcResult<Vec<u8>> {
+        self.read_memory(offset, length)
+            .ok_or_else(|| EcError::DeviceError("Failed to read memory".to_string()))
+    }
     fn send_command(&self, command: u16, command_version: u8, data: &[u8]) -> EcResult<Vec<u8>>;
 }
 
+/// Handle to the EC, cheap to clone and share between threads
+///
+/// Commands of all handles in a process are sent one at a time, see [`lock`].
 #[derive(Clone)]
 pub struct CrosEc {
-    driver: CrosEcDriverType,
+    driver: Arc<dyn CrosEcDriver + Send + Sync>,
 }
 
 impl Default for CrosEc {
@@ -225,7 +249,7 @@ impl Default for CrosEc {
 /// Find out which drivers are available
 ///
 /// Depending on the availability we choose the first one as default
-fn available_drivers() -> Vec<CrosEcDriverType> {
+pub fn available_drivers() -> Vec<CrosEcDriverType> {
     let mut drivers = vec![];
 
     #[cfg(windows)]
@@ -239,24 +263,32 @@ fn available_drivers() -> Vec<CrosEcDriverType> {
     #[cfg(not(windows))]
     drivers.push(CrosEcDriverType::Portio);
 
+    // Always available, but never picked by default
+    drivers.push(CrosEcDriverType::Mock);
+    drivers.push(CrosEcDriverType::Replay);
+
     drivers
 }
 
 impl CrosEc {
     pub fn new() -> CrosEc {
         debug!("Chromium EC Driver: {:?}", available_drivers()[0]);
-        CrosEc {
-            driver: available_drivers()[0],
-        }
+        CrosEc::from_driver(Box::new(BuiltinDriver(available_drivers()[0])))
     }
 
     pub fn with(driver: CrosEcDriverType) -> Option<CrosEc> {
-        if !available_drivers().contains(&driver) {
-            return None;
-        }
-        debug!("Chromium EC Driver: {:?}", driver);
+        let driver = BuiltinDriver::new(driver)?;
+        debug!("Chromium EC Driver: {:?}", driver.0);
+
+        Some(CrosEc::from_driver(Box::new(driver)))
+    }
 
-        Some(CrosEc { driver })
+    /// Talk to the EC through any driver, e.g. a remote transport or a wrapper around another
+    /// driver
+    pub fn from_driver(driver: Box<dyn CrosEcDriver + Send + Sync>) -> CrosEc {
+        CrosEc {
+            driver: Arc::from(driver),
+        }
     }
 
     /// Lock bus to PD controller in the beginning of flashing
@@ -1550,9 +1582,13 @@ impl CrosEc {
     }
 
     pub fn rgbkbd_set_color(&self, start_key: u8, colors: Vec<RgbS>) -> EcResult<()> {
+        // All keys must exist, before anything is sent
+        if let Some(last) = colors.len().checked_sub(1) {
+            rgbkbd_key(start_key, last)?;
+        }
         for (chunk, colors) in colors.chunks(EC_RGBKBD_MAX_KEY_COUNT).enumerate() {
             let mut request = EcRequestRgbKbdSetColor {
-                start_key: start_key + ((chunk * EC_RGBKBD_MAX_KEY_COUNT) as u8),
+                start_key: rgbkbd_key(start_key, chunk * EC_RGBKBD_MAX_KEY_COUNT)?,
                 length: colors.len() as u8,
                 color: [(); EC_RGBKBD_MAX_KEY_COUNT].map(|()| Default::default()),
             };
@@ -1567,12 +1603,29 @@ impl CrosEc {
     }
 }
 
+/// Index of the RGB key `offset` keys after `start_key`, key indices are a single byte
+fn rgbkbd_key(start_key: u8, offset: usize) -> EcResult<u8> {
+    u8::try_from(offset)
+        .ok()
+        .and_then(|offset| start_key.checked_add(offset))
+        .ok_or_else(|| {
+            EcError::DeviceError(format!(
+                "Key {} after key {} is beyond the last RGB key",
+                offset, start_key
+            ))
+        })
+}
+
 #[cfg_attr(not(feature = "uefi"), derive(clap::ValueEnum))]
 #[derive(Clone, Debug, Copy, PartialEq)]
 pub enum CrosEcDriverType {
     Portio,
     CrosEc,
     Windows,
+    /// Simulated EC, see [`mock`]
+    Mock,
+    /// Recorded EC traffic, see [`replay`]
+    Replay,
 }
 
 #[cfg_attr(not(feature = "uefi"), derive(clap::ValueEnum))]
@@ -1588,52 +1641,103 @@ pub enum HardwareDeviceType {
     AcRight,
 }
 
-impl CrosEcDriver for CrosEc {
-    fn read_memory(&self, offset: u16, length: u16) -> Option<Vec<u8>> {
-        if !smbios::is_framework() {
-            return None;
-        }
+/// One of the drivers built into this crate, for wrapping with [`CrosEc::from_driver`]
+pub struct BuiltinDriver(CrosEcDriverType);
 
-        debug!("read_memory(offset={:#X}, size={:#X})", offset, length);
-        if offset + length > EC_MEMMAP_SIZE {
+impl BuiltinDriver {
+    /// `None` if the driver isn't available on this platform, see [`available_drivers`]
+    pub fn new(driver: CrosEcDriverType) -> Option<BuiltinDriver> {
+        if !available_drivers().contains(&driver) {
             return None;
         }
+        Some(BuiltinDriver(driver))
+    }
 
+    /// Whether the driver talks to real hardware, only then it has to be a Framework system
+    fn is_simulated(&self) -> bool {
+        matches!(self.0, CrosEcDriverType::Mock | CrosEcDriverType::Replay)
+    }
+}
+
+impl CrosEcDriver for BuiltinDriver {
+    fn read_memory(&self, offset: u16, length: u16) -> Option<Vec<u8>> {
         // TODO: Change this function to return EcResult instead and print the error only in UI code
-        print_err(match self.driver {
+        print_err(self.try_read_memory(offset, length))
+    }
+    fn try_read_memory(&self, offset: u16, length: u16) -> EcResult<Vec<u8>> {
+        if !self.is_simulated() && !smbios::is_framework() {
+            return Err(EcError::DeviceError("Not a Framework Laptop".to_string()));
+        }
+        let _lock = if self.is_simulated() {
+            None
+        } else {
+            lock::across_processes()?
+        };
+
+        match self.0 {
             #[cfg(not(windows))]
             CrosEcDriverType::Portio => portio::read_memory(offset, length),
             #[cfg(windows)]
             CrosEcDriverType::Windows => windows::read_memory(offset, length),
             #[cfg(target_os = "linux")]
             CrosEcDriverType::CrosEc => cros_ec::read_memory(offset, length),
+            CrosEcDriverType::Mock => mock::read_memory(offset, length),
+            CrosEcDriverType::Replay => replay::read_memory(offset, length),
             _ => Err(EcError::DeviceError("No EC driver available".to_string())),
-        })
+        }
     }
     fn send_command(&self, command: u16, command_version: u8, data: &[u8]) -> EcResult<Vec<u8>> {
-        debug!(
-            "send_command(command={:X?}, ver={:?}, data_len={:?})",
-            <EcCommands as FromPrimitive>::from_u16(command),
-            command_version,
-            data.len()
-        );
-
-        if !smbios::is_framework() {
+        if !self.is_simulated() && !smbios::is_framework() {
             return Err(EcError::DeviceError("Not a Framework Laptop".to_string()));
         }
+        let _lock = if self.is_simulated() {
+            None
+        } else {
+            lock::across_processes()?
+        };
 
-        match self.driver {
+        match self.0 {
             #[cfg(not(windows))]
             CrosEcDriverType::Portio => portio::send_command(command, command_version, data),
             #[cfg(windows)]
             CrosEcDriverType::Windows => windows::send_command(command, command_version, data),
             #[cfg(target_os = "linux")]
             CrosEcDriverType::CrosEc => cros_ec::send_command(command, command_version, data),
+            CrosEcDriverType::Mock => mock::send_command(command, command_version, data),
+            CrosEcDriverType::Replay => replay::send_command(command, command_version, data),
             _ => Err(EcError::DeviceError("No EC driver available".to_string())),
         }
     }
 }
 
+impl CrosEcDriver for CrosEc {
+    fn read_memory(&self, offset: u16, length: u16) -> Option<Vec<u8>> {
+        print_err(self.try_read_memory(offset, length))
+    }
+    fn try_read_memory(&self, offset: u16, length: u16) -> EcResult<Vec<u8>> {
+        debug!("read_memory(offset={:#X}, size={:#X})", offset, length);
+        if offset + length > EC_MEMMAP_SIZE {
+            return Err(EcError::DeviceError(
+                "Read beyond end of memory map".to_string(),
+            ));
+        }
+
+        let _transaction = lock::transaction();
+        self.driver.try_read_memory(offset, length)
+    }
+    fn send_command(&self, command: u16, command_version: u8, data: &[u8]) -> EcResult<Vec<u8>> {
+        debug!(
+            "send_command(command={:X?}, ver={:?}, data_len={:?})",
+            <EcCommands as FromPrimitive>::from_u16(command),
+            command_version,
+            data.len()
+        );
+
+        let _transaction = lock::transaction();
+        self.driver.send_command(command, command_version, data)
+    }
+}
+
 /// Print the error
 pub fn print_err_ref<T>(something: &EcResult<T>) {
     match something {
@@ -1707,3 +1811,69 @@ pub struct GpuCfgDescriptor {
     /// CRC of header before this value
     pub crc32: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Driver from outside this module, keeping the commands it receives
+    #[derive(Default)]
+    struct LoggingDriver {
+        commands: Arc<Mutex<Vec<(u16, Vec<u8>)>>>,
+    }
+
+    impl CrosEcDriver for LoggingDriver {
+        fn read_memory(&self, offset: u16, length: u16) -> Option<Vec<u8>> {
+            Some((offset..offset + length).map(|addr| addr as u8).collect())
+        }
+        fn send_command(&self, command: u16, _version: u8, data: &[u8]) -> EcResult<Vec<u8>> {
+            self.commands.lock().unwrap().push((command, data.to_vec()));
+            if command == EcCommands::RgbKbdSetColor as u16 {
+                Ok(vec![])
+            } else {
+                Err(EcError::Response(EcResponseStatus::InvalidCommand))
+            }
+        }
+    }
+
+    #[test]
+    fn custom_driver() {
+        let driver = LoggingDriver::default();
+        let commands = driver.commands.clone();
+        let ec = CrosEc::from_driver(Box::new(driver));
+
+        ec.clone()
+            .rgbkbd_set_color(2, vec![RgbS::default(); 70])
+            .unwrap();
+        let sent: Vec<(u16, u8, u8)> = commands
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(command, data)| (*command, data[0], data[1]))
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                (EcCommands::RgbKbdSetColor as u16, 2, 64),
+                (EcCommands::RgbKbdSetColor as u16, 66, 6)
+            ]
+        );
+        // Past the last key, nothing is sent
+        commands.lock().unwrap().clear();
+        assert!(matches!(
+            ec.rgbkbd_set_color(200, vec![RgbS::default(); 100]),
+            Err(EcError::DeviceError(_))
+        ));
+        assert!(commands.lock().unwrap().is_empty());
+        assert!(ec.rgbkbd_set_color(200, vec![RgbS::default(); 56]).is_ok());
+        assert_eq!(
+            ec.send_command(EcCommands::GetVersion as u16, 0, &[]),
+            Err(EcError::Response(EcResponseStatus::InvalidCommand))
+        );
+
+        assert_eq!(ec.read_memory(EC_MEMMAP_ID, 2), Some(vec![0x20, 0x21]));
+        // Reads beyond the memory map never reach the driver
+        assert_eq!(ec.read_memory(EC_MEMMAP_SIZE - 1, 2), None);
+    }
+}
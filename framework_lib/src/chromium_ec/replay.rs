@@ -0,0 +1,404 @@
+//! Record EC traffic and replay it without hardware
+//!
+//! A [`CrosEc`] built on a [`RecordingDriver`] captures every host command and memory map read
+//! together with its response. The `replay` driver serves a recorded [`Trace`] back, so code like
+//! [`power::get_and_print_power_info`] can be tested against a session that was recorded on a
+//! real system once.
+//!
+//! Traces are plain text with one exchange per line. Commands list their id, version and request
+//! bytes, memory reads their offset and length, followed by the response:
+//!
+//! ```text
+//! command 0x0002 0 - => ok 010203
+//! command 0x013a 1 050010 => status 3 InvalidParameter
+//! memory 0x20 2 => ok 4543
+//! ```
+//!
+//! [`CrosEc`]: crate::chromium_ec::CrosEc
+//! [`power::get_and_print_power_info`]: crate::power::get_and_print_power_info
+
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use num_traits::FromPrimitive;
+use spin::Mutex;
+
+use crate::chromium_ec::{CrosEcDriver, EcError, EcResponseStatus, EcResult};
+
+/// What was asked of the EC
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceRequest {
+    Command {
+        command: u16,
+        command_version: u8,
+        data: Vec<u8>,
+    },
+    Memory {
+        offset: u16,
+        length: u16,
+    },
+}
+
+/// A request and the response the EC gave to it
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub request: TraceRequest,
+    pub response: EcResult<Vec<u8>>,
+}
+
+/// Recorded EC session, in the order the requests were sent
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    pub entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    /// Parse a trace in the text format, empty lines and lines starting with `#` are skipped
+    pub fn parse(text: &str) -> Result<Trace, String> {
+        let entries = text
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(idx, line)| {
+                parse_entry(line).map_err(|err| format!("line {}: {}", idx + 1, err))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Trace { entries })
+    }
+
+    #[cfg(not(feature = "uefi"))]
+    pub fn load(path: &std::path::Path) -> Result<Trace, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read `{}`: {}", path.display(), err))?;
+        Trace::parse(&text).map_err(|err| format!("invalid trace `{}`: {}", path.display(), err))
+    }
+
+    #[cfg(not(feature = "uefi"))]
+    pub fn save(&self, path: &std::path::Path) -> Result<(), String> {
+        std::fs::write(path, self.to_string())
+            .map_err(|err| format!("failed to write `{}`: {}", path.display(), err))
+    }
+}
+
+impl fmt::Display for TraceRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceRequest::Command {
+                command,
+                command_version,
+                data,
+            } => write!(
+                f,
+                "command {:#06x} {} {}",
+                command,
+                command_version,
+                Hex(data)
+            ),
+            TraceRequest::Memory { offset, length } => {
+                write!(f, "memory {:#04x} {}", offset, length)
+            }
+        }
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} => ", self.request)?;
+        match &self.response {
+            Ok(data) => write!(f, "ok {}", Hex(data)),
+            Err(EcError::Response(status)) => write!(f, "status {} {:?}", *status as u16, status),
+            Err(EcError::UnknownResponseCode(code)) => write!(f, "unknown {:#x}", code),
+            Err(EcError::DeviceError(msg)) => write!(f, "device {}", msg.replace('\n', " ")),
+        }
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# framework_lib EC trace")?;
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Bytes as lowercase hex digits, `-` if there are none
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "-");
+        }
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    if text == "-" {
+        return Ok(Vec::new());
+    }
+    if text.len() % 2 != 0 || !text.is_ascii() {
+        return Err(format!("invalid hex bytes `{}`", text));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|idx| {
+            u8::from_str_radix(&text[idx..idx + 2], 16)
+                .map_err(|_| format!("invalid hex bytes `{}`", text))
+        })
+        .collect()
+}
+
+fn parse_number<T: num::Num>(text: Option<&str>, what: &str) -> Result<T, String> {
+    let text = text.ok_or_else(|| format!("missing {}", what))?;
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => T::from_str_radix(hex, 16),
+        None => T::from_str_radix(text, 10),
+    };
+    parsed.map_err(|_| format!("invalid {} `{}`", what, text))
+}
+
+fn parse_entry(line: &str) -> Result<TraceEntry, String> {
+    let (request, response) = line
+        .split_once(" => ")
+        .ok_or("expected `<request> => <response>`")?;
+
+    let mut fields = request.split_whitespace();
+    let request = match fields.next() {
+        Some("command") => TraceRequest::Command {
+            command: parse_number(fields.next(), "command")?,
+            command_version: parse_number(fields.next(), "command version")?,
+            data: parse_hex(fields.next().ok_or("missing request data")?)?,
+        },
+        Some("memory") => TraceRequest::Memory {
+            offset: parse_number(fields.next(), "offset")?,
+            length: parse_number(fields.next(), "length")?,
+        },
+        _ => return Err("expected `command` or `memory`".to_string()),
+    };
+    if let Some(extra) = fields.next() {
+        return Err(format!("unexpected `{}` in request", extra));
+    }
+
+    let (kind, rest) = response.split_once(' ').unwrap_or((response, ""));
+    let response = match kind {
+        "ok" => Ok(parse_hex(rest.trim())?),
+        "status" => {
+            let code: u16 = parse_number(rest.split_whitespace().next(), "status")?;
+            let status = EcResponseStatus::from_u16(code)
+                .ok_or_else(|| format!("unknown status {}", code))?;
+            Err(EcError::Response(status))
+        }
+        "unknown" => Err(EcError::UnknownResponseCode(parse_number(
+            rest.split_whitespace().next(),
+            "response code",
+        )?)),
+        "device" => Err(EcError::DeviceError(rest.to_string())),
+        _ => return Err(format!("unknown response `{}`", kind)),
+    };
+
+    Ok(TraceEntry { request, response })
+}
+
+/// Trace that one or more [`RecordingDriver`]s add to, cheap to clone
+#[derive(Clone, Default)]
+pub struct Recording(Arc<Mutex<Trace>>);
+
+impl Recording {
+    pub fn new() -> Recording {
+        Recording::default()
+    }
+
+    /// Everything recorded so far
+    pub fn trace(&self) -> Trace {
+        self.0.lock().clone()
+    }
+
+    fn record(&self, request: TraceRequest, response: &EcResult<Vec<u8>>) {
+        self.0.lock().entries.push(TraceEntry {
+            request,
+            response: response.clone(),
+        });
+    }
+}
+
+/// Passes everything on to another driver and records the exchanges
+///
+/// Only the traffic of the [`CrosEc`](crate::chromium_ec::CrosEc) built on it (and its clones)
+/// ends up in the recording:
+///
+/// ```no_run
+/// # use framework_lib::chromium_ec::{BuiltinDriver, CrosEc, CrosEcDriverType};
+/// # use framework_lib::chromium_ec::replay::{Recording, RecordingDriver};
+/// let recording = Recording::new();
+/// let driver = BuiltinDriver::new(CrosEcDriverType::Portio).unwrap();
+/// let ec = CrosEc::from_driver(Box::new(RecordingDriver::new(driver, recording.clone())));
+/// ec.version_info().unwrap();
+/// let trace = recording.trace();
+/// ```
+pub struct RecordingDriver<D> {
+    driver: D,
+    recording: Recording,
+}
+
+impl<D: CrosEcDriver> RecordingDriver<D> {
+    pub fn new(driver: D, recording: Recording) -> RecordingDriver<D> {
+        RecordingDriver { driver, recording }
+    }
+}
+
+impl<D: CrosEcDriver> CrosEcDriver for RecordingDriver<D> {
+    fn read_memory(&self, offset: u16, length: u16) -> Option<Vec<u8>> {
+        self.try_read_memory(offset, length).ok()
+    }
+    fn try_read_memory(&self, offset: u16, length: u16) -> EcResult<Vec<u8>> {
+        let result = self.driver.try_read_memory(offset, length);
+        self.recording
+            .record(TraceRequest::Memory { offset, length }, &result);
+        result
+    }
+    fn send_command(&self, command: u16, command_version: u8, data: &[u8]) -> EcResult<Vec<u8>> {
+        let result = self.driver.send_command(command, command_version, data);
+        self.recording.record(
+            TraceRequest::Command {
+                command,
+                command_version,
+                data: data.to_vec(),
+            },
+            &result,
+        );
+        result
+    }
+}
+
+lazy_static! {
+    /// Trace served by the replay driver, with whether each entry was served already
+    static ref REPLAY: Mutex<Vec<(TraceEntry, bool)>> = Mutex::new(Vec::new());
+}
+
+/// Serve `trace` from the replay driver, replacing the trace served before
+pub fn load(trace: Trace) {
+    *REPLAY.lock() = trace
+        .entries
+        .into_iter()
+        .map(|entry| (entry, false))
+        .collect();
+}
+
+/// Entries of the loaded trace that weren't requested yet
+pub fn remaining() -> Vec<TraceEntry> {
+    REPLAY
+        .lock()
+        .iter()
+        .filter(|(_, served)| !served)
+        .map(|(entry, _)| entry.clone())
+        .collect()
+}
+
+/// Answer with the first entry for the same request that wasn't served yet
+///
+/// Identical requests get their responses in the order they were recorded. Requests that were
+/// never recorded, or more often than recorded, fail.
+fn serve(request: TraceRequest) -> EcResult<Vec<u8>> {
+    let mut replay = REPLAY.lock();
+    let (entry, served) = replay
+        .iter_mut()
+        .find(|(entry, served)| !*served && entry.request == request)
+        .ok_or_else(|| EcError::DeviceError(format!("No recorded response for `{}`", request)))?;
+    *served = true;
+    entry.response.clone()
+}
+
+pub fn send_command(command: u16, command_version: u8, data: &[u8]) -> EcResult<Vec<u8>> {
+    serve(TraceRequest::Command {
+        command,
+        command_version,
+        data: data.to_vec(),
+    })
+}
+
+pub fn read_memory(offset: u16, length: u16) -> EcResult<Vec<u8>> {
+    serve(TraceRequest::Memory { offset, length })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chromium_ec::command::EcCommands;
+    use crate::chromium_ec::commands::RgbS;
+    use crate::chromium_ec::{mock, BuiltinDriver, CrosEc, CrosEcDriver, CrosEcDriverType};
+    use crate::power;
+
+    #[test]
+    fn replay_recorded_session() {
+        let _mock = mock::lock_for_test();
+        let recording = Recording::new();
+        let driver = BuiltinDriver::new(CrosEcDriverType::Mock).unwrap();
+        let ec = CrosEc::from_driver(Box::new(RecordingDriver::new(driver, recording.clone())));
+        let colors: Vec<RgbS> = (0..20u8)
+            .map(|idx| RgbS {
+                r: idx,
+                g: 0x80,
+                b: 0xFF - idx,
+            })
+            .collect();
+        mock::fail_command(EcCommands::PwmSetFanDuty, EcResponseStatus::AccessDenied);
+
+        let power_info = power::get_and_print_power_info(&ec);
+        power::print_thermal(&ec);
+        let snapshot = power::get_thermal_snapshot(&ec);
+        ec.rgbkbd_set_color(4, colors.clone()).unwrap();
+        let fan_duty = ec.fan_set_duty(None, 50);
+        mock::set_memory_unreadable(true);
+        assert_eq!(ec.read_memory(0x20, 2), None);
+        // Other handles aren't recorded
+        let other = CrosEc::with(CrosEcDriverType::Mock).unwrap();
+        assert_eq!(other.read_memory(0x20, 2), None);
+        let recorded = recording.trace();
+
+        // Through the text format, as if it was stored in a file
+        let text = recorded.to_string();
+        assert!(text.contains("=> status 4 AccessDenied"));
+        assert!(text.contains("memory 0x20 2 => device Memory map unreadable"));
+        let trace = Trace::parse(&text).unwrap();
+        assert_eq!(trace, recorded);
+
+        load(trace);
+        let ec = CrosEc::with(CrosEcDriverType::Replay).unwrap();
+        assert_eq!(power::get_and_print_power_info(&ec), power_info);
+        power::print_thermal(&ec);
+        assert_eq!(power::get_thermal_snapshot(&ec), snapshot);
+        ec.rgbkbd_set_color(4, colors.clone()).unwrap();
+        assert_eq!(ec.fan_set_duty(None, 50), fan_duty);
+        assert_eq!(ec.read_memory(0x20, 2), None);
+        assert_eq!(remaining(), []);
+
+        // Everything was served, so repeating a request fails
+        assert!(matches!(
+            ec.rgbkbd_set_color(4, colors),
+            Err(EcError::DeviceError(_))
+        ));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Trace::parse("# nothing\n\n").unwrap(), Trace::default());
+        assert!(Trace::parse("memory 0x20 2 => ok 4")
+            .unwrap_err()
+            .starts_with("line 1:"));
+        assert!(Trace::parse("command 0x0002 0 => ok -").is_err());
+        assert!(Trace::parse("memory 0x20 2 => status 9999").is_err());
+        assert!(Trace::parse("# header\nport 0x20 => ok -")
+            .unwrap_err()
+            .starts_with("line 2:"));
+    }
+}
@@ -4,7 +4,6 @@ use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec;
 use alloc::vec::Vec;
-use core::convert::TryInto;
 use core::fmt;
 use core::prelude::v1::derive;
 use log::Level;
@@ -12,72 +11,22 @@ use log::Level;
 use crate::ccgx::{AppVersion, Application, BaseVersion, ControllerVersion, MainPdVersions};
 use crate::chromium_ec::command::EcRequestRaw;
 use crate::chromium_ec::commands::*;
+use crate::chromium_ec::memmap::*;
 use crate::chromium_ec::*;
 use crate::smbios;
 use crate::util::{Platform, PlatformFamily};
 
-/// Maximum length of strings in memmap
-const EC_MEMMAP_TEXT_MAX: u16 = 8;
-
-// The offset address of each type of data in mapped memory.
-// TODO: Move non-power values to other modules
-const EC_MEMMAP_TEMP_SENSOR: u16 = 0x00; // Temp sensors 0x00 - 0x0f
-const EC_MEMMAP_FAN: u16 = 0x10; // Fan speeds 0x10 - 0x17
-const _EC_MEMMAP_TEMP_SENSOR_B: u16 = 0x18; // More temp sensors 0x18 - 0x1f
-const _EC_MEMMAP_ID: u16 = 0x2120; // 0x20 == 'E', 0x21 == 'C'
-const EC_MEMMAP_ID_VERSION: u16 = 0x22; // Version of data in 0x20 - 0x2f
-const EC_MEMMAP_THERMAL_VERSION: u16 = 0x23; // Version of data in 0x00 - 0x1f
-const EC_MEMMAP_BATTERY_VERSION: u16 = 0x24; // Version of data in 0x40 - 0x7f
-const EC_MEMMAP_SWITCHES_VERSION: u16 = 0x25; // Version of data in 0x30 - 0x33
-const EC_MEMMAP_EVENTS_VERSION: u16 = 0x26; // Version of data in 0x34 - 0x3f
-const _EC_MEMMAP_HOST_CMD_FLAGS: u16 = 0x27; // Host cmd interface flags (8 bits)
-                                             // Unused 0x28 - 0x2f
-const _EC_MEMMAP_SWITCHES: u16 = 0x30; // 8 bits
-                                       // Unused 0x31 - 0x33
-const _EC_MEMMAP_HOST_EVENTS: u16 = 0x34; // 64 bits
-                                          // Battery values are all 32 bits, unless otherwise noted.
-const EC_MEMMAP_BATT_VOLT: u16 = 0x40; // Battery Present Voltage
-const EC_MEMMAP_BATT_RATE: u16 = 0x44; // Battery Present Rate
-const EC_MEMMAP_BATT_CAP: u16 = 0x48; // Battery Remaining Capacity
-const EC_MEMMAP_BATT_FLAG: u16 = 0x4c; // Battery State, see below (8-bit)
-const EC_MEMMAP_BATT_COUNT: u16 = 0x4d; // Battery Count (8-bit)
-const EC_MEMMAP_BATT_INDEX: u16 = 0x4e; // Current Battery Data Index (8-bit)
-                                        // Unused 0x4f
-const EC_MEMMAP_BATT_DCAP: u16 = 0x50; // Battery Design Capacity
-const EC_MEMMAP_BATT_DVLT: u16 = 0x54; // Battery Design Voltage
-const EC_MEMMAP_BATT_LFCC: u16 = 0x58; // Battery Last Full Charge Capacity
-const EC_MEMMAP_BATT_CCNT: u16 = 0x5c; // Battery Cycle Count
-                                       // Strings are all 8 bytes (EC_MEMMAP_TEXT_MAX)
-const EC_MEMMAP_BATT_MFGR: u16 = 0x60; // Battery Manufacturer String
-const EC_MEMMAP_BATT_MODEL: u16 = 0x68; // Battery Model Number String
-const EC_MEMMAP_BATT_SERIAL: u16 = 0x70; // Battery Serial Number String
-const EC_MEMMAP_BATT_TYPE: u16 = 0x78; // Battery Type String
-const EC_MEMMAP_ALS: u16 = 0x80; // ALS readings in lux (2 X 16 bits)
-                                 // Unused 0x84 - 0x8f
-const EC_MEMMAP_ACC_STATUS: u16 = 0x90; // Accelerometer status (8 bits )
-                                        // Unused 0x91
-const EC_MEMMAP_ACC_DATA: u16 = 0x92; // Accelerometers data 0x92 - 0x9f
-                                      // 0x92: u16Lid Angle if available, LID_ANGLE_UNRELIABLE otherwise
-                                      // 0x94 - 0x99: u161st Accelerometer
-                                      // 0x9a - 0x9f: u162nd Accelerometer
 const LID_ANGLE_UNRELIABLE: u16 = 500;
-const _EC_MEMMAP_GYRO_DATA: u16 = 0xa0; // Gyroscope data 0xa0 - 0xa5
-                                        // Unused 0xa6 - 0xdf
 
-// Battery bit flags at EC_MEMMAP_BATT_FLAG.
-const EC_BATT_FLAG_AC_PRESENT: u8 = 0x01;
-const EC_BATT_FLAG_BATT_PRESENT: u8 = 0x02;
-const EC_BATT_FLAG_DISCHARGING: u8 = 0x04;
-const EC_BATT_FLAG_CHARGING: u8 = 0x08;
-const EC_BATT_FLAG_LEVEL_CRITICAL: u8 = 0x10;
-
-const EC_FAN_SPEED_ENTRIES: usize = 4;
+pub(crate) const EC_FAN_SPEED_ENTRIES: usize = 4;
 /// Used on old EC firmware (before 2023)
 const EC_FAN_SPEED_STALLED_DEPRECATED: u16 = 0xFFFE;
-const EC_FAN_SPEED_NOT_PRESENT: u16 = 0xFFFF;
+pub(crate) const EC_FAN_SPEED_NOT_PRESENT: u16 = 0xFFFF;
 
-#[derive(Debug, PartialEq)]
-enum TempSensor {
+/// State of a temperature sensor as reported in the EC memory map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TempSensor {
+    /// Temperature in degrees Celsius
     Ok(u8),
     NotPresent,
     Error,
@@ -95,6 +44,16 @@ impl From<u8> for TempSensor {
         }
     }
 }
+impl TempSensor {
+    /// Temperature in degrees Celsius, if the sensor has a valid reading
+    pub fn celsius(&self) -> Option<u8> {
+        if let TempSensor::Ok(t) = self {
+            Some(*t)
+        } else {
+            None
+        }
+    }
+}
 impl fmt::Display for TempSensor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let TempSensor::Ok(t) = self {
@@ -105,6 +64,34 @@ impl fmt::Display for TempSensor {
     }
 }
 
+/// Speed of a fan as reported in the EC memory map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FanSpeed {
+    Rpm(u16),
+    /// Only reported by old EC firmware (before 2023)
+    Stalled,
+    NotPresent,
+}
+impl From<u16> for FanSpeed {
+    fn from(rpm: u16) -> Self {
+        match rpm {
+            EC_FAN_SPEED_STALLED_DEPRECATED => FanSpeed::Stalled,
+            EC_FAN_SPEED_NOT_PRESENT => FanSpeed::NotPresent,
+            _ => FanSpeed::Rpm(rpm),
+        }
+    }
+}
+impl FanSpeed {
+    /// Speed in RPM, if the fan is present and not stalled
+    pub fn rpm(&self) -> Option<u16> {
+        if let FanSpeed::Rpm(rpm) = self {
+            Some(*rpm)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BatteryInformation {
     pub present_voltage: u32,
@@ -216,25 +203,6 @@ impl fmt::Display for LidAngle {
     }
 }
 
-fn read_string(ec: &CrosEc, address: u16) -> String {
-    let bytes = ec.read_memory(address, EC_MEMMAP_TEXT_MAX).unwrap();
-    String::from_utf8_lossy(bytes.as_slice()).replace(['\0'], "")
-}
-
-fn read_u32(ec: &CrosEc, address: u16) -> u32 {
-    let bytes = ec.read_memory(address, 4).unwrap();
-    if bytes.len() != 4 {
-        debug_assert!(
-            bytes.len() == 4,
-            "Tried to read 4 bytes but got {}",
-            bytes.len()
-        );
-        error!("Unexpected length returned: {:?} instead of 4", bytes.len());
-        return 0;
-    }
-    u32::from_ne_bytes(bytes[0..4].try_into().unwrap())
-}
-
 pub fn print_memmap_version_info(ec: &CrosEc) {
     // TODO: I don't think these are very useful
     let _id_ver = ec.read_memory(EC_MEMMAP_ID_VERSION, 2).unwrap(); /* Version of data in 0x20 - 0x2f */
@@ -257,26 +225,10 @@ pub fn get_als_reading(ec: &CrosEc, index: usize) -> Option<u32> {
 }
 
 pub fn get_accel_data(ec: &CrosEc) -> (AccelData, AccelData, LidAngle) {
-    // bit 4 = busy
-    // bit 7 = present
-    // #define EC_MEMMAP_ACC_STATUS_SAMPLE_ID_MASK 0x0f
-    let _acc_status = ec.read_memory(EC_MEMMAP_ACC_STATUS, 0x01).unwrap()[0];
-    // While busy, keep reading
-
-    let lid_angle = ec.read_memory(EC_MEMMAP_ACC_DATA, 0x02).unwrap();
-    let lid_angle = u16::from_le_bytes([lid_angle[0], lid_angle[1]]);
-    let accel_1 = ec.read_memory(EC_MEMMAP_ACC_DATA + 2, 0x06).unwrap();
-    let accel_2 = ec.read_memory(EC_MEMMAP_ACC_DATA + 8, 0x06).unwrap();
-
-    // TODO: Make sure we got a new sample
-    // println!("  Status Bit: {} 0x{:X}", acc_status, acc_status);
-    // println!("  Present:    {}", (acc_status & 0x80) > 0);
-    // println!("  Busy:       {}", (acc_status & 0x8) > 0);
-    (
-        AccelData::from(accel_1),
-        AccelData::from(accel_2),
-        LidAngle::from(lid_angle),
-    )
+    // TODO: While busy, keep reading. Make sure we got a new sample
+    let accel = EcMemmap::read(ec).unwrap().accelerometers;
+    let [accel_1, accel_2] = accel.sensors;
+    (accel_1, accel_2, accel.lid_angle)
 }
 
 pub fn print_sensors(ec: &CrosEc) {
@@ -316,210 +268,220 @@ pub fn print_sensors(ec: &CrosEc) {
         println!("ALS: {:>4} Lux", als_int);
     }
 
-    // bit 4 = busy
-    // bit 7 = present
-    // #define EC_MEMMAP_ACC_STATUS_SAMPLE_ID_MASK 0x0f
-    let acc_status = ec.read_memory(EC_MEMMAP_ACC_STATUS, 0x01).unwrap()[0];
-    // While busy, keep reading
-
-    let lid_angle = ec.read_memory(EC_MEMMAP_ACC_DATA, 0x02).unwrap();
-    let lid_angle = u16::from_le_bytes([lid_angle[0], lid_angle[1]]);
-    let accel_1 = ec.read_memory(EC_MEMMAP_ACC_DATA + 2, 0x06).unwrap();
-    let accel_2 = ec.read_memory(EC_MEMMAP_ACC_DATA + 8, 0x06).unwrap();
-
-    let present = (acc_status & 0x80) > 0;
-    if present {
+    // TODO: While busy, keep reading
+    let accel = EcMemmap::read(ec).unwrap().accelerometers;
+    if accel.present {
         println!("Accelerometers:");
-        debug!("  Status Bit: {} 0x{:X}", acc_status, acc_status);
-        debug!("  Present:    {}", present);
-        debug!("  Busy:       {}", (acc_status & 0x8) > 0);
+        debug!("  Sample ID:  {}", accel.sample_id);
+        debug!("  Busy:       {}", accel.busy);
         print!("  Lid Angle:   ");
-        if lid_angle == LID_ANGLE_UNRELIABLE {
-            println!("Unreliable");
-        } else {
-            println!("{} Deg", lid_angle);
+        match accel.lid_angle {
+            LidAngle::Angle(deg) => println!("{} Deg", deg),
+            LidAngle::Unreliable => println!("Unreliable"),
         }
         println!(
             "  {:<12} {}",
             format!("{:?} Sensor:", accel_locations[0]),
-            AccelData::from(accel_1)
+            accel.sensors[0]
         );
         println!(
             "  {:<12} {}",
             format!("{:?} Sensor:", accel_locations[1]),
-            AccelData::from(accel_2)
+            accel.sensors[1]
         );
     }
 }
 
-pub fn print_thermal(ec: &CrosEc) {
-    let temps = ec.read_memory(EC_MEMMAP_TEMP_SENSOR, 0x0F).unwrap();
-    let fans = ec.read_memory(EC_MEMMAP_FAN, 0x08).unwrap();
-
-    let platform = smbios::get_platform();
-    let family = smbios::get_family();
-    let remaining_sensors = match platform {
-        Some(Platform::IntelGen11) | Some(Platform::IntelGen12) | Some(Platform::IntelGen13) => {
-            println!("  F75303_Local: {:>4}", TempSensor::from(temps[0]));
-            println!("  F75303_CPU:   {:>4}", TempSensor::from(temps[1]));
-            println!("  F75303_DDR:   {:>4}", TempSensor::from(temps[2]));
-            println!("  Battery:      {:>4}", TempSensor::from(temps[3]));
-            println!("  PECI:         {:>4}", TempSensor::from(temps[4]));
-            if matches!(
-                platform,
-                Some(Platform::IntelGen12) | Some(Platform::IntelGen13)
-            ) {
-                println!("  F57397_VCCGT: {:>4}", TempSensor::from(temps[5]));
-            }
-            2
-        }
-
-        Some(Platform::IntelCoreUltra1) => {
-            println!("  F75303_Local: {:>4}", TempSensor::from(temps[0]));
-            println!("  F75303_CPU:   {:>4}", TempSensor::from(temps[1]));
-            println!("  Battery:      {:>4}", TempSensor::from(temps[2]));
-            println!("  F75303_DDR:   {:>4}", TempSensor::from(temps[3]));
-            println!("  PECI:         {:>4}", TempSensor::from(temps[4]));
-            3
-        }
-
-        Some(Platform::Framework12IntelGen13) => {
-            println!("  F75303_CPU:   {:>4}", TempSensor::from(temps[0]));
-            println!("  F75303_Skin:  {:>4}", TempSensor::from(temps[1]));
-            println!("  F75303_Local: {:>4}", TempSensor::from(temps[2]));
-            println!("  Battery:      {:>4}", TempSensor::from(temps[3]));
-            println!("  PECI:         {:>4}", TempSensor::from(temps[4]));
-            println!("  Charger IC    {:>4}", TempSensor::from(temps[5]));
-            2
-        }
-
+/// A named temperature sensor reading
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TempReading {
+    /// Platform specific sensor name, e.g. `F75303_APU`, or `Temp N` if unknown
+    pub name: String,
+    pub state: TempSensor,
+}
+
+/// Names of the temperature sensors in memmap order, and how many more
+/// unnamed sensors might follow them in the first 8 slots
+fn temp_sensor_names(
+    platform: Option<Platform>,
+    family: Option<PlatformFamily>,
+) -> (&'static [&'static str], usize) {
+    match platform {
+        Some(Platform::IntelGen11) => (
+            &[
+                "F75303_Local",
+                "F75303_CPU",
+                "F75303_DDR",
+                "Battery",
+                "PECI",
+            ],
+            2,
+        ),
+        Some(Platform::IntelGen12) | Some(Platform::IntelGen13) => (
+            &[
+                "F75303_Local",
+                "F75303_CPU",
+                "F75303_DDR",
+                "Battery",
+                "PECI",
+                "F57397_VCCGT",
+            ],
+            2,
+        ),
+        Some(Platform::IntelCoreUltra1) => (
+            &[
+                "F75303_Local",
+                "F75303_CPU",
+                "Battery",
+                "F75303_DDR",
+                "PECI",
+            ],
+            3,
+        ),
+        Some(Platform::Framework12IntelGen13) => (
+            &[
+                "F75303_CPU",
+                "F75303_Skin",
+                "F75303_Local",
+                "Battery",
+                "PECI",
+                "Charger IC",
+            ],
+            2,
+        ),
         Some(
             Platform::Framework13Amd7080
             | Platform::Framework13AmdAi300
             | Platform::Framework16Amd7080
             | Platform::Framework16AmdAi300,
         ) => {
-            println!("  F75303_Local: {:>4}", TempSensor::from(temps[0]));
-            println!("  F75303_CPU:   {:>4}", TempSensor::from(temps[1]));
-            println!("  F75303_DDR:   {:>4}", TempSensor::from(temps[2]));
-            println!("  APU:          {:>4}", TempSensor::from(temps[3]));
             if family == Some(PlatformFamily::Framework16) {
-                println!("  dGPU VR:      {:>4}", TempSensor::from(temps[4]));
-                println!("  dGPU VRAM:    {:>4}", TempSensor::from(temps[5]));
-                println!("  dGPU AMB:     {:>4}", TempSensor::from(temps[6]));
-                println!("  dGPU temp:    {:>4}", TempSensor::from(temps[7]));
-                0
+                (
+                    &[
+                        "F75303_Local",
+                        "F75303_CPU",
+                        "F75303_DDR",
+                        "APU",
+                        "dGPU VR",
+                        "dGPU VRAM",
+                        "dGPU AMB",
+                        "dGPU temp",
+                    ],
+                    0,
+                )
             } else {
-                4
+                (&["F75303_Local", "F75303_CPU", "F75303_DDR", "APU"], 4)
             }
         }
-
         Some(Platform::FrameworkDesktopAmdAiMax300) => {
-            println!("  F75303_APU:   {:>4}", TempSensor::from(temps[0]));
-            println!("  F75303_DDR:   {:>4}", TempSensor::from(temps[1]));
-            println!("  F75303_AMB:   {:>4}", TempSensor::from(temps[2]));
-            println!("  APU:          {:>4}", TempSensor::from(temps[3]));
-            4
+            (&["F75303_APU", "F75303_DDR", "F75303_AMB", "APU"], 4)
         }
+        _ => (&[], 0),
+    }
+}
 
-        _ => {
-            println!("  Temp 0:       {:>4}", TempSensor::from(temps[0]));
-            println!("  Temp 1:       {:>4}", TempSensor::from(temps[1]));
-            println!("  Temp 2:       {:>4}", TempSensor::from(temps[2]));
-            println!("  Temp 3:       {:>4}", TempSensor::from(temps[3]));
-            println!("  Temp 4:       {:>4}", TempSensor::from(temps[4]));
-            println!("  Temp 5:       {:>4}", TempSensor::from(temps[5]));
-            println!("  Temp 6:       {:>4}", TempSensor::from(temps[6]));
-            println!("  Temp 7:       {:>4}", TempSensor::from(temps[7]));
-            0
+/// Read all temperature sensors, named according to the current platform
+///
+/// On unknown platforms the first 8 sensors are returned as `Temp 0` to `Temp 7`.
+/// Sensors the EC has beyond the known ones are only returned if present.
+pub fn get_temperatures(ec: &CrosEc) -> Option<Vec<TempReading>> {
+    let temps = ec.read_memory(EC_MEMMAP_TEMP_SENSOR, 0x0F)?;
+    let (names, remaining_sensors) =
+        temp_sensor_names(smbios::get_platform(), smbios::get_family());
+
+    let mut readings: Vec<TempReading> = names
+        .iter()
+        .zip(&temps)
+        .map(|(name, temp)| TempReading {
+            name: name.to_string(),
+            state: TempSensor::from(*temp),
+        })
+        .collect();
+
+    if names.is_empty() {
+        readings.extend(
+            temps
+                .iter()
+                .take(8)
+                .enumerate()
+                .map(|(i, temp)| TempReading {
+                    name: format!("Temp {}", i),
+                    state: TempSensor::from(*temp),
+                }),
+        );
+    } else {
+        // Just in case EC has more sensors than we know about
+        for (i, temp) in temps.iter().enumerate().take(8).skip(8 - remaining_sensors) {
+            let state = TempSensor::from(*temp);
+            if state != TempSensor::NotPresent {
+                readings.push(TempReading {
+                    name: format!("Temp {}", i),
+                    state,
+                });
+            }
         }
-    };
+    }
 
-    // Just in case EC has more sensors than we know about, print them
-    for (i, temp) in temps.iter().enumerate().take(8).skip(8 - remaining_sensors) {
-        let temp = TempSensor::from(*temp);
-        if temp != TempSensor::NotPresent {
-            println!("  Temp {}:       {:>4}", i, temp);
-        }
+    Some(readings)
+}
+
+/// Read the speed of all fan slots, including ones that are not present
+pub fn get_fan_speeds(ec: &CrosEc) -> Option<Vec<FanSpeed>> {
+    let fans = ec.read_memory(EC_MEMMAP_FAN, 0x08)?;
+    Some(
+        fans.chunks_exact(2)
+            .take(EC_FAN_SPEED_ENTRIES)
+            .map(|rpm| FanSpeed::from(u16::from_le_bytes([rpm[0], rpm[1]])))
+            .collect(),
+    )
+}
+
+/// Temperatures and fan speeds, read together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThermalSnapshot {
+    /// Named temperature sensors, see [`get_temperatures`]
+    pub temperatures: Vec<TempReading>,
+    /// All fan slots, including stalled and not present fans
+    pub fans: Vec<FanSpeed>,
+}
+
+pub fn get_thermal_snapshot(ec: &CrosEc) -> Option<ThermalSnapshot> {
+    Some(ThermalSnapshot {
+        temperatures: get_temperatures(ec)?,
+        fans: get_fan_speeds(ec)?,
+    })
+}
+
+pub fn print_thermal(ec: &CrosEc) {
+    let snapshot = get_thermal_snapshot(ec).unwrap();
+
+    for temp in snapshot.temperatures {
+        println!("  {:<14}{:>4}", format!("{}:", temp.name), temp.state);
     }
 
-    for i in 0..EC_FAN_SPEED_ENTRIES {
-        let fan = u16::from_le_bytes([fans[i * 2], fans[1 + i * 2]]);
-        if fan == EC_FAN_SPEED_STALLED_DEPRECATED {
-            println!("  Fan Speed:  {:>4} RPM (Stalled)", fan);
-        } else if fan == EC_FAN_SPEED_NOT_PRESENT {
-            info!("  Fan Speed:    Not present");
-        } else {
-            println!("  Fan Speed:  {:>4} RPM", fan);
+    for fan in snapshot.fans {
+        match fan {
+            FanSpeed::Stalled => println!(
+                "  Fan Speed:  {:>4} RPM (Stalled)",
+                EC_FAN_SPEED_STALLED_DEPRECATED
+            ),
+            FanSpeed::NotPresent => info!("  Fan Speed:    Not present"),
+            FanSpeed::Rpm(rpm) => println!("  Fan Speed:  {:>4} RPM", rpm),
         }
     }
 }
 
 pub fn get_fan_num(ec: &CrosEc) -> EcResult<usize> {
-    let fans = ec.read_memory(EC_MEMMAP_FAN, 0x08).unwrap();
-
-    let mut count = 0;
-    for i in 0..EC_FAN_SPEED_ENTRIES {
-        let fan = u16::from_le_bytes([fans[i * 2], fans[1 + i * 2]]);
-        if fan == EC_FAN_SPEED_NOT_PRESENT {
-            continue;
-        }
-        count += 1;
-    }
-    Ok(count)
+    let fans = get_fan_speeds(ec).unwrap();
+    Ok(fans
+        .iter()
+        .filter(|fan| **fan != FanSpeed::NotPresent)
+        .count())
 }
 
 // TODO: Use Result
 pub fn power_info(ec: &CrosEc) -> Option<PowerInfo> {
-    let battery_flag = ec.read_memory(EC_MEMMAP_BATT_FLAG, 1)?[0];
-    debug!("AC/Battery flag: {:#X}", battery_flag);
-    let battery_lfcc = read_u32(ec, EC_MEMMAP_BATT_LFCC);
-    let battery_cap = read_u32(ec, EC_MEMMAP_BATT_CAP);
-
-    let present_voltage = read_u32(ec, EC_MEMMAP_BATT_VOLT);
-    let present_rate = read_u32(ec, EC_MEMMAP_BATT_RATE);
-    let _remaining_capacity = read_u32(ec, EC_MEMMAP_BATT_CAP); // TODO: Why didn't I use this?
-    let battery_count = ec.read_memory(EC_MEMMAP_BATT_COUNT, 1).unwrap()[0]; // 8 bit
-    let current_battery_index = ec.read_memory(EC_MEMMAP_BATT_INDEX, 1).unwrap()[0]; // 8 bit
-    let design_capacity = read_u32(ec, EC_MEMMAP_BATT_DCAP);
-    let design_voltage = read_u32(ec, EC_MEMMAP_BATT_DVLT);
-    let cycle_count = read_u32(ec, EC_MEMMAP_BATT_CCNT);
-
-    let manufacturer = read_string(ec, EC_MEMMAP_BATT_MFGR);
-    let model_number = read_string(ec, EC_MEMMAP_BATT_MODEL);
-    let serial_number = read_string(ec, EC_MEMMAP_BATT_SERIAL);
-    let battery_type = read_string(ec, EC_MEMMAP_BATT_TYPE);
-
-    Some(PowerInfo {
-        ac_present: 0 != (battery_flag & EC_BATT_FLAG_AC_PRESENT),
-        battery: if 0 != (battery_flag & EC_BATT_FLAG_BATT_PRESENT) {
-            Some(BatteryInformation {
-                // TODO: Add some more information
-                present_voltage,
-                present_rate,
-                remaining_capacity: battery_cap,
-                battery_count,
-                current_battery_index,
-                design_capacity,
-                design_voltage,
-                last_full_charge_capacity: battery_lfcc,
-                cycle_count,
-
-                charge_percentage: (100 * battery_cap) / battery_lfcc,
-
-                manufacturer,
-                model_number,
-                serial_number,
-                battery_type,
-                // TODO: Can both be true/falses at the same time?
-                discharging: 0 != (battery_flag & EC_BATT_FLAG_DISCHARGING),
-                charging: 0 != (battery_flag & EC_BATT_FLAG_CHARGING),
-                level_critical: 0 != (battery_flag & EC_BATT_FLAG_LEVEL_CRITICAL),
-            })
-        } else {
-            None
-        },
-    })
+    EcMemmap::read(ec).map(|memmap| memmap.power)
 }
 
 // When no battery is present and we're running on AC
@@ -866,3 +828,41 @@ pub fn standalone_mode(ec: &CrosEc) -> bool {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thermal_snapshot_from_mock() {
+        let _mock = mock::lock_for_test();
+        let ec = CrosEc::with(CrosEcDriverType::Mock).unwrap();
+        // Second fan stalled, as reported by old EC firmware
+        mock::write_memory(
+            EC_MEMMAP_FAN + 2,
+            &EC_FAN_SPEED_STALLED_DEPRECATED.to_le_bytes(),
+        );
+
+        let snapshot = get_thermal_snapshot(&ec).unwrap();
+        let temps: Vec<TempSensor> = snapshot.temperatures.iter().map(|t| t.state).collect();
+        assert_eq!(
+            temps[..4],
+            [
+                TempSensor::Ok(45),
+                TempSensor::Ok(40),
+                TempSensor::Ok(30),
+                TempSensor::Ok(50)
+            ]
+        );
+        assert_eq!(
+            snapshot.fans,
+            [
+                FanSpeed::Rpm(1200),
+                FanSpeed::Stalled,
+                FanSpeed::NotPresent,
+                FanSpeed::NotPresent
+            ]
+        );
+        assert_eq!(get_fan_num(&ec).unwrap(), 2);
+    }
+}
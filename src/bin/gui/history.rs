@@ -0,0 +1,110 @@
+//! Undo and redo of edits, recorded as snapshots of the edited state.
+
+/// Most steps that can be undone, older ones are forgotten.
+const MAX_STEPS: usize = 100;
+
+pub struct History<T> {
+    current: T,
+    undo: Vec<T>,
+    redo: Vec<T>,
+}
+
+impl<T: Clone + PartialEq> History<T> {
+    pub fn new(state: T) -> Self {
+        Self {
+            current: state,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Record `state` as a new step if it differs from the last one, dropping the redo steps.
+    pub fn record(&mut self, state: &T) {
+        if *state == self.current {
+            return;
+        }
+
+        self.undo
+            .push(std::mem::replace(&mut self.current, state.clone()));
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Step back, returning the state to restore.
+    pub fn undo(&mut self) -> Option<T> {
+        let previous = self.undo.pop()?;
+        self.redo
+            .push(std::mem::replace(&mut self.current, previous));
+        Some(self.current.clone())
+    }
+
+    /// Step forward again after [`History::undo`], returning the state to restore.
+    pub fn redo(&mut self) -> Option<T> {
+        let next = self.redo.pop()?;
+        self.undo.push(std::mem::replace(&mut self.current, next));
+        Some(self.current.clone())
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_and_redo() {
+        let mut history = History::new(0);
+        assert!(!history.can_undo() && !history.can_redo());
+        assert_eq!(history.undo(), None);
+
+        history.record(&1);
+        history.record(&2);
+        assert_eq!(history.undo(), Some(1));
+        assert_eq!(history.undo(), Some(0));
+        assert_eq!(history.undo(), None);
+        assert!(history.can_redo());
+        assert_eq!(history.redo(), Some(1));
+        assert_eq!(history.redo(), Some(2));
+        assert_eq!(history.redo(), None);
+    }
+
+    #[test]
+    fn record_skips_no_ops_and_drops_redo() {
+        let mut history = History::new(0);
+        history.record(&0);
+        assert!(!history.can_undo());
+
+        history.record(&1);
+        history.record(&1);
+        assert_eq!(history.undo(), Some(0));
+        assert!(!history.can_undo());
+
+        history.record(&2);
+        assert!(!history.can_redo());
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo(), Some(0));
+    }
+
+    #[test]
+    fn forgets_steps_beyond_the_limit() {
+        let mut history = History::new(0);
+        for state in 1..=MAX_STEPS + 10 {
+            history.record(&state);
+        }
+        let mut undone = 0;
+        while let Some(state) = history.undo() {
+            undone += 1;
+            assert_eq!(state, MAX_STEPS + 10 - undone);
+        }
+        assert_eq!(undone, MAX_STEPS);
+    }
+}
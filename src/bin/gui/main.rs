@@ -0,0 +1,1475 @@
+mod history;
+mod ring;
+mod worker;
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+use framework_lib::chromium_ec::CrosEcDriverType;
+use framework_lib::chromium_ec::commands::RgbS;
+use framework_lib::power::{FanSpeed, ThermalSnapshot};
+use history::History;
+use ring::{RingState, ring_preview};
+use worker::{Job, Report, Tag, Worker};
+
+use fwd_rgb::config::Config;
+#[cfg(unix)]
+use fwd_rgb::daemon::Request;
+use fwd_rgb::effects::{Effect, EffectClock, EffectKind};
+use fwd_rgb::fan_curve::{CurvePoint, FanCurveSettings};
+use fwd_rgb::layout::{Geometry, Layout};
+use fwd_rgb::palette::{self, PaletteFormat};
+use fwd_rgb::preset::{self, Preset};
+use fwd_rgb::profile::{self, Profile};
+use fwd_rgb::state;
+use fwd_rgb::{ColorPipeline, rgb_to_hex_string};
+
+const EFFECT_FPS: f32 = 30.0;
+const THERMAL_POLL_INTERVAL: Duration = Duration::from_secs(2);
+fn color32_from_rgb(color: RgbS) -> egui::Color32 {
+    egui::Color32::from_rgb(color.r, color.g, color.b)
+}
+
+fn rgb_from_color32(color: egui::Color32) -> RgbS {
+    RgbS {
+        r: color.r(),
+        g: color.g(),
+        b: color.b(),
+    }
+}
+
+/// Change to a user preset requested from the presets panel.
+enum PresetAction {
+    Move(String, isize),
+    StartRename(String),
+    CancelRename,
+    Rename(String, String),
+    Delete(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DriverChoice {
+    Auto,
+    Portio,
+    CrosEc,
+    Windows,
+    Mock,
+    /// Talk to a running `fwd_rgb daemon` instead of opening the EC.
+    #[cfg(unix)]
+    Daemon,
+}
+
+impl DriverChoice {
+    fn from_option(driver: Option<CrosEcDriverType>) -> Self {
+        match driver {
+            None => DriverChoice::Auto,
+            Some(CrosEcDriverType::Portio) => DriverChoice::Portio,
+            Some(CrosEcDriverType::CrosEc) => DriverChoice::CrosEc,
+            Some(CrosEcDriverType::Windows) => DriverChoice::Windows,
+            Some(CrosEcDriverType::Mock) => DriverChoice::Mock,
+            // Traces are only replayed from the CLI
+            Some(CrosEcDriverType::Replay) => DriverChoice::Auto,
+        }
+    }
+
+    fn to_option(self) -> Option<CrosEcDriverType> {
+        match self {
+            DriverChoice::Auto => None,
+            DriverChoice::Portio => Some(CrosEcDriverType::Portio),
+            DriverChoice::CrosEc => Some(CrosEcDriverType::CrosEc),
+            DriverChoice::Windows => Some(CrosEcDriverType::Windows),
+            DriverChoice::Mock => Some(CrosEcDriverType::Mock),
+            // The daemon picks its own driver
+            #[cfg(unix)]
+            DriverChoice::Daemon => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            DriverChoice::Auto => "Auto (recommended)",
+            DriverChoice::Portio => "Port I/O",
+            DriverChoice::CrosEc => "Linux cros_ec",
+            DriverChoice::Windows => "Windows HID",
+            DriverChoice::Mock => "Simulated EC (testing)",
+            #[cfg(unix)]
+            DriverChoice::Daemon => "Background daemon (no root needed)",
+        }
+    }
+
+    fn all() -> &'static [DriverChoice] {
+        &[
+            DriverChoice::Auto,
+            DriverChoice::Portio,
+            DriverChoice::CrosEc,
+            DriverChoice::Windows,
+            DriverChoice::Mock,
+            #[cfg(unix)]
+            DriverChoice::Daemon,
+        ]
+    }
+}
+
+#[derive(Clone, Copy)]
+enum StatusKind {
+    Info,
+    Success,
+    Error,
+}
+
+struct StatusMessage {
+    kind: StatusKind,
+    text: String,
+}
+
+struct FanRgbApp {
+    layout: Layout,
+    /// One color per zone of the layout.
+    colors: Vec<egui::Color32>,
+    ring: RingState,
+    /// Color edits, recorded once the pointer is released so a drag is a single step.
+    history: History<Vec<egui::Color32>>,
+    /// Colors sent by the last successful Apply, what "Revert" goes back to.
+    applied_colors: Option<Vec<egui::Color32>>,
+    /// Color applied to the zones selected on the ring.
+    selection_color: egui::Color32,
+    status: Option<StatusMessage>,
+    driver: DriverChoice,
+    auto_apply: bool,
+    dirty: bool,
+    lights_enabled: bool,
+    color_pipeline: ColorPipeline,
+    effect: Effect,
+    effect_clock: Option<EffectClock>,
+    /// Last frame of the playing effect, shown on the ring.
+    effect_frame: Vec<egui::Color32>,
+    profile_names: Vec<String>,
+    active_profile: Option<String>,
+    /// Name being typed into the "Save as…" field, `None` while the field is hidden.
+    new_profile_name: Option<String>,
+    builtin_presets: Vec<Preset>,
+    user_presets: Vec<Preset>,
+    /// Name being typed into the "Save as preset…" field, `None` while the field is hidden.
+    new_preset_name: Option<String>,
+    /// User preset being renamed and the name typed so far.
+    renaming_preset: Option<(String, String)>,
+    /// Path typed into the "Export…" field, `None` while the field is hidden.
+    export_path: Option<String>,
+    thermal: Option<ThermalSnapshot>,
+    thermal_polled: Option<Instant>,
+    fan_curve: FanCurveSettings,
+    /// Whether the worker runs the fan curve.
+    fan_curve_running: bool,
+    /// Last fan speed read for fan-synced effects.
+    fan_rpm: Option<u16>,
+    /// Does all EC and daemon I/O, so the GUI never waits on it.
+    worker: Worker,
+}
+
+impl FanRgbApp {
+    fn new(ctx: &egui::Context) -> Self {
+        let mut app = Self {
+            layout: Layout::default(),
+            colors: Vec::new(),
+            ring: RingState::default(),
+            history: History::new(Vec::new()),
+            applied_colors: None,
+            selection_color: egui::Color32::WHITE,
+            status: Some(StatusMessage {
+                kind: StatusKind::Info,
+                text: "Adjust the colors and press Apply to update the fan LEDs.".to_string(),
+            }),
+            driver: DriverChoice::Auto,
+            auto_apply: false,
+            dirty: false,
+            lights_enabled: true,
+            color_pipeline: ColorPipeline::default(),
+            effect: Effect::new(EffectKind::Rainbow),
+            effect_clock: None,
+            effect_frame: Vec::new(),
+            profile_names: Vec::new(),
+            active_profile: None,
+            new_profile_name: None,
+            builtin_presets: preset::builtin_presets(),
+            user_presets: Vec::new(),
+            new_preset_name: None,
+            renaming_preset: None,
+            export_path: None,
+            thermal: None,
+            thermal_polled: None,
+            fan_curve: FanCurveSettings::default(),
+            fan_curve_running: false,
+            fan_rpm: None,
+            worker: Worker::spawn(ctx.clone()),
+        };
+
+        let config = Config::load();
+        // Config::load rejects a bad layout, check it again anyway as the sliders rely on it
+        app.layout = config
+            .as_ref()
+            .map(Config::layout)
+            .ok()
+            .filter(|layout| layout.validate().is_ok())
+            .unwrap_or_else(Layout::detect);
+        app.reset_spectrum();
+        app.dirty = false;
+
+        match config {
+            Ok(config) => {
+                app.auto_apply = config.gui.auto_apply;
+                app.color_pipeline = config.color;
+                app.fan_curve = config.fan_curve.clone();
+                app.profile_names = config.profiles.keys().cloned().collect();
+                app.user_presets = config.presets.clone();
+                let active = config
+                    .active_profile
+                    .and_then(|name| Some((config.profiles.get(&name)?.clone(), name)));
+                if let Some((profile, name)) = active {
+                    if let Err(err) = app.load_profile(&profile) {
+                        app.set_status(StatusKind::Error, err);
+                    } else {
+                        app.active_profile = Some(name);
+                        app.dirty = false;
+                    }
+                }
+            }
+            Err(err) => app.set_status(StatusKind::Error, err),
+        }
+
+        // Pick up where the last run left off
+        match state::last_frame(app.driver.to_option()).and_then(|frame| {
+            frame
+                .map(|frame| Ok((frame.start_key, frame.rgb_colors()?)))
+                .transpose()
+        }) {
+            Ok(Some((start_key, colors))) => app.resume_colors(start_key, colors),
+            Ok(None) => {}
+            Err(err) => app.set_status(StatusKind::Error, err),
+        }
+
+        app.history = History::new(app.colors.clone());
+        app
+    }
+
+    /// Show `colors` as what the LEDs currently display.
+    fn resume_colors(&mut self, start_key: u8, colors: Vec<RgbS>) {
+        if colors.is_empty() {
+            return;
+        }
+        let colors: Vec<_> = colors.into_iter().map(color32_from_rgb).collect();
+        self.layout.start_key = start_key;
+        self.layout.zones = colors.len();
+        self.ring.retain(self.layout.zones);
+        self.colors = colors.clone();
+        self.applied_colors = Some(colors);
+        self.lights_enabled = true;
+        self.dirty = false;
+    }
+
+    fn set_status(&mut self, kind: StatusKind, text: impl Into<String>) {
+        self.status = Some(StatusMessage {
+            kind,
+            text: text.into(),
+        });
+    }
+
+    fn current_colors(&self) -> Vec<RgbS> {
+        self.colors.iter().copied().map(rgb_from_color32).collect()
+    }
+
+    /// Fill all zones with the colors of `preset`.
+    fn apply_preset(&mut self, preset: &Preset) {
+        match preset.render(self.layout.zones) {
+            Ok(colors) => {
+                self.colors = colors.into_iter().map(color32_from_rgb).collect();
+                self.dirty = true;
+                self.lights_enabled = true;
+            }
+            Err(err) => self.set_status(StatusKind::Error, err),
+        }
+    }
+
+    /// Change the number of zones, repeating the existing colors into new zones.
+    fn set_zone_count(&mut self, zones: usize) {
+        let pattern = self.colors.clone();
+        self.colors = (0..zones)
+            .map(|idx| {
+                pattern
+                    .get(idx % pattern.len().max(1))
+                    .copied()
+                    .unwrap_or(egui::Color32::BLACK)
+            })
+            .collect();
+        self.layout.zones = zones;
+        self.dirty = true;
+    }
+
+    fn save_layout(&mut self) {
+        let result = Config::load().and_then(|mut config| {
+            config.layout = Some(self.layout);
+            config.save()
+        });
+        match result {
+            Ok(()) => self.set_status(StatusKind::Success, "Saved layout as the default"),
+            Err(err) => self.set_status(StatusKind::Error, err),
+        }
+    }
+
+    /// Copy a profile's colors, start key and driver into the editor.
+    fn load_profile(&mut self, profile: &Profile) -> Result<(), String> {
+        let colors = profile.rgb_colors()?;
+        self.set_driver(DriverChoice::from_option(profile.driver()?));
+        self.layout.start_key = profile.start_key;
+        self.layout.zones = colors.len();
+        self.colors = colors.into_iter().map(color32_from_rgb).collect();
+        self.dirty = true;
+        self.lights_enabled = true;
+        Ok(())
+    }
+
+    fn refresh_profile_names(&mut self) {
+        match profile::list_profiles() {
+            Ok(names) => self.profile_names = names,
+            Err(err) => self.set_status(StatusKind::Error, err),
+        }
+    }
+
+    fn select_profile(&mut self, name: &str) {
+        let result = profile::load_profile(name).and_then(|profile| self.load_profile(&profile));
+        if let Err(err) = result {
+            self.set_status(StatusKind::Error, err);
+            return;
+        }
+
+        self.active_profile = Some(name.to_string());
+        if let Err(err) = profile::set_active_profile(name) {
+            self.set_status(StatusKind::Error, err);
+        } else if self.auto_apply {
+            self.apply();
+        } else {
+            self.set_status(StatusKind::Info, format!("Loaded profile {name}"));
+        }
+    }
+
+    fn save_profile_as(&mut self, name: &str) {
+        let profile = Profile::new(
+            self.layout.start_key,
+            &self.current_colors(),
+            self.driver.to_option(),
+        );
+        match profile::save_profile(name, &profile) {
+            Ok(()) => {
+                let name = name.trim().to_string();
+                self.set_status(StatusKind::Success, format!("Saved profile {name}"));
+                self.active_profile = Some(name);
+                self.new_profile_name = None;
+                self.refresh_profile_names();
+            }
+            Err(err) => self.set_status(StatusKind::Error, err),
+        }
+    }
+
+    fn delete_active_profile(&mut self) {
+        let Some(name) = self.active_profile.take() else {
+            return;
+        };
+        match profile::delete_profile(&name) {
+            Ok(()) => self.set_status(StatusKind::Info, format!("Deleted profile {name}")),
+            Err(err) => self.set_status(StatusKind::Error, err),
+        }
+        self.refresh_profile_names();
+    }
+
+    /// Load the palettes dropped onto the window, the last one wins.
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        for file in ctx.input(|i| i.raw.dropped_files.clone()) {
+            let (name, colors) = if let Some(path) = &file.path {
+                (
+                    path.file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| path.display().to_string()),
+                    palette::import(path, self.layout.zones),
+                )
+            } else if let Some(bytes) = &file.bytes {
+                // Some platforms only hand over the contents
+                let colors = PaletteFormat::from_path(Path::new(&file.name))
+                    .and_then(|format| palette::import_bytes(format, bytes, self.layout.zones));
+                (file.name.clone(), colors)
+            } else {
+                continue;
+            };
+
+            match colors {
+                Ok(colors) => {
+                    self.stop_effect();
+                    self.colors = (0..self.layout.zones)
+                        .map(|idx| color32_from_rgb(colors[idx % colors.len()]))
+                        .collect();
+                    self.dirty = true;
+                    self.lights_enabled = true;
+                    self.set_status(
+                        StatusKind::Success,
+                        format!("Loaded {} colors from {name}", colors.len()),
+                    );
+                    self.finish_preset();
+                }
+                Err(err) => self.set_status(StatusKind::Error, err),
+            }
+        }
+    }
+
+    fn default_export_path() -> String {
+        dirs::home_dir()
+            .unwrap_or_default()
+            .join("fwd_rgb-palette.gpl")
+            .display()
+            .to_string()
+    }
+
+    fn export_palette(&mut self, path: &str) {
+        let path = PathBuf::from(path.trim());
+        let profile = Profile::new(
+            self.layout.start_key,
+            &self.current_colors(),
+            self.driver.to_option(),
+        );
+        let name = self.active_profile.as_deref().unwrap_or("fwd_rgb");
+        match palette::export(&path, name, &profile) {
+            Ok(()) => {
+                self.set_status(
+                    StatusKind::Success,
+                    format!("Exported palette to {}", path.display()),
+                );
+                self.export_path = None;
+            }
+            Err(err) => self.set_status(StatusKind::Error, err),
+        }
+    }
+
+    fn export_ui(&mut self, ui: &mut egui::Ui) {
+        if let Some(mut path) = self.export_path.take() {
+            ui.horizontal(|ui| {
+                let response = ui.add(egui::TextEdit::singleline(&mut path).desired_width(320.0));
+                let mut export =
+                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                export |= ui.button("Export").clicked();
+                let cancelled = ui.button("Cancel").clicked();
+
+                if !cancelled {
+                    self.export_path = Some(path.clone());
+                    if export {
+                        self.export_palette(&path);
+                    }
+                }
+            });
+            ui.label("The extension picks the format: .gpl, .json or .toml.");
+        } else {
+            ui.horizontal(|ui| {
+                if ui.button("Export…").clicked() {
+                    self.export_path = Some(Self::default_export_path());
+                }
+                ui.weak("Drop a profile, GIMP palette or image here to load its colors.");
+            });
+        }
+    }
+
+    fn refresh_presets(&mut self) {
+        match Config::load() {
+            Ok(config) => self.user_presets = config.presets,
+            Err(err) => self.set_status(StatusKind::Error, err),
+        }
+    }
+
+    /// Run a change to the saved presets and reload them, reporting `success` if it worked.
+    fn update_presets(&mut self, result: Result<(), String>, success: String) {
+        match result {
+            Ok(()) => self.set_status(StatusKind::Success, success),
+            Err(err) => self.set_status(StatusKind::Error, err),
+        }
+        self.refresh_presets();
+    }
+
+    /// Preset buttons, then the user's presets with controls to reorder, rename and delete them.
+    fn presets_ui(&mut self, ui: &mut egui::Ui) {
+        let mut chosen = None;
+        for preset in &self.builtin_presets {
+            if ui.button(&preset.name).clicked() {
+                chosen = Some(preset.clone());
+            }
+        }
+
+        if !self.user_presets.is_empty() {
+            ui.separator();
+        }
+        let mut action = None;
+        let last = self.user_presets.len().saturating_sub(1);
+        for (idx, preset) in self.user_presets.iter().enumerate() {
+            if let Some((name, new_name)) = &mut self.renaming_preset
+                && preset.is_named(name)
+            {
+                ui.horizontal(|ui| {
+                    let response =
+                        ui.add(egui::TextEdit::singleline(new_name).desired_width(100.0));
+                    if ui.small_button("OK").clicked()
+                        || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+                    {
+                        action = Some(PresetAction::Rename(name.clone(), new_name.clone()));
+                    }
+                    if ui.small_button("Cancel").clicked() {
+                        action = Some(PresetAction::CancelRename);
+                    }
+                });
+                continue;
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button(&preset.name).clicked() {
+                    chosen = Some(preset.clone());
+                }
+                let name = || preset.name.clone();
+                if ui
+                    .add_enabled(idx > 0, egui::Button::new("⏶").small())
+                    .on_hover_text("Move up")
+                    .clicked()
+                {
+                    action = Some(PresetAction::Move(name(), -1));
+                }
+                if ui
+                    .add_enabled(idx < last, egui::Button::new("⏷").small())
+                    .on_hover_text("Move down")
+                    .clicked()
+                {
+                    action = Some(PresetAction::Move(name(), 1));
+                }
+                if ui.small_button("✏").on_hover_text("Rename").clicked() {
+                    action = Some(PresetAction::StartRename(name()));
+                }
+                if ui.small_button("🗑").on_hover_text("Delete").clicked() {
+                    action = Some(PresetAction::Delete(name()));
+                }
+            });
+        }
+
+        if let Some(preset) = chosen {
+            self.apply_preset(&preset);
+            self.finish_preset();
+        }
+        match action {
+            Some(PresetAction::Move(name, offset)) => {
+                let result = preset::move_preset(&name, offset);
+                self.update_presets(result, format!("Moved preset {name}"));
+            }
+            Some(PresetAction::StartRename(name)) => {
+                self.renaming_preset = Some((name.clone(), name));
+            }
+            Some(PresetAction::CancelRename) => self.renaming_preset = None,
+            Some(PresetAction::Rename(name, new_name)) => {
+                let result = preset::rename_preset(&name, &new_name);
+                if result.is_ok() {
+                    self.renaming_preset = None;
+                }
+                let new_name = new_name.trim();
+                self.update_presets(result, format!("Renamed preset {name} to {new_name}"));
+            }
+            Some(PresetAction::Delete(name)) => {
+                let result = preset::delete_preset(&name);
+                self.update_presets(result, format!("Deleted preset {name}"));
+            }
+            None => {}
+        }
+
+        if let Some(mut name) = self.new_preset_name.take() {
+            let response = ui.text_edit_singleline(&mut name);
+            let mut save = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let mut cancelled = false;
+            ui.horizontal(|ui| {
+                save |= ui.button("Save").clicked();
+                cancelled = ui.button("Cancel").clicked();
+            });
+
+            if !cancelled {
+                self.new_preset_name = Some(name.clone());
+                if save {
+                    let result = preset::save_preset(&Preset::new(&name, &self.current_colors()));
+                    if result.is_ok() {
+                        self.new_preset_name = None;
+                    }
+                    self.update_presets(result, format!("Saved preset {}", name.trim()));
+                }
+            }
+        } else if ui.button("Save as preset…").clicked() {
+            self.new_preset_name = Some(String::new());
+        }
+        ui.separator();
+    }
+
+    /// Put back colors from the history or the last Apply.
+    fn restore_colors(&mut self, colors: Vec<egui::Color32>) {
+        self.stop_effect();
+        self.layout.zones = colors.len();
+        self.colors = colors;
+        self.ring.retain(self.layout.zones);
+        self.lights_enabled = true;
+        self.dirty = self.applied_colors.as_ref() != Some(&self.colors);
+        if self.dirty && self.auto_apply {
+            self.apply();
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(colors) = self.history.undo() {
+            self.restore_colors(colors);
+            self.set_status(StatusKind::Info, "Undid the last change");
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(colors) = self.history.redo() {
+            self.restore_colors(colors);
+            self.set_status(StatusKind::Info, "Redid the change");
+        }
+    }
+
+    fn revert_to_applied(&mut self) {
+        if let Some(colors) = self.applied_colors.clone() {
+            self.restore_colors(colors);
+            self.set_status(StatusKind::Info, "Reverted to the applied colors");
+        }
+    }
+
+    /// Ctrl+Z undoes, Ctrl+Shift+Z or Ctrl+Y redoes and Ctrl+Enter applies, unless a text field
+    /// has the keyboard.
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+
+        let shortcut = |modifiers, key| egui::KeyboardShortcut::new(modifiers, key);
+        let (redo, undo, apply) = ctx.input_mut(|i| {
+            // Most specific first, Ctrl+Z also matches Ctrl+Shift+Z
+            let redo = i.consume_shortcut(&shortcut(
+                egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                egui::Key::Z,
+            )) || i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::Y));
+            let undo = i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::Z));
+            let apply = i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::Enter));
+            (redo, undo, apply)
+        });
+
+        if redo {
+            self.redo();
+        }
+        if undo {
+            self.undo();
+        }
+        if apply {
+            self.stop_effect();
+            self.apply();
+        }
+    }
+
+    fn save_auto_apply(&mut self) {
+        let result = Config::load().and_then(|mut config| {
+            config.gui.auto_apply = self.auto_apply;
+            config.save()
+        });
+        if let Err(err) = result {
+            self.set_status(StatusKind::Error, err);
+        }
+    }
+
+    /// Hand new brightness/gamma settings to the lights, and save them once `done` editing.
+    fn update_color_pipeline(&mut self, done: bool) {
+        #[cfg(unix)]
+        let daemon = self.driver == DriverChoice::Daemon;
+        #[cfg(not(unix))]
+        let daemon = false;
+
+        if daemon {
+            #[cfg(unix)]
+            self.worker.send(Job::Daemon(
+                Request::SetColorPipeline(self.color_pipeline),
+                Tag::Quiet,
+            ));
+        } else if self.lights_enabled && self.effect_clock.is_none() && !self.dirty {
+            // Effects pick the new settings up with the next frame
+            self.apply();
+        }
+
+        if done {
+            let result = Config::load().and_then(|mut config| {
+                config.color = self.color_pipeline;
+                config.save()
+            });
+            if let Err(err) = result {
+                self.set_status(StatusKind::Error, err);
+            }
+        }
+    }
+
+    fn color_pipeline_ui(&mut self, ui: &mut egui::Ui) {
+        let pipeline = &mut self.color_pipeline;
+        let mut responses = vec![
+            ui.add(egui::Slider::new(&mut pipeline.brightness, 0..=100).text("brightness %")),
+            ui.add(
+                egui::Slider::new(&mut pipeline.gamma, ColorPipeline::GAMMA_RANGE).text("gamma"),
+            ),
+        ];
+        egui::CollapsingHeader::new("White balance").show(ui, |ui| {
+            let balance = &mut pipeline.white_balance;
+            for (gain, label) in [
+                (&mut balance.red, "red"),
+                (&mut balance.green, "green"),
+                (&mut balance.blue, "blue"),
+            ] {
+                responses.push(ui.add(egui::Slider::new(gain, 0.0..=1.0).text(label)));
+            }
+        });
+
+        if responses.iter().any(|response| response.changed()) {
+            // Don't rewrite the config file on every step of a drag
+            let done = !responses.iter().any(|response| response.dragged());
+            self.update_color_pipeline(done);
+        } else if responses.iter().any(|response| response.drag_stopped()) {
+            self.update_color_pipeline(true);
+        }
+    }
+
+    fn finish_preset(&mut self) {
+        if self.auto_apply && self.lights_enabled {
+            self.apply();
+        }
+    }
+
+    fn apply(&mut self) {
+        if self.lights_enabled {
+            self.send_colors(self.current_colors(), Tag::Applied(self.colors.clone()));
+        } else {
+            self.turn_off_lights();
+        }
+    }
+
+    /// Queue colors for the EC, or the daemon when it is selected.
+    fn send_colors(&self, colors: Vec<RgbS>, tag: Tag) {
+        #[cfg(unix)]
+        let daemon = self.driver == DriverChoice::Daemon;
+        #[cfg(not(unix))]
+        let daemon = false;
+
+        // The daemon applies its own brightness and gamma
+        let colors = if daemon {
+            colors
+        } else {
+            self.color_pipeline.apply_all(colors)
+        };
+        self.worker.send(Job::Write {
+            driver: self.driver,
+            start_key: self.layout.start_key,
+            colors,
+            tag,
+        });
+    }
+
+    /// Act on what the worker reports back.
+    fn handle_reports(&mut self) {
+        let reports: Vec<_> = self.worker.reports().collect();
+        for report in reports {
+            match report {
+                Report::Done(Tag::Applied(colors), Ok(())) => {
+                    // The colors may have been edited again while they were written
+                    if colors == self.colors {
+                        self.dirty = false;
+                    }
+                    self.set_status(
+                        StatusKind::Success,
+                        format!(
+                            "Updated {} colors starting at {}",
+                            colors.len(),
+                            self.layout.start_key
+                        ),
+                    );
+                    self.applied_colors = Some(colors);
+                }
+                Report::Done(Tag::TurnedOff, Ok(())) => {
+                    self.dirty = false;
+                    self.set_status(StatusKind::Info, "Fan lighting disabled");
+                }
+                Report::Done(_, Ok(())) => {}
+                Report::Done(Tag::Effect, Err(err)) => {
+                    self.effect_clock = None;
+                    self.set_status(StatusKind::Error, err);
+                }
+                Report::Done(_, Err(err)) => self.set_status(StatusKind::Error, err),
+                Report::Thermal(snapshot) => self.thermal = snapshot,
+                Report::FanRpm(rpm) => self.fan_rpm = rpm,
+                Report::FanCurve(Ok(true)) => {
+                    self.fan_curve_running = true;
+                    self.set_status(StatusKind::Info, "Fan curve running");
+                }
+                Report::FanCurve(Ok(false)) => {
+                    self.fan_curve_running = false;
+                    self.set_status(StatusKind::Info, "Fans returned to automatic control");
+                }
+                Report::FanCurve(Err(err)) => {
+                    self.fan_curve_running = false;
+                    self.set_status(StatusKind::Error, err);
+                }
+            }
+        }
+    }
+
+    fn start_effect(&mut self) {
+        self.effect.color = self
+            .colors
+            .first()
+            .copied()
+            .map_or(self.effect.color, rgb_from_color32);
+
+        // The daemon animates the effect itself, the GUI only hands it over
+        #[cfg(unix)]
+        if self.driver == DriverChoice::Daemon {
+            let request = Request::SetEffect {
+                start_key: self.layout.start_key,
+                zones: self.layout.zones,
+                geometry: self.layout.geometry,
+                kind: self.effect.kind,
+                color: rgb_to_hex_string(self.effect.color),
+                speed: self.effect.speed,
+            };
+            self.worker.send(Job::Daemon(request, Tag::Effect));
+        }
+
+        self.effect_clock = Some(EffectClock::new());
+        self.lights_enabled = true;
+        self.set_status(
+            StatusKind::Info,
+            format!("Playing {} effect", self.effect.kind.label()),
+        );
+    }
+
+    fn stop_effect(&mut self) {
+        if self.effect_clock.take().is_none() {
+            return;
+        }
+
+        #[cfg(unix)]
+        if self.driver == DriverChoice::Daemon {
+            // Otherwise the daemon keeps animating
+            self.send_colors(self.current_colors(), Tag::Quiet);
+        }
+        self.set_status(StatusKind::Info, "Effect stopped");
+    }
+
+    /// Queue the next effect frame for the EC and schedule the one after it.
+    ///
+    /// With the daemon the frame is only rendered for the preview, the daemon animates the lights.
+    fn play_effect_frame(&mut self, ctx: &egui::Context) {
+        let Some(clock) = &mut self.effect_clock else {
+            return;
+        };
+
+        // The speed read now arrives with a later frame
+        let t = clock.tick(&self.effect, || {
+            self.worker.send(Job::ReadFanRpm(self.driver));
+            self.fan_rpm
+        });
+        let frame = self.effect.frame_for(t, &self.layout);
+        self.effect_frame = frame.iter().copied().map(color32_from_rgb).collect();
+        ctx.request_repaint_after(Duration::from_secs_f32(1.0 / EFFECT_FPS));
+
+        #[cfg(unix)]
+        if self.driver == DriverChoice::Daemon {
+            return;
+        }
+        self.send_colors(frame, Tag::Effect);
+    }
+
+    /// Ring preview with controls for recoloring the selected zones.
+    fn ring_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        self.ring.retain(self.colors.len());
+        let previous = self.ring.selected.clone();
+        let shown = self
+            .effect_clock
+            .is_some()
+            .then_some(self.effect_frame.as_slice());
+
+        let mut updated = ring_preview(
+            ui,
+            &mut self.colors,
+            shown,
+            self.layout.geometry,
+            &mut self.ring,
+            200.0,
+        );
+
+        if self.ring.selected != previous
+            && let Some(first) = self.ring.selected.first()
+        {
+            self.selection_color = self.colors[*first];
+        }
+
+        ui.horizontal(|ui| {
+            let count = self.ring.selected.len();
+            ui.label(format!(
+                "{count} zone{} selected",
+                if count == 1 { "" } else { "s" }
+            ));
+            let enabled = count > 0;
+            ui.add_enabled_ui(enabled, |ui| {
+                let response = egui::color_picker::color_edit_button_srgba(
+                    ui,
+                    &mut self.selection_color,
+                    egui::color_picker::Alpha::Opaque,
+                );
+                if response.changed() {
+                    for zone in &self.ring.selected {
+                        self.colors[*zone] = self.selection_color;
+                    }
+                    updated = true;
+                }
+            });
+            if ui.small_button("All").clicked() {
+                self.ring.select_all(self.colors.len());
+            }
+            if ui
+                .add_enabled(enabled, egui::Button::new("None").small())
+                .clicked()
+            {
+                self.ring.selected.clear();
+            }
+        });
+        ui.weak("Click, Ctrl+click or drag to select. Shift+drag blends colors.");
+
+        updated
+    }
+
+    /// Refresh the temperatures and fan speeds shown in the status bar.
+    fn poll_thermal(&mut self, ctx: &egui::Context) {
+        #[cfg(unix)]
+        if self.driver == DriverChoice::Daemon {
+            // Reading the EC needs the privileges the daemon is there to avoid
+            self.thermal = None;
+            return;
+        }
+
+        if let Some(polled) = self.thermal_polled {
+            let elapsed = polled.elapsed();
+            if elapsed < THERMAL_POLL_INTERVAL {
+                ctx.request_repaint_after(THERMAL_POLL_INTERVAL - elapsed);
+                return;
+            }
+        }
+
+        self.thermal_polled = Some(Instant::now());
+        self.worker.send(Job::ReadThermal(self.driver));
+        ctx.request_repaint_after(THERMAL_POLL_INTERVAL);
+    }
+
+    fn start_fan_curve(&mut self) {
+        self.worker
+            .send(Job::StartFanCurve(self.driver, self.fan_curve.clone()));
+        // Show the new fan speeds right away instead of at the next poll
+        self.thermal_polled = None;
+    }
+
+    /// The fan curve runs on the driver it was started with, stop it when switching away.
+    fn set_driver(&mut self, driver: DriverChoice) {
+        if driver != self.driver {
+            self.stop_fan_curve();
+        }
+        self.driver = driver;
+    }
+
+    fn stop_fan_curve(&mut self) {
+        self.worker.send(Job::StopFanCurve);
+    }
+
+    fn save_fan_curve(&mut self) {
+        let result = Config::load().and_then(|mut config| {
+            config.fan_curve = self.fan_curve.clone();
+            config.save()
+        });
+        match result {
+            Ok(()) => self.set_status(StatusKind::Success, "Saved fan curve"),
+            Err(err) => self.set_status(StatusKind::Error, err),
+        }
+    }
+
+    fn fan_curve_ui(&mut self, ui: &mut egui::Ui) {
+        for (curve_idx, curve) in self.fan_curve.curves.iter_mut().enumerate() {
+            ui.label(format!("Curve for {}", curve.label()));
+
+            let mut remove = None;
+            egui::Grid::new(("fan_curve_points", curve_idx))
+                .num_columns(3)
+                .show(ui, |ui| {
+                    for (idx, point) in curve.points.iter_mut().enumerate() {
+                        ui.add(
+                            egui::DragValue::new(&mut point.temp)
+                                .clamp_range(0.0..=110.0)
+                                .suffix(" °C"),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut point.duty)
+                                .clamp_range(0.0..=100.0)
+                                .suffix(" %"),
+                        );
+                        if ui.small_button("✕").clicked() {
+                            remove = Some(idx);
+                        }
+                        ui.end_row();
+                    }
+                });
+            if let Some(idx) = remove {
+                curve.points.remove(idx);
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Add point").clicked() {
+                    let last = curve.points.last().copied().unwrap_or(CurvePoint {
+                        temp: 40.0,
+                        duty: 20.0,
+                    });
+                    curve.points.push(CurvePoint {
+                        temp: (last.temp + 10.0).min(110.0),
+                        duty: last.duty,
+                    });
+                }
+                ui.label("Duty");
+                ui.add(egui::DragValue::new(&mut curve.min_duty).clamp_range(0..=100));
+                ui.label("to");
+                ui.add(egui::DragValue::new(&mut curve.max_duty).clamp_range(0..=100));
+                ui.label("%");
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Hysteresis");
+            ui.add(
+                egui::DragValue::new(&mut self.fan_curve.hysteresis)
+                    .clamp_range(0.0..=20.0)
+                    .suffix(" °C"),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Save curve").clicked() {
+                self.save_fan_curve();
+            }
+
+            if self.fan_curve_running {
+                if ui.button("Stop (automatic fans)").clicked() {
+                    self.stop_fan_curve();
+                }
+                ui.label("Restart to use edits");
+            } else {
+                #[cfg(unix)]
+                let available = self.driver != DriverChoice::Daemon;
+                #[cfg(not(unix))]
+                let available = true;
+                if ui
+                    .add_enabled(available, egui::Button::new("Run curve"))
+                    .clicked()
+                {
+                    self.start_fan_curve();
+                }
+            }
+        });
+    }
+
+    fn thermal_summary(snapshot: &ThermalSnapshot) -> String {
+        let temperatures = snapshot.temperatures.iter().filter_map(|temp| {
+            temp.state
+                .celsius()
+                .map(|celsius| format!("{} {celsius} °C", temp.name))
+        });
+        let fans = snapshot
+            .fans
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, fan)| match fan {
+                FanSpeed::Rpm(rpm) => Some(format!("Fan {} {rpm} RPM", idx + 1)),
+                FanSpeed::Stalled => Some(format!("Fan {} stalled", idx + 1)),
+                FanSpeed::NotPresent => None,
+            });
+        temperatures.chain(fans).collect::<Vec<_>>().join("  ·  ")
+    }
+
+    fn turn_off_lights(&mut self) {
+        #[cfg(unix)]
+        if self.driver == DriverChoice::Daemon {
+            self.worker.send(Job::Daemon(Request::Off, Tag::TurnedOff));
+            return;
+        }
+
+        let off = vec![RgbS { r: 0, g: 0, b: 0 }; self.layout.zones];
+        self.send_colors(off, Tag::TurnedOff);
+    }
+
+    fn reset_spectrum(&mut self) {
+        if let Some(spectrum) = self.builtin_presets.first().cloned() {
+            self.apply_preset(&spectrum);
+        }
+    }
+
+    fn apply_twilight(&mut self) {
+        let first = self.colors.first().copied().unwrap_or(egui::Color32::BLACK);
+        let last = self.colors.last().copied().unwrap_or(egui::Color32::BLACK);
+
+        for (idx, color) in self.colors.iter_mut().enumerate() {
+            let t = idx as f32 / (self.layout.zones.saturating_sub(1) as f32).max(1.0);
+            *color = egui::Color32::from_rgb(
+                Self::lerp_channel(first.r(), last.r(), t),
+                Self::lerp_channel(first.g(), last.g(), t),
+                Self::lerp_channel(first.b(), last.b(), t),
+            );
+        }
+
+        self.dirty = true;
+        self.lights_enabled = true;
+    }
+
+    fn lerp_channel(a: u8, b: u8, t: f32) -> u8 {
+        let a = a as f32;
+        let b = b as f32;
+        (a + (b - a) * t).round().clamp(0.0, 255.0) as u8
+    }
+}
+
+impl eframe::App for FanRgbApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if ctx.input(|i| i.viewport().close_requested()) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            return;
+        }
+
+        self.handle_reports();
+        self.handle_shortcuts(ctx);
+        self.handle_dropped_files(ctx);
+        self.play_effect_frame(ctx);
+        self.poll_thermal(ctx);
+
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            ui.heading("Framework CPU Fan RGB");
+            ui.label("Configure a contiguous range of zones and apply them directly via the EC.");
+            ui.add(
+                egui::Label::new(
+                    "Note: EC access requires administrative privileges (sudo or \
+Administrator) on Framework systems, unless a running daemon is selected as the driver.",
+                )
+                .wrap(true),
+            );
+        });
+
+        egui::SidePanel::right("presets_panel")
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .id_source("presets_scroll")
+                    .show(ui, |ui| {
+                        ui.heading("Presets");
+                        self.presets_ui(ui);
+
+                        if ui
+                            .button("Twilight")
+                            .on_hover_text("Blend from the first to the last zone")
+                            .clicked()
+                        {
+                            self.apply_twilight();
+                            self.finish_preset();
+                        }
+                        if ui.button("Fan Sync").clicked() {
+                            self.effect.kind = EffectKind::FanSync;
+                            self.start_effect();
+                            ctx.request_repaint();
+                        }
+
+                        ui.separator();
+                        ui.heading("Profiles");
+
+                        let mut selected = None;
+                        egui::ComboBox::from_id_source("profile_choice")
+                            .selected_text(self.active_profile.as_deref().unwrap_or("None"))
+                            .show_ui(ui, |ui| {
+                                for name in &self.profile_names {
+                                    let active = self.active_profile.as_ref() == Some(name);
+                                    if ui.selectable_label(active, name).clicked() {
+                                        selected = Some(name.clone());
+                                    }
+                                }
+                            });
+                        if let Some(name) = selected {
+                            self.stop_effect();
+                            self.select_profile(&name);
+                        }
+
+                        if let Some(mut name) = self.new_profile_name.take() {
+                            let response = ui.text_edit_singleline(&mut name);
+                            let submitted = response.lost_focus()
+                                && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                            let mut cancelled = false;
+                            let mut save = submitted;
+                            ui.horizontal(|ui| {
+                                save |= ui.button("Save").clicked();
+                                cancelled = ui.button("Cancel").clicked();
+                            });
+
+                            if !cancelled {
+                                self.new_profile_name = Some(name.clone());
+                                if save {
+                                    self.save_profile_as(&name);
+                                }
+                            }
+                        } else {
+                            ui.horizontal(|ui| {
+                                if ui.button("Save as…").clicked() {
+                                    self.new_profile_name =
+                                        Some(self.active_profile.clone().unwrap_or_default());
+                                }
+                                if ui
+                                    .add_enabled(
+                                        self.active_profile.is_some(),
+                                        egui::Button::new("Delete"),
+                                    )
+                                    .clicked()
+                                {
+                                    self.delete_active_profile();
+                                }
+                            });
+                        }
+
+                        ui.separator();
+                        if ui
+                            .checkbox(&mut self.auto_apply, "Auto-apply after changes")
+                            .changed()
+                        {
+                            self.save_auto_apply();
+                        }
+                        if ui
+                            .checkbox(&mut self.lights_enabled, "Lighting enabled")
+                            .changed()
+                        {
+                            if !self.lights_enabled {
+                                self.stop_effect();
+                            }
+                            self.apply();
+                        }
+                    });
+            });
+
+        if let Some(snapshot) = &self.thermal {
+            egui::TopBottomPanel::bottom("thermal_status").show(ctx, |ui| {
+                ui.label(Self::thermal_summary(snapshot));
+            });
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .id_source("central")
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Start key");
+                        // Keep the last zone within the key range
+                        let max_start =
+                            Layout::MAX_KEYS.saturating_sub(self.layout.zones).min(255) as u8;
+                        ui.add(
+                            egui::Slider::new(&mut self.layout.start_key, 0..=max_start)
+                                .text("index")
+                                .clamp_to_range(true),
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Zones");
+                        let max_zones =
+                            Layout::MAX_KEYS.saturating_sub(self.layout.start_key as usize);
+                        let mut zones = self.layout.zones;
+                        ui.add(egui::DragValue::new(&mut zones).clamp_range(1..=max_zones));
+                        if zones != self.layout.zones {
+                            self.set_zone_count(zones);
+                        }
+
+                        egui::ComboBox::from_id_source("geometry")
+                            .selected_text(self.layout.geometry.label())
+                            .show_ui(ui, |ui| {
+                                for geometry in Geometry::all() {
+                                    ui.selectable_value(
+                                        &mut self.layout.geometry,
+                                        *geometry,
+                                        geometry.label(),
+                                    );
+                                }
+                            });
+
+                        if ui.button("Save as default").clicked() {
+                            self.save_layout();
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Driver");
+                        egui::ComboBox::from_id_source("driver_choice")
+                            .selected_text(self.driver.label())
+                            .show_ui(ui, |ui| {
+                                for choice in DriverChoice::all() {
+                                    let selected = self.driver == *choice;
+                                    if ui.selectable_label(selected, choice.label()).clicked() {
+                                        self.set_driver(*choice);
+                                    }
+                                }
+                            });
+                    });
+
+                    ui.separator();
+                    ui.heading("Colors");
+                    self.export_ui(ui);
+
+                    let mut updated = false;
+                    ui.horizontal_top(|ui| {
+                        ui.vertical(|ui| updated |= self.ring_ui(ui));
+
+                        egui::ScrollArea::vertical()
+                            .max_height(240.0)
+                            .show(ui, |ui| {
+                                for (idx, color_value) in self.colors.iter_mut().enumerate() {
+                                    ui.horizontal(|ui| {
+                                        ui.label(format!("Zone {}", idx + 1));
+                                        let response = egui::color_picker::color_edit_button_srgba(
+                                            ui,
+                                            color_value,
+                                            egui::color_picker::Alpha::Opaque,
+                                        );
+                                        updated |= response.changed();
+
+                                        ui.label(rgb_to_hex_string(rgb_from_color32(*color_value)));
+                                    });
+                                }
+                            });
+                    });
+
+                    if updated {
+                        self.dirty = true;
+                        self.lights_enabled = true;
+                        if self.auto_apply {
+                            self.apply();
+                        }
+                    }
+
+                    ui.separator();
+                    ui.heading("Output");
+                    self.color_pipeline_ui(ui);
+
+                    ui.separator();
+                    ui.heading("Effects");
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("effect_kind")
+                            .selected_text(self.effect.kind.label())
+                            .show_ui(ui, |ui| {
+                                for kind in EffectKind::all() {
+                                    ui.selectable_value(&mut self.effect.kind, *kind, kind.label());
+                                }
+                            });
+                        ui.add(egui::Slider::new(&mut self.effect.speed, 0.1..=4.0).text("speed"));
+
+                        if self.effect_clock.is_some() {
+                            if ui.button("Stop").clicked() {
+                                self.stop_effect();
+                            }
+                        } else if ui.button("Play").clicked() {
+                            self.start_effect();
+                            ctx.request_repaint();
+                        }
+                    });
+
+                    ui.separator();
+                    egui::CollapsingHeader::new("Fan curve").show(ui, |ui| self.fan_curve_ui(ui));
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui.button("Apply").on_hover_text("Ctrl+Enter").clicked() {
+                            self.stop_effect();
+                            self.apply();
+                        }
+
+                        let toggle_label = if self.lights_enabled {
+                            "Turn off"
+                        } else {
+                            "Turn on"
+                        };
+                        if ui.button(toggle_label).clicked() {
+                            self.stop_effect();
+                            self.lights_enabled = !self.lights_enabled;
+                            self.apply();
+                        }
+
+                        if ui
+                            .add_enabled(
+                                self.dirty && self.applied_colors.is_some(),
+                                egui::Button::new("Revert"),
+                            )
+                            .on_hover_text("Go back to the colors of the last Apply")
+                            .clicked()
+                        {
+                            self.revert_to_applied();
+                        }
+
+                        ui.separator();
+                        if ui
+                            .add_enabled(self.history.can_undo(), egui::Button::new("Undo"))
+                            .on_hover_text("Ctrl+Z")
+                            .clicked()
+                        {
+                            self.undo();
+                        }
+                        if ui
+                            .add_enabled(self.history.can_redo(), egui::Button::new("Redo"))
+                            .on_hover_text("Ctrl+Shift+Z")
+                            .clicked()
+                        {
+                            self.redo();
+                        }
+
+                        if self.dirty {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(230, 180, 60),
+                                "● Not applied",
+                            )
+                            .on_hover_text("The LEDs don't show the latest changes yet");
+                        }
+                    });
+
+                    if let Some(status) = &self.status {
+                        ui.separator();
+                        let color = match status.kind {
+                            StatusKind::Info => egui::Color32::LIGHT_GRAY,
+                            StatusKind::Success => egui::Color32::from_rgb(0, 200, 83),
+                            StatusKind::Error => egui::Color32::from_rgb(209, 71, 78),
+                        };
+                        ui.colored_label(color, &status.text);
+                    }
+                });
+        });
+
+        // Wait for drags (sliders, color pickers, the ring) to finish so they undo in one step
+        if !ctx.input(|i| i.pointer.any_down()) {
+            self.history.record(&self.colors);
+        }
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([760.0, 640.0])
+            .with_min_inner_size([520.0, 360.0])
+            .with_drag_and_drop(true),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "Framework Fan RGB",
+        native_options,
+        Box::new(|cc| Box::new(FanRgbApp::new(&cc.egui_ctx))),
+    )
+}
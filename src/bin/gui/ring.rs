@@ -0,0 +1,215 @@
+//! Preview of the LEDs in their physical order that can be clicked and dragged to edit zones.
+
+use std::collections::BTreeSet;
+use std::f32::consts::{PI, TAU};
+
+use eframe::egui::{self, Color32, Pos2, Sense, Stroke, Vec2};
+use fwd_rgb::layout::Geometry;
+
+/// LEDs per row when drawing a strip.
+const STRIP_ROW: usize = 16;
+const MAX_LED_RADIUS: f32 = 14.0;
+
+/// Selection and drag state of the preview, kept across frames.
+#[derive(Default)]
+pub struct RingState {
+    pub selected: BTreeSet<usize>,
+    drag: Option<Drag>,
+}
+
+#[derive(Clone, Copy)]
+struct Drag {
+    start: usize,
+    /// Zone the pointer was last over, the pointer may have left the widget since.
+    end: usize,
+    /// Blend the colors of the run on release instead of just selecting it.
+    gradient: bool,
+}
+
+impl RingState {
+    pub fn select_all(&mut self, zones: usize) {
+        self.selected = (0..zones).collect();
+    }
+
+    /// Forget zones that no longer exist.
+    pub fn retain(&mut self, zones: usize) {
+        self.selected.retain(|zone| *zone < zones);
+    }
+}
+
+/// Draw `colors` (or `shown` while an effect plays) as LEDs laid out by `geometry`.
+///
+/// Click to select a zone, Ctrl+click to add or remove zones, drag to select a run of zones and
+/// Shift+drag to blend the colors between the first and last zone of the run. Returns whether
+/// `colors` changed.
+pub fn ring_preview(
+    ui: &mut egui::Ui,
+    colors: &mut [Color32],
+    shown: Option<&[Color32]>,
+    geometry: Geometry,
+    state: &mut RingState,
+    size: f32,
+) -> bool {
+    let zones = colors.len();
+    let height = match geometry {
+        Geometry::Ring => size,
+        Geometry::Strip => {
+            let rows = zones.div_ceil(STRIP_ROW).max(1);
+            (size / STRIP_ROW.min(zones).max(1) as f32).min(MAX_LED_RADIUS * 2.5) * rows as f32
+        }
+    };
+    let (rect, response) = ui.allocate_exact_size(Vec2::new(size, height), Sense::click_and_drag());
+    let (positions, radius) = led_positions(rect, zones, geometry);
+
+    let mut changed = false;
+    let hovered = response
+        .interact_pointer_pos()
+        .or(response.hover_pos())
+        .and_then(|pos| hit_test(&positions, radius, pos));
+    let modifiers = ui.input(|i| i.modifiers);
+
+    if response.drag_started() {
+        state.drag = hovered.map(|zone| Drag {
+            start: zone,
+            end: zone,
+            gradient: modifiers.shift,
+        });
+    }
+    if let Some(drag) = &mut state.drag {
+        if let Some(zone) = hovered {
+            drag.end = zone;
+        }
+        state.selected = run(drag.start, drag.end, zones, geometry)
+            .into_iter()
+            .collect();
+    }
+    if response.drag_stopped()
+        && let Some(drag) = state.drag.take()
+        && drag.gradient
+    {
+        changed |= blend(colors, &run(drag.start, drag.end, zones, geometry));
+    }
+    if response.clicked() {
+        match hovered {
+            Some(zone) if modifiers.command => {
+                if !state.selected.remove(&zone) {
+                    state.selected.insert(zone);
+                }
+            }
+            Some(zone) => state.selected = BTreeSet::from([zone]),
+            None => state.selected.clear(),
+        }
+    }
+
+    let painter = ui.painter_at(rect);
+    if geometry == Geometry::Ring && zones > 0 {
+        // Fan hub, so the ring reads as a fan
+        painter.circle_filled(
+            rect.center(),
+            rect.width() / 2.0 * 0.45,
+            Color32::from_gray(40),
+        );
+    }
+    for (zone, pos) in positions.iter().enumerate() {
+        let color = shown
+            .and_then(|shown| shown.get(zone))
+            .unwrap_or(&colors[zone]);
+        let stroke = if state.selected.contains(&zone) {
+            Stroke::new(2.5, ui.visuals().selection.stroke.color)
+        } else if hovered == Some(zone) {
+            Stroke::new(1.5, Color32::GRAY)
+        } else {
+            Stroke::new(1.0, Color32::from_gray(60))
+        };
+        painter.circle(*pos, radius, *color, stroke);
+    }
+
+    if let Some(zone) = hovered {
+        response.on_hover_text(format!("Zone {}", zone + 1));
+    }
+    changed
+}
+
+/// Center of every LED and their radius.
+fn led_positions(rect: egui::Rect, zones: usize, geometry: Geometry) -> (Vec<Pos2>, f32) {
+    if zones == 0 {
+        return (Vec::new(), 0.0);
+    }
+
+    match geometry {
+        Geometry::Ring => {
+            let spacing = (rect.width().min(rect.height()) / 2.0) * PI / zones as f32;
+            let radius = (spacing * 0.4).min(MAX_LED_RADIUS);
+            let ring = rect.width().min(rect.height()) / 2.0 - radius - 2.0;
+            let positions = (0..zones)
+                .map(|zone| {
+                    // Clockwise starting at the top
+                    let angle = zone as f32 / zones as f32 * TAU - PI / 2.0;
+                    rect.center() + ring * Vec2::angled(angle)
+                })
+                .collect();
+            (positions, radius)
+        }
+        Geometry::Strip => {
+            let columns = STRIP_ROW.min(zones);
+            let cell = (rect.width() / columns as f32).min(MAX_LED_RADIUS * 2.5);
+            let positions = (0..zones)
+                .map(|zone| {
+                    let (row, column) = (zone / STRIP_ROW, zone % STRIP_ROW);
+                    rect.min + cell * Vec2::new(column as f32 + 0.5, row as f32 + 0.5)
+                })
+                .collect();
+            (positions, (cell * 0.4).min(MAX_LED_RADIUS))
+        }
+    }
+}
+
+fn hit_test(positions: &[Pos2], radius: f32, pointer: Pos2) -> Option<usize> {
+    positions
+        .iter()
+        .enumerate()
+        .map(|(zone, pos)| (zone, pos.distance(pointer)))
+        .filter(|(_, distance)| *distance <= radius * 1.5)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(zone, _)| zone)
+}
+
+/// Zones from `start` to `end` in physical order, the short way around a ring.
+fn run(start: usize, end: usize, zones: usize, geometry: Geometry) -> Vec<usize> {
+    match geometry {
+        Geometry::Ring => {
+            let forward = (end + zones - start) % zones;
+            if forward <= zones / 2 {
+                (0..=forward).map(|step| (start + step) % zones).collect()
+            } else {
+                (0..=zones - forward)
+                    .map(|step| (start + zones - step) % zones)
+                    .collect()
+            }
+        }
+        Geometry::Strip if start <= end => (start..=end).collect(),
+        Geometry::Strip => (end..=start).rev().collect(),
+    }
+}
+
+/// Blend the colors of `run` from its first to its last zone.
+fn blend(colors: &mut [Color32], run: &[usize]) -> bool {
+    let (Some(&first), Some(&last)) = (run.first(), run.last()) else {
+        return false;
+    };
+    if run.len() < 3 {
+        return false;
+    }
+
+    let (from, to) = (colors[first], colors[last]);
+    let mix = |a: u8, b: u8, t: f32| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    for (step, zone) in run.iter().enumerate() {
+        let t = step as f32 / (run.len() - 1) as f32;
+        colors[*zone] = Color32::from_rgb(
+            mix(from.r(), to.r(), t),
+            mix(from.g(), to.g(), t),
+            mix(from.b(), to.b(), t),
+        );
+    }
+    true
+}
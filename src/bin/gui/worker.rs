@@ -0,0 +1,362 @@
+//! EC and daemon access on a background thread, so a slow EC never stalls the GUI.
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+use framework_lib::chromium_ec::commands::RgbS;
+use framework_lib::power::{self, ThermalSnapshot};
+
+#[cfg(unix)]
+use fwd_rgb::daemon::{self, Request};
+use fwd_rgb::effects::fan_rpm;
+use fwd_rgb::fan_curve::{FanController, FanCurveSettings};
+use fwd_rgb::state;
+use fwd_rgb::{apply_colors, format_ec_error, open_ec};
+
+use crate::{DriverChoice, rgb_from_color32};
+
+/// Least time between two color writes, effect frames queued in between are merged into the latest.
+const MIN_WRITE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Work for the worker thread.
+pub enum Job {
+    /// Write colors, through the daemon if it is the driver. Colors for the EC must already
+    /// have the color pipeline applied. Replaces an effect frame or quiet write to the same keys
+    /// that hasn't been sent yet.
+    Write {
+        driver: DriverChoice,
+        start_key: u8,
+        colors: Vec<RgbS>,
+        tag: Tag,
+    },
+    /// Send a request to the daemon.
+    #[cfg(unix)]
+    Daemon(Request, Tag),
+    /// Read the temperatures and fan speeds.
+    ReadThermal(DriverChoice),
+    /// Read the fan speed for fan-synced effects.
+    ReadFanRpm(DriverChoice),
+    /// Run the fan curve every `interval` of its settings until stopped.
+    StartFanCurve(DriverChoice, FanCurveSettings),
+    /// Hand the fans back to the EC.
+    StopFanCurve,
+}
+
+/// What a job was for, handed back with its result.
+pub enum Tag {
+    /// Colors from Apply, as shown in the editor.
+    Applied(Vec<egui::Color32>),
+    TurnedOff,
+    /// Starting an effect or one of its frames, failures stop the effect.
+    Effect,
+    /// Only failures matter.
+    Quiet,
+}
+
+/// Results sent back to the GUI.
+pub enum Report {
+    Done(Tag, Result<(), String>),
+    Thermal(Option<ThermalSnapshot>),
+    FanRpm(Option<u16>),
+    /// Whether the fan curve is running, or why it stopped.
+    FanCurve(Result<bool, String>),
+}
+
+pub struct Worker {
+    /// `None` once dropped, which tells the thread to finish.
+    jobs: Option<Sender<Job>>,
+    reports: Receiver<Report>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    /// Start the worker thread, `ctx` is repainted whenever a report arrives.
+    pub fn spawn(ctx: egui::Context) -> Self {
+        let (jobs, job_receiver) = mpsc::channel();
+        let (report_sender, reports) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("ec-worker".to_string())
+            .spawn(move || {
+                let mut state = State {
+                    reports: report_sender,
+                    ctx,
+                    fan_controller: None,
+                    next_fan_update: Instant::now(),
+                    last_write: None,
+                };
+                state.run(&job_receiver);
+            })
+            .expect("failed to start the EC worker thread");
+
+        Self {
+            jobs: Some(jobs),
+            reports,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn send(&self, job: Job) {
+        if let Some(jobs) = &self.jobs {
+            // Only fails if the thread panicked, there is nobody left to do the work then
+            let _ = jobs.send(job);
+        }
+    }
+
+    /// Reports that arrived since the last call.
+    pub fn reports(&self) -> impl Iterator<Item = Report> + '_ {
+        self.reports.try_iter()
+    }
+}
+
+impl Drop for Worker {
+    /// Wait for queued jobs so the last colors are written and the fans are handed back.
+    fn drop(&mut self) {
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct State {
+    reports: Sender<Report>,
+    ctx: egui::Context,
+    /// Running fan curve, dropping it hands the fans back to the EC.
+    fan_controller: Option<FanController>,
+    next_fan_update: Instant,
+    last_write: Option<Instant>,
+}
+
+impl State {
+    fn run(&mut self, jobs: &Receiver<Job>) {
+        loop {
+            // Wake up for the fan curve even if no jobs arrive
+            let job = match &self.fan_controller {
+                Some(_) => {
+                    let timeout = self
+                        .next_fan_update
+                        .saturating_duration_since(Instant::now());
+                    match jobs.recv_timeout(timeout) {
+                        Ok(job) => job,
+                        Err(RecvTimeoutError::Timeout) => {
+                            self.update_fan_curve();
+                            continue;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                None => match jobs.recv() {
+                    Ok(job) => job,
+                    Err(_) => break,
+                },
+            };
+            let mut batch = vec![job];
+            batch.extend(jobs.try_iter());
+
+            let writes = batch.iter().any(|job| matches!(job, Job::Write { .. }));
+            if writes && let Some(last_write) = self.last_write {
+                let since = last_write.elapsed();
+                if since < MIN_WRITE_INTERVAL {
+                    thread::sleep(MIN_WRITE_INTERVAL - since);
+                    batch.extend(jobs.try_iter());
+                }
+            }
+
+            // Skip writes that a later one overwrites anyway
+            let superseded: Vec<bool> = (0..batch.len())
+                .map(|idx| {
+                    batch[idx + 1..]
+                        .iter()
+                        .any(|later| replaces(later, &batch[idx]))
+                })
+                .collect();
+            for (job, superseded) in batch.into_iter().zip(superseded) {
+                if !superseded {
+                    self.handle(job);
+                }
+            }
+            // Jobs may keep coming faster than the fan curve's interval, e.g. effect frames
+            if Instant::now() >= self.next_fan_update {
+                self.update_fan_curve();
+            }
+        }
+    }
+
+    fn update_fan_curve(&mut self) {
+        let Some(controller) = &mut self.fan_controller else {
+            return;
+        };
+        self.next_fan_update = Instant::now() + controller.interval();
+        if let Err(err) = controller.update() {
+            self.fan_controller = None;
+            self.report(Report::FanCurve(Err(format_ec_error(&err).to_string())));
+        }
+    }
+
+    fn report(&self, report: Report) {
+        if self.reports.send(report).is_ok() {
+            self.ctx.request_repaint();
+        }
+    }
+
+    fn handle(&mut self, job: Job) {
+        match job {
+            Job::Write {
+                driver,
+                start_key,
+                colors,
+                tag,
+            } => {
+                let shown = colors.clone();
+                let result = write(driver, start_key, colors);
+                self.last_write = Some(Instant::now());
+                if result.is_ok() {
+                    record(driver, start_key, &tag, shown);
+                }
+                self.report(Report::Done(tag, result));
+            }
+            #[cfg(unix)]
+            Job::Daemon(request, tag) => self.report(Report::Done(tag, daemon_request(&request))),
+            Job::ReadThermal(driver) => {
+                let snapshot = open_ec(driver.to_option())
+                    .ok()
+                    .and_then(|ec| power::get_thermal_snapshot(&ec));
+                self.report(Report::Thermal(snapshot));
+            }
+            Job::ReadFanRpm(driver) => {
+                let rpm = open_ec(driver.to_option()).ok().and_then(|ec| fan_rpm(&ec));
+                self.report(Report::FanRpm(rpm));
+            }
+            Job::StartFanCurve(driver, settings) => {
+                // Stop the old curve first, only one controller may own the fans
+                self.fan_controller = None;
+                let result = open_ec(driver.to_option())
+                    .map_err(|err| format_ec_error(&err).to_string())
+                    .and_then(|ec| FanController::new(ec, settings));
+                let running = result.map(|controller| {
+                    self.fan_controller = Some(controller);
+                    self.next_fan_update = Instant::now();
+                    true
+                });
+                self.report(Report::FanCurve(running));
+            }
+            Job::StopFanCurve => {
+                if self.fan_controller.take().is_some() {
+                    self.report(Report::FanCurve(Ok(false)));
+                }
+            }
+        }
+    }
+}
+
+/// Whether `later` overwrites all keys of `earlier`, which nobody waits for a report of.
+fn replaces(later: &Job, earlier: &Job) -> bool {
+    match (later, earlier) {
+        (
+            Job::Write {
+                driver,
+                start_key,
+                colors,
+                ..
+            },
+            Job::Write {
+                driver: earlier_driver,
+                start_key: earlier_start_key,
+                colors: earlier_colors,
+                tag: Tag::Effect | Tag::Quiet,
+            },
+        ) => {
+            driver == earlier_driver
+                && start_key == earlier_start_key
+                && colors.len() >= earlier_colors.len()
+        }
+        _ => false,
+    }
+}
+
+/// Send colors to the EC, or to the daemon when it is selected.
+fn write(driver: DriverChoice, start_key: u8, colors: Vec<RgbS>) -> Result<(), String> {
+    #[cfg(unix)]
+    if driver == DriverChoice::Daemon {
+        let colors = colors.into_iter().map(fwd_rgb::rgb_to_hex_string).collect();
+        return daemon_request(&Request::SetColors { start_key, colors });
+    }
+
+    apply_colors(start_key, colors, driver.to_option())
+        .map_err(|err| format_ec_error(&err).to_string())
+}
+
+/// Remember what was applied or turned off, so the next start can resume it.
+fn record(driver: DriverChoice, start_key: u8, tag: &Tag, shown: Vec<RgbS>) {
+    let colors = match tag {
+        Tag::Applied(colors) => colors.iter().copied().map(rgb_from_color32).collect(),
+        Tag::TurnedOff => shown,
+        Tag::Effect | Tag::Quiet => return,
+    };
+    // The daemon keeps its own state
+    #[cfg(unix)]
+    if driver == DriverChoice::Daemon {
+        return;
+    }
+    if let Err(err) = state::record(driver.to_option(), start_key, &colors) {
+        log::warn!("{err}");
+    }
+}
+
+#[cfg(unix)]
+fn daemon_request(request: &Request) -> Result<(), String> {
+    daemon::Client::connect(&daemon::socket_path())?
+        .request(request)
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(driver: DriverChoice, start_key: u8, zones: usize, tag: Tag) -> Job {
+        Job::Write {
+            driver,
+            start_key,
+            colors: vec![RgbS::default(); zones],
+            tag,
+        }
+    }
+
+    #[test]
+    fn only_unreported_writes_to_the_same_keys_are_replaced() {
+        let frame = write(DriverChoice::Mock, 0, 8, Tag::Effect);
+        assert!(replaces(
+            &frame,
+            &write(DriverChoice::Mock, 0, 8, Tag::Effect)
+        ));
+        assert!(replaces(
+            &frame,
+            &write(DriverChoice::Mock, 0, 4, Tag::Quiet)
+        ));
+
+        // Apply and Turn off have to report back
+        assert!(!replaces(
+            &frame,
+            &write(DriverChoice::Mock, 0, 8, Tag::TurnedOff)
+        ));
+        let applied = Tag::Applied(vec![egui::Color32::RED; 8]);
+        assert!(!replaces(&frame, &write(DriverChoice::Mock, 0, 8, applied)));
+        // Other keys or another driver
+        assert!(!replaces(
+            &frame,
+            &write(DriverChoice::Mock, 1, 8, Tag::Effect)
+        ));
+        assert!(!replaces(
+            &frame,
+            &write(DriverChoice::Mock, 0, 9, Tag::Effect)
+        ));
+        assert!(!replaces(
+            &frame,
+            &write(DriverChoice::Auto, 0, 8, Tag::Effect)
+        ));
+        assert!(!replaces(&frame, &Job::StopFanCurve));
+    }
+}
@@ -0,0 +1,651 @@
+//! Color parsing, conversion and formatting.
+
+use framework_lib::chromium_ec::commands::RgbS;
+use serde::{Deserialize, Serialize};
+
+/// Lowest and highest color temperature accepted in Kelvin.
+pub const KELVIN_RANGE: std::ops::RangeInclusive<u32> = 1000..=40000;
+
+/// Convert a raw 24-bit RGB value into the EC payload struct.
+pub fn rgb_from_u32(value: u32) -> RgbS {
+    RgbS {
+        r: ((value & 0x00FF_0000) >> 16) as u8,
+        g: ((value & 0x0000_FF00) >> 8) as u8,
+        b: (value & 0x0000_00FF) as u8,
+    }
+}
+
+/// Parse a textual color representation into a 24-bit RGB value accepted by the EC.
+///
+/// Supports:
+/// - `0xRRGGBB`, `#RRGGBB`, `#RGB`, bare hex or decimal literals (0-16777215)
+/// - CSS color names such as `orange` or `rebeccapurple`
+/// - `rgb(255, 128, 0)`, channels may also be percentages
+/// - `hsl(30, 100%, 50%)` and `hsv(30, 100%, 100%)`, hue in degrees
+/// - color temperatures like `3200K` (1000K-40000K)
+pub fn parse_color(input: &str) -> Result<RgbS, String> {
+    let trimmed = input.trim();
+    let invalid = |reason: String| format!("invalid color `{input}`: {reason}");
+    if trimmed.is_empty() {
+        return Err(invalid("empty color".to_string()));
+    }
+
+    if let Some(color) = named_color(trimmed) {
+        return Ok(color);
+    }
+    if let Some((function, args)) = split_function(trimmed) {
+        return parse_function(&function, args).map_err(invalid);
+    }
+    if let Some(kelvin) = trimmed
+        .strip_suffix(['K', 'k'])
+        .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return parse_kelvin(kelvin).map_err(invalid);
+    }
+
+    let (radix, digits) = if let Some(hex) = trimmed.strip_prefix("0x") {
+        (16, hex)
+    } else if let Some(hex) = trimmed.strip_prefix("0X") {
+        (16, hex)
+    } else if let Some(hex) = trimmed.strip_prefix('#') {
+        if hex.len() == 3 && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            // `#RGB` shorthand, each digit is doubled
+            let expanded: String = hex.chars().flat_map(|c| [c, c]).collect();
+            let value =
+                u32::from_str_radix(&expanded, 16).map_err(|err| invalid(err.to_string()))?;
+            return Ok(rgb_from_u32(value));
+        }
+        (16, hex)
+    } else {
+        match trimmed.parse::<u32>() {
+            Ok(value) => return rgb_from_value(trimmed, value),
+            Err(_) => (16, trimmed),
+        }
+    };
+
+    let value = u32::from_str_radix(digits, radix).map_err(|err| {
+        if digits
+            .bytes()
+            .any(|b| b.is_ascii_alphabetic() && !b.is_ascii_hexdigit())
+        {
+            invalid("not a hex value or a known color name".to_string())
+        } else {
+            invalid(err.to_string())
+        }
+    })?;
+
+    rgb_from_value(input, value)
+}
+
+fn rgb_from_value(input: &str, value: u32) -> Result<RgbS, String> {
+    if value > 0x00FF_FFFF {
+        return Err(format!(
+            "color `{input}` exceeds 24-bit RGB range (0x000000..=0xFFFFFF)"
+        ));
+    }
+    Ok(rgb_from_u32(value))
+}
+
+/// Split `name(args)` into the lowercase function name and its arguments.
+fn split_function(input: &str) -> Option<(String, &str)> {
+    let (name, rest) = input.split_once('(')?;
+    let name = name.trim();
+    if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((name.to_ascii_lowercase(), rest))
+}
+
+fn parse_function(function: &str, rest: &str) -> Result<RgbS, String> {
+    let args = rest
+        .strip_suffix(')')
+        .ok_or_else(|| format!("missing `)` after {function}("))?;
+    let args: Vec<&str> = args
+        .split([',', ' ', '\t'])
+        .filter(|arg| !arg.is_empty())
+        .collect();
+    let [first, second, third] = args[..] else {
+        return Err(format!("{function}() takes 3 values, got {}", args.len()));
+    };
+
+    match function {
+        "rgb" => Ok(RgbS {
+            r: rgb_channel(first)?,
+            g: rgb_channel(second)?,
+            b: rgb_channel(third)?,
+        }),
+        "hsl" => Ok(hsl_to_rgb(
+            hue(first)?,
+            percentage(second, "saturation")?,
+            percentage(third, "lightness")?,
+        )),
+        "hsv" => Ok(hsv_to_rgb(
+            hue(first)?,
+            percentage(second, "saturation")?,
+            percentage(third, "value")?,
+        )),
+        _ => Err(format!(
+            "unknown color function `{function}()`, expected rgb(), hsl() or hsv()"
+        )),
+    }
+}
+
+fn number(value: &str, what: &str) -> Result<f32, String> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| format!("{what} `{value}` is not a number"))
+}
+
+/// A channel from 0 to 255, or a percentage.
+fn rgb_channel(value: &str) -> Result<u8, String> {
+    let channel = match value.strip_suffix('%') {
+        Some(percent) => number(percent, "channel")? / 100.0 * 255.0,
+        None => number(value, "channel")?,
+    };
+    if !(0.0..=255.0).contains(&channel) {
+        return Err(format!("channel `{value}` is outside 0-255 (0%-100%)"));
+    }
+    Ok(channel.round() as u8)
+}
+
+/// A hue in degrees (`deg` suffix optional), as a fraction of a turn.
+fn hue(value: &str) -> Result<f32, String> {
+    let degrees = number(value.strip_suffix("deg").unwrap_or(value), "hue")?;
+    Ok(degrees / 360.0)
+}
+
+/// A percentage from 0 to 100 (`%` suffix optional), as a fraction.
+fn percentage(value: &str, what: &str) -> Result<f32, String> {
+    let percent = number(value.strip_suffix('%').unwrap_or(value), what)?;
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(format!("{what} `{value}` is outside 0%-100%"));
+    }
+    Ok(percent / 100.0)
+}
+
+fn parse_kelvin(digits: &str) -> Result<RgbS, String> {
+    match digits.parse::<u32>() {
+        Ok(kelvin) if KELVIN_RANGE.contains(&kelvin) => Ok(kelvin_to_rgb(kelvin)),
+        _ => Err(format!(
+            "color temperature must be between {}K and {}K",
+            KELVIN_RANGE.start(),
+            KELVIN_RANGE.end()
+        )),
+    }
+}
+
+/// Look up a CSS color name, ignoring case.
+pub fn named_color(name: &str) -> Option<RgbS> {
+    NAMED_COLORS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name))
+        .map(|(_, value)| rgb_from_u32(*value))
+}
+
+/// Convert a hue (0.0-1.0, wrapping), saturation and value (both 0.0-1.0) into an RGB color.
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> RgbS {
+    let hue = hue.rem_euclid(1.0) * 6.0;
+    let saturation = saturation.clamp(0.0, 1.0);
+    let value = value.clamp(0.0, 1.0);
+
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    let channel = |c: f32| ((c + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+
+    RgbS {
+        r: channel(r),
+        g: channel(g),
+        b: channel(b),
+    }
+}
+
+/// Convert a hue (0.0-1.0, wrapping), saturation and lightness (both 0.0-1.0) into an RGB color.
+pub fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> RgbS {
+    let saturation = saturation.clamp(0.0, 1.0);
+    let lightness = lightness.clamp(0.0, 1.0);
+
+    let value = lightness + saturation * lightness.min(1.0 - lightness);
+    let hsv_saturation = if value > 0.0 {
+        2.0 * (1.0 - lightness / value)
+    } else {
+        0.0
+    };
+    hsv_to_rgb(hue, hsv_saturation, value)
+}
+
+/// Approximate the color of a black body at `kelvin`, e.g. 2700 for a warm white bulb.
+pub fn kelvin_to_rgb(kelvin: u32) -> RgbS {
+    // Tanner Helland's curve fit of the black body spectrum
+    let temp = kelvin.clamp(*KELVIN_RANGE.start(), *KELVIN_RANGE.end()) as f32 / 100.0;
+    let r = if temp <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (temp - 60.0).powf(-0.133_204_76)
+    };
+    let g = if temp <= 66.0 {
+        99.470_8 * temp.ln() - 161.119_57
+    } else {
+        288.122_17 * (temp - 60.0).powf(-0.075_514_85)
+    };
+    let b = if temp >= 66.0 {
+        255.0
+    } else if temp <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (temp - 10.0).ln() - 305.044_8
+    };
+    let channel = |c: f32| c.round().clamp(0.0, 255.0) as u8;
+
+    RgbS {
+        r: channel(r),
+        g: channel(g),
+        b: channel(b),
+    }
+}
+
+/// Scale all channels of a color by `factor` (0.0-1.0).
+pub fn scale_rgb(color: RgbS, factor: f32) -> RgbS {
+    let factor = factor.clamp(0.0, 1.0);
+    let channel = |c: u8| (c as f32 * factor).round() as u8;
+    RgbS {
+        r: channel(color.r),
+        g: channel(color.g),
+        b: channel(color.b),
+    }
+}
+
+/// `count` colors blending evenly from `from` to `to`, both included.
+pub fn gradient(from: RgbS, to: RgbS, count: usize) -> Vec<RgbS> {
+    let mix = |a: u8, b: u8, t: f32| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    (0..count)
+        .map(|idx| {
+            let t = idx as f32 / count.saturating_sub(1).max(1) as f32;
+            RgbS {
+                r: mix(from.r, to.r, t),
+                g: mix(from.g, to.g, t),
+                b: mix(from.b, to.b, t),
+            }
+        })
+        .collect()
+}
+
+/// Output stage applied to every color right before it is sent to the EC.
+///
+/// Stored in the `[color]` config section. The defaults leave colors untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorPipeline {
+    /// Overall brightness in percent (0-100).
+    pub brightness: u8,
+    /// Exponent mapping perceived to LED intensity, 1.0 is linear and 2.2 suits most LEDs.
+    pub gamma: f32,
+    /// Per-channel gains (0.0-1.0) to correct the tint of the LEDs' white.
+    pub white_balance: WhiteBalance,
+}
+
+impl Default for ColorPipeline {
+    fn default() -> Self {
+        Self {
+            brightness: 100,
+            gamma: 1.0,
+            white_balance: WhiteBalance::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WhiteBalance {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+}
+
+impl Default for WhiteBalance {
+    fn default() -> Self {
+        Self {
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+        }
+    }
+}
+
+impl ColorPipeline {
+    /// Range of accepted gamma values.
+    pub const GAMMA_RANGE: std::ops::RangeInclusive<f32> = 0.2..=4.0;
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.brightness > 100 {
+            return Err(format!(
+                "brightness {}% is outside 0%-100%",
+                self.brightness
+            ));
+        }
+        if !Self::GAMMA_RANGE.contains(&self.gamma) {
+            return Err(format!(
+                "gamma {} is outside {}-{}",
+                self.gamma,
+                Self::GAMMA_RANGE.start(),
+                Self::GAMMA_RANGE.end()
+            ));
+        }
+        let WhiteBalance { red, green, blue } = self.white_balance;
+        if ![red, green, blue]
+            .iter()
+            .all(|gain| (0.0..=1.0).contains(gain))
+        {
+            return Err("white balance gains must be between 0.0 and 1.0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Scale by brightness, apply gamma, then the white balance.
+    pub fn apply(&self, color: RgbS) -> RgbS {
+        let brightness = f32::from(self.brightness.min(100)) / 100.0;
+        let gamma = self
+            .gamma
+            .clamp(*Self::GAMMA_RANGE.start(), *Self::GAMMA_RANGE.end());
+        let channel = |c: u8, gain: f32| {
+            let level = (f32::from(c) / 255.0 * brightness).powf(gamma);
+            (level * gain.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+
+        RgbS {
+            r: channel(color.r, self.white_balance.red),
+            g: channel(color.g, self.white_balance.green),
+            b: channel(color.b, self.white_balance.blue),
+        }
+    }
+
+    pub fn apply_all(&self, colors: Vec<RgbS>) -> Vec<RgbS> {
+        if *self == Self::default() {
+            return colors;
+        }
+        colors.into_iter().map(|color| self.apply(color)).collect()
+    }
+}
+
+/// Convert a `RgbS` color to a hex string (`#RRGGBB`).
+pub fn rgb_to_hex_string(color: RgbS) -> String {
+    format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+}
+
+/// CSS Color Module Level 4 named colors.
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xF0F8FF),
+    ("antiquewhite", 0xFAEBD7),
+    ("aqua", 0x00FFFF),
+    ("aquamarine", 0x7FFFD4),
+    ("azure", 0xF0FFFF),
+    ("beige", 0xF5F5DC),
+    ("bisque", 0xFFE4C4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xFFEBCD),
+    ("blue", 0x0000FF),
+    ("blueviolet", 0x8A2BE2),
+    ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887),
+    ("cadetblue", 0x5F9EA0),
+    ("chartreuse", 0x7FFF00),
+    ("chocolate", 0xD2691E),
+    ("coral", 0xFF7F50),
+    ("cornflowerblue", 0x6495ED),
+    ("cornsilk", 0xFFF8DC),
+    ("crimson", 0xDC143C),
+    ("cyan", 0x00FFFF),
+    ("darkblue", 0x00008B),
+    ("darkcyan", 0x008B8B),
+    ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xA9A9A9),
+    ("darkkhaki", 0xBDB76B),
+    ("darkmagenta", 0x8B008B),
+    ("darkolivegreen", 0x556B2F),
+    ("darkorange", 0xFF8C00),
+    ("darkorchid", 0x9932CC),
+    ("darkred", 0x8B0000),
+    ("darksalmon", 0xE9967A),
+    ("darkseagreen", 0x8FBC8F),
+    ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F),
+    ("darkslategrey", 0x2F4F4F),
+    ("darkturquoise", 0x00CED1),
+    ("darkviolet", 0x9400D3),
+    ("deeppink", 0xFF1493),
+    ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1E90FF),
+    ("firebrick", 0xB22222),
+    ("floralwhite", 0xFFFAF0),
+    ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF),
+    ("gainsboro", 0xDCDCDC),
+    ("ghostwhite", 0xF8F8FF),
+    ("gold", 0xFFD700),
+    ("goldenrod", 0xDAA520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xADFF2F),
+    ("grey", 0x808080),
+    ("honeydew", 0xF0FFF0),
+    ("hotpink", 0xFF69B4),
+    ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082),
+    ("ivory", 0xFFFFF0),
+    ("khaki", 0xF0E68C),
+    ("lavender", 0xE6E6FA),
+    ("lavenderblush", 0xFFF0F5),
+    ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD),
+    ("lightblue", 0xADD8E6),
+    ("lightcoral", 0xF08080),
+    ("lightcyan", 0xE0FFFF),
+    ("lightgoldenrodyellow", 0xFAFAD2),
+    ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90),
+    ("lightgrey", 0xD3D3D3),
+    ("lightpink", 0xFFB6C1),
+    ("lightsalmon", 0xFFA07A),
+    ("lightseagreen", 0x20B2AA),
+    ("lightskyblue", 0x87CEFA),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xB0C4DE),
+    ("lightyellow", 0xFFFFE0),
+    ("lime", 0x00FF00),
+    ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6),
+    ("magenta", 0xFF00FF),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66CDAA),
+    ("mediumblue", 0x0000CD),
+    ("mediumorchid", 0xBA55D3),
+    ("mediumpurple", 0x9370DB),
+    ("mediumseagreen", 0x3CB371),
+    ("mediumslateblue", 0x7B68EE),
+    ("mediumspringgreen", 0x00FA9A),
+    ("mediumturquoise", 0x48D1CC),
+    ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xF5FFFA),
+    ("mistyrose", 0xFFE4E1),
+    ("moccasin", 0xFFE4B5),
+    ("navajowhite", 0xFFDEAD),
+    ("navy", 0x000080),
+    ("oldlace", 0xFDF5E6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500),
+    ("orangered", 0xFF4500),
+    ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA),
+    ("palegreen", 0x98FB98),
+    ("paleturquoise", 0xAFEEEE),
+    ("palevioletred", 0xDB7093),
+    ("papayawhip", 0xFFEFD5),
+    ("peachpuff", 0xFFDAB9),
+    ("peru", 0xCD853F),
+    ("pink", 0xFFC0CB),
+    ("plum", 0xDDA0DD),
+    ("powderblue", 0xB0E0E6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000),
+    ("rosybrown", 0xBC8F8F),
+    ("royalblue", 0x4169E1),
+    ("saddlebrown", 0x8B4513),
+    ("salmon", 0xFA8072),
+    ("sandybrown", 0xF4A460),
+    ("seagreen", 0x2E8B57),
+    ("seashell", 0xFFF5EE),
+    ("sienna", 0xA0522D),
+    ("silver", 0xC0C0C0),
+    ("skyblue", 0x87CEEB),
+    ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xFFFAFA),
+    ("springgreen", 0x00FF7F),
+    ("steelblue", 0x4682B4),
+    ("tan", 0xD2B48C),
+    ("teal", 0x008080),
+    ("thistle", 0xD8BFD8),
+    ("tomato", 0xFF6347),
+    ("turquoise", 0x40E0D0),
+    ("violet", 0xEE82EE),
+    ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF),
+    ("whitesmoke", 0xF5F5F5),
+    ("yellow", 0xFFFF00),
+    ("yellowgreen", 0x9ACD32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(input: &str) -> String {
+        rgb_to_hex_string(parse_color(input).unwrap())
+    }
+
+    #[test]
+    fn parses_hex_and_decimal() {
+        assert_eq!(hex("0xF2662B"), "#F2662B");
+        assert_eq!(hex("#f2662b"), "#F2662B");
+        assert_eq!(hex("F2662B"), "#F2662B");
+        assert_eq!(hex("#F60"), "#FF6600");
+        assert_eq!(hex("255"), "#0000FF");
+        assert!(parse_color("0x1000000").is_err());
+    }
+
+    #[test]
+    fn parses_names_and_functions() {
+        assert_eq!(hex("Orange"), "#FFA500");
+        assert_eq!(hex("rebeccapurple"), "#663399");
+        assert_eq!(hex("rgb(255, 128, 0)"), "#FF8000");
+        assert_eq!(hex("RGB(100% 50% 0%)"), "#FF8000");
+        assert_eq!(hex("hsl(30, 100%, 50%)"), "#FF8000");
+        assert_eq!(hex("hsl(120deg 100% 25%)"), "#008000");
+        assert_eq!(hex("hsv(240, 100%, 100%)"), "#0000FF");
+        assert_eq!(hex("hsl(0, 0%, 100%)"), "#FFFFFF");
+    }
+
+    #[test]
+    fn parses_color_temperatures() {
+        assert_eq!(hex("6600K"), "#FFFFFF");
+        let warm = parse_color("2700k").unwrap();
+        assert_eq!(warm.r, 255);
+        assert!(warm.g > warm.b);
+        assert!(parse_color("500K").is_err());
+    }
+
+    #[test]
+    fn gradient_includes_both_ends() {
+        let colors: Vec<_> = gradient(rgb_from_u32(0xFF0000), rgb_from_u32(0x0000FF), 5)
+            .into_iter()
+            .map(rgb_to_hex_string)
+            .collect();
+        assert_eq!(
+            colors,
+            ["#FF0000", "#BF0040", "#800080", "#4000BF", "#0000FF"]
+        );
+        assert_eq!(
+            gradient(rgb_from_u32(0xFF0000), rgb_from_u32(0), 1).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn pipeline_scales_and_corrects() {
+        let orange = rgb_from_u32(0xFF8000);
+        assert_eq!(
+            rgb_to_hex_string(ColorPipeline::default().apply(orange)),
+            "#FF8000"
+        );
+
+        let mut pipeline = ColorPipeline {
+            brightness: 50,
+            ..Default::default()
+        };
+        assert_eq!(rgb_to_hex_string(pipeline.apply(orange)), "#804000");
+
+        pipeline.brightness = 100;
+        pipeline.gamma = 2.2;
+        assert_eq!(rgb_to_hex_string(pipeline.apply(orange)), "#FF3800");
+
+        pipeline.white_balance.red = 0.8;
+        assert_eq!(rgb_to_hex_string(pipeline.apply(orange)), "#CC3800");
+        assert!(pipeline.validate().is_ok());
+
+        pipeline.gamma = 0.0;
+        assert!(pipeline.validate().is_err());
+    }
+
+    #[test]
+    fn malformed_colors_explain_the_problem() {
+        let err = |input| parse_color(input).unwrap_err();
+        assert_eq!(
+            err("bluish"),
+            "invalid color `bluish`: not a hex value or a known color name"
+        );
+        assert_eq!(
+            err("rgb(1, 2)"),
+            "invalid color `rgb(1, 2)`: rgb() takes 3 values, got 2"
+        );
+        assert_eq!(
+            err("rgb(300, 0, 0)"),
+            "invalid color `rgb(300, 0, 0)`: channel `300` is outside 0-255 (0%-100%)"
+        );
+        assert_eq!(
+            err("hsl(0, 50%, 50"),
+            "invalid color `hsl(0, 50%, 50`: missing `)` after hsl("
+        );
+        assert_eq!(
+            err("hsl(red, 50%, 50%)"),
+            "invalid color `hsl(red, 50%, 50%)`: hue `red` is not a number"
+        );
+        assert_eq!(
+            err("cmyk(0, 0, 0)"),
+            "invalid color `cmyk(0, 0, 0)`: unknown color function `cmyk()`, expected rgb(), hsl() or hsv()"
+        );
+        assert_eq!(
+            err("100000K"),
+            "invalid color `100000K`: color temperature must be between 1000K and 40000K"
+        );
+        assert!(err("").contains("empty color"));
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::ColorPipeline;
+use crate::events::EventSettings;
+use crate::fan_curve::FanCurveSettings;
+use crate::layout::Layout;
+use crate::preset::Preset;
+use crate::profile::Profile;
+use crate::schedule::Rule;
+use crate::thermal::ThermalSettings;
+
+/// Environment variable that overrides the location of the configuration file.
+pub const CONFIG_PATH_ENV: &str = "FWD_RGB_CONFIG";
+
+/// Settings shared by the CLI and GUI, stored as TOML in the user's config directory.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// Profile that was applied or saved most recently.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
+
+    /// LEDs to drive, detected from the platform if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+
+    /// GUI-only preferences.
+    #[serde(default)]
+    pub gui: GuiSettings,
+
+    /// Brightness, gamma and white balance applied to all lighting.
+    #[serde(default)]
+    pub color: ColorPipeline,
+
+    /// Fan curve controller.
+    #[serde(default)]
+    pub fan_curve: FanCurveSettings,
+
+    /// Thermal lighting mode.
+    #[serde(default)]
+    pub thermal: ThermalSettings,
+
+    /// Lighting reactions of `fwd_rgb watch` to lid, chassis and battery events.
+    #[serde(default)]
+    pub events: EventSettings,
+
+    /// Named lighting profiles.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// User presets, listed after the built-in ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub presets: Vec<Preset>,
+
+    /// Time-of-day lighting rules for `fwd_rgb schedule`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<Rule>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuiSettings {
+    #[serde(default)]
+    pub auto_apply: bool,
+}
+
+impl Config {
+    /// The configured layout, or the default for this platform.
+    pub fn layout(&self) -> Layout {
+        self.layout.unwrap_or_else(Layout::detect)
+    }
+
+    /// Location of the configuration file.
+    ///
+    /// `$FWD_RGB_CONFIG` if set, otherwise `fwd_rgb/config.toml` in the platform config directory
+    /// (e.g. `~/.config` on Linux).
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(CONFIG_PATH_ENV) {
+            return Some(PathBuf::from(path));
+        }
+        dirs::config_dir().map(|dir| dir.join("fwd_rgb").join("config.toml"))
+    }
+
+    /// Load the configuration from the default location.
+    pub fn load() -> Result<Self, String> {
+        let path = Self::default_path().ok_or("unable to determine the config directory")?;
+        Self::load_from(&path)
+    }
+
+    /// Load the configuration from `path`. A missing file yields the default configuration.
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(format!("failed to read `{}`: {err}", path.display())),
+        };
+
+        let config: Self = toml::from_str(&contents)
+            .map_err(|err| format!("invalid config `{}`: {err}", path.display()))?;
+        config
+            .validate()
+            .map_err(|err| format!("invalid config `{}`: {err}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Check the layout and the times in seconds, which would otherwise only fail once they are
+    /// used.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(layout) = &self.layout {
+            layout
+                .validate()
+                .map_err(|err| format!("`layout`: {err}"))?;
+        }
+        let times = [
+            ("fan_curve.interval", self.fan_curve.interval),
+            ("thermal.interval", self.thermal.interval),
+            ("events.interval", self.events.interval),
+            ("events.flash_duration", self.events.flash_duration),
+        ];
+        for (name, value) in times {
+            crate::seconds(value).map_err(|err| format!("`{name}`: {err}"))?;
+        }
+        Ok(())
+    }
+
+    /// Save the configuration to the default location.
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::default_path().ok_or("unable to determine the config directory")?;
+        self.save_to(&path)
+    }
+
+    /// Save the configuration to `path`, creating parent directories as needed.
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|err| format!("failed to create `{}`: {err}", parent.display()))?;
+        }
+
+        let contents = toml::to_string_pretty(self)
+            .map_err(|err| format!("failed to encode config: {err}"))?;
+        std::fs::write(path, contents)
+            .map_err(|err| format!("failed to write `{}`: {err}", path.display()))
+    }
+}
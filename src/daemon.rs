@@ -0,0 +1,541 @@
+//! Long-running lighting daemon that owns the EC and is controlled over a Unix socket.
+//!
+//! Clients send one JSON [`Request`] per line and receive one JSON [`Response`] per line, so the
+//! CLI and GUI can control the lights without root privileges while the daemon keeps effects
+//! running and restores the lighting after suspend/resume.
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use framework_lib::chromium_ec::CrosEc;
+use framework_lib::chromium_ec::commands::RgbS;
+use serde::{Deserialize, Serialize};
+
+use crate::effects::{self, Effect, EffectClock, EffectKind};
+use crate::layout::{Geometry, Layout};
+use crate::profile::Profile;
+use crate::{ColorPipeline, parse_color, rgb_to_hex_string};
+
+/// Environment variable that overrides the location of the control socket.
+pub const SOCKET_PATH_ENV: &str = "FWD_RGB_SOCKET";
+pub const DEFAULT_SOCKET_PATH: &str = "/run/fwd_rgb.sock";
+
+const EFFECT_FPS: f32 = 30.0;
+/// How often the daemon wakes up to check for suspend/resume while the lighting is static.
+const IDLE_TICK: Duration = Duration::from_secs(1);
+/// Wall clock advancing this much further than the monotonic clock means the system was suspended.
+const RESUME_THRESHOLD: Duration = Duration::from_secs(5);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Connections served at the same time, further clients are turned away.
+const MAX_CLIENTS: usize = 16;
+
+/// Location of the control socket, `$FWD_RGB_SOCKET` or [`DEFAULT_SOCKET_PATH`].
+pub fn socket_path() -> PathBuf {
+    std::env::var_os(SOCKET_PATH_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_SOCKET_PATH))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Show static colors starting at `start_key`.
+    SetColors {
+        start_key: u8,
+        colors: Vec<String>,
+    },
+    /// Play an effect until something else is requested.
+    SetEffect {
+        start_key: u8,
+        zones: usize,
+        #[serde(default)]
+        geometry: Geometry,
+        kind: EffectKind,
+        color: String,
+        speed: f32,
+    },
+    /// Change the brightness, gamma and white balance of everything shown.
+    SetColorPipeline(ColorPipeline),
+    /// Turn the lights off, keeping the current colors or effect for [`Request::On`].
+    Off,
+    On,
+    /// Query the current [`DaemonState`].
+    State,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum Response {
+    Ok,
+    State(DaemonState),
+    Error { message: String },
+}
+
+/// What the daemon is currently showing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaemonState {
+    pub enabled: bool,
+    pub start_key: u8,
+    /// Static colors, empty while an effect is playing.
+    pub colors: Vec<String>,
+    pub effect: Option<EffectKind>,
+    #[serde(default)]
+    pub color: ColorPipeline,
+}
+
+/// Lighting owned by the daemon.
+#[derive(Debug, Clone)]
+pub enum Lighting {
+    Colors { start_key: u8, colors: Vec<RgbS> },
+    Effect { layout: Layout, effect: Effect },
+}
+
+impl Lighting {
+    pub fn from_profile(profile: &Profile) -> Result<Self, String> {
+        Ok(Lighting::Colors {
+            start_key: profile.start_key,
+            colors: profile.rgb_colors()?,
+        })
+    }
+
+    fn start_key(&self) -> u8 {
+        match self {
+            Lighting::Colors { start_key, .. } => *start_key,
+            Lighting::Effect { layout, .. } => layout.start_key,
+        }
+    }
+
+    fn zones(&self) -> usize {
+        match self {
+            Lighting::Colors { colors, .. } => colors.len(),
+            Lighting::Effect { layout, .. } => layout.zones,
+        }
+    }
+}
+
+struct State {
+    lighting: Option<Lighting>,
+    pipeline: ColorPipeline,
+    enabled: bool,
+    /// Bumped on every change so the render loop knows when to push new colors.
+    generation: u64,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn new(lighting: Option<Lighting>, pipeline: ColorPipeline) -> Self {
+        Self {
+            state: Mutex::new(State {
+                lighting,
+                pipeline,
+                enabled: true,
+                generation: 0,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn handle(&self, request: Request) -> Response {
+        let mut state = self.state.lock().unwrap();
+        match request {
+            Request::SetColors { start_key, colors } => {
+                let layout = Layout {
+                    zones: colors.len(),
+                    start_key,
+                    geometry: Geometry::default(),
+                };
+                if let Err(message) = layout.validate() {
+                    return Response::Error { message };
+                }
+                let colors = match colors.iter().map(|color| parse_color(color)).collect() {
+                    Ok(colors) => colors,
+                    Err(message) => return Response::Error { message },
+                };
+                state.lighting = Some(Lighting::Colors { start_key, colors });
+                state.enabled = true;
+            }
+            Request::SetEffect {
+                start_key,
+                zones,
+                geometry,
+                kind,
+                color,
+                speed,
+            } => {
+                let layout = Layout {
+                    zones,
+                    start_key,
+                    geometry,
+                };
+                if let Err(message) = layout.validate() {
+                    return Response::Error { message };
+                }
+                let color = match parse_color(&color) {
+                    Ok(color) => color,
+                    Err(message) => return Response::Error { message },
+                };
+                let effect = Effect { kind, color, speed };
+                state.lighting = Some(Lighting::Effect { layout, effect });
+                state.enabled = true;
+            }
+            Request::SetColorPipeline(pipeline) => {
+                if let Err(message) = pipeline.validate() {
+                    return Response::Error { message };
+                }
+                state.pipeline = pipeline;
+            }
+            Request::Off => state.enabled = false,
+            Request::On => state.enabled = true,
+            Request::State => {
+                let (colors, effect) = match &state.lighting {
+                    Some(Lighting::Colors { colors, .. }) => (
+                        colors.iter().copied().map(rgb_to_hex_string).collect(),
+                        None,
+                    ),
+                    Some(Lighting::Effect { effect, .. }) => (Vec::new(), Some(effect.kind)),
+                    None => (Vec::new(), None),
+                };
+                return Response::State(DaemonState {
+                    enabled: state.enabled,
+                    start_key: state.lighting.as_ref().map_or(0, Lighting::start_key),
+                    colors,
+                    effect,
+                    color: state.pipeline,
+                });
+            }
+        }
+
+        state.generation += 1;
+        self.changed.notify_all();
+        Response::Ok
+    }
+}
+
+/// Run the daemon on `socket`, starting with `initial` lighting shown through `pipeline`. Only
+/// returns on setup errors.
+///
+/// Only root and members of `group` (if given) may connect.
+pub fn run(
+    ec: CrosEc,
+    socket: &Path,
+    group: Option<&str>,
+    initial: Option<Lighting>,
+    pipeline: ColorPipeline,
+) -> Result<(), String> {
+    let group = group.map(group_id).transpose()?;
+    let listener = bind(socket, group)?;
+    let shared = Shared::new(initial, pipeline);
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| render(&ec, &shared));
+        serve(&listener, &shared);
+    });
+    Ok(())
+}
+
+/// Numeric ID of the group `name`, which may also be a number.
+fn group_id(name: &str) -> Result<u32, String> {
+    if let Ok(gid) = name.parse() {
+        return Ok(gid);
+    }
+    let c_name =
+        std::ffi::CString::new(name).map_err(|_| format!("invalid group name `{name}`"))?;
+    // SAFETY: the entry is copied out before anything else can call getgrnam
+    let group = unsafe { libc::getgrnam(c_name.as_ptr()) };
+    if group.is_null() {
+        return Err(format!("unknown group `{name}`"));
+    }
+    Ok(unsafe { (*group).gr_gid })
+}
+
+fn bind(socket: &Path, group: Option<u32>) -> Result<UnixListener, String> {
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(format!(
+                "another daemon is already listening on `{}`",
+                socket.display()
+            ));
+        }
+        // Left behind by a daemon that didn't shut down cleanly
+        std::fs::remove_file(socket)
+            .map_err(|err| format!("failed to remove `{}`: {err}", socket.display()))?;
+    }
+
+    let listener = UnixListener::bind(socket)
+        .map_err(|err| format!("failed to listen on `{}`: {err}", socket.display()))?;
+    // The daemon runs as root, let the members of the group connect
+    if group.is_some() {
+        std::os::unix::fs::chown(socket, None, group).map_err(|err| {
+            format!(
+                "failed to change the group of `{}`: {err}",
+                socket.display()
+            )
+        })?;
+    }
+    std::fs::set_permissions(socket, std::fs::Permissions::from_mode(0o660))
+        .map_err(|err| format!("failed to set permissions on `{}`: {err}", socket.display()))?;
+    Ok(listener)
+}
+
+/// Serve every client on its own thread, so a slow client can't hold up the others.
+fn serve(listener: &UnixListener, shared: &Shared) {
+    let clients = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    log::warn!("client error: {err}");
+                    continue;
+                }
+            };
+            if clients.fetch_add(1, Ordering::SeqCst) >= MAX_CLIENTS {
+                clients.fetch_sub(1, Ordering::SeqCst);
+                log::warn!("too many clients, dropping a connection");
+                continue;
+            }
+            let clients = &clients;
+            scope.spawn(move || {
+                if let Err(err) = handle_client(stream, shared) {
+                    log::warn!("client error: {err}");
+                }
+                clients.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+}
+
+fn handle_client(stream: UnixStream, shared: &Shared) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => shared.handle(request),
+            Err(err) => Response::Error {
+                message: format!("invalid request: {err}"),
+            },
+        };
+        let mut encoded = serde_json::to_string(&response).map_err(std::io::Error::other)?;
+        encoded.push('\n');
+        writer.write_all(encoded.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Push the lighting to the EC whenever it changes, animate effects, and restore the lighting
+/// after the system resumes from suspend.
+fn render(ec: &CrosEc, shared: &Shared) {
+    let frame_time = Duration::from_secs_f32(1.0 / EFFECT_FPS);
+    let mut applied = None;
+    let mut clock = EffectClock::new();
+    let mut last_tick = (Instant::now(), SystemTime::now());
+
+    loop {
+        let monotonic = last_tick.0.elapsed();
+        let wall = last_tick.1.elapsed().unwrap_or_default();
+        if wall > monotonic + RESUME_THRESHOLD {
+            log::info!("system resumed, restoring lighting");
+            applied = None;
+        }
+        last_tick = (Instant::now(), SystemTime::now());
+
+        // Copied out so clients aren't blocked while the EC is written
+        let (lighting, pipeline, enabled, generation) = {
+            let state = shared.state.lock().unwrap();
+            (
+                state.lighting.clone(),
+                state.pipeline,
+                state.enabled,
+                state.generation,
+            )
+        };
+
+        let timeout = match (&lighting, enabled) {
+            (Some(Lighting::Effect { .. }), true) if applied != Some(generation) => {
+                clock = EffectClock::new();
+                applied = Some(generation);
+                Duration::ZERO
+            }
+            (Some(Lighting::Effect { layout, effect }), true) => {
+                let t = clock.tick(effect, || effects::fan_rpm(ec));
+                let frame = pipeline.apply_all(effect.frame_for(t, layout));
+                if let Err(err) = ec.rgbkbd_set_color(layout.start_key, frame) {
+                    log::warn!("failed to update lighting: {err:?}");
+                }
+                frame_time
+            }
+            _ if applied == Some(generation) => IDLE_TICK,
+            (Some(lighting), enabled) => {
+                let colors = match lighting {
+                    Lighting::Colors { colors, .. } if enabled => {
+                        pipeline.apply_all(colors.clone())
+                    }
+                    _ => vec![RgbS::default(); lighting.zones()],
+                };
+                match ec.rgbkbd_set_color(lighting.start_key(), colors) {
+                    Ok(()) => applied = Some(generation),
+                    // Retried on the next tick, the EC may still be waking up
+                    Err(err) => log::warn!("failed to update lighting: {err:?}"),
+                }
+                IDLE_TICK
+            }
+            (None, _) => IDLE_TICK,
+        };
+
+        if !timeout.is_zero() {
+            let state = shared.state.lock().unwrap();
+            // A change made while the EC was written has already been notified
+            if state.generation == generation {
+                drop(shared.changed.wait_timeout(state, timeout).unwrap());
+            }
+        }
+    }
+}
+
+/// Connection to a running daemon.
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    pub fn connect(socket: &Path) -> Result<Self, String> {
+        let stream = UnixStream::connect(socket).map_err(|err| {
+            format!(
+                "unable to reach the daemon at `{}`: {err}",
+                socket.display()
+            )
+        })?;
+        let writer = stream
+            .try_clone()
+            .map_err(|err| format!("unable to reach the daemon: {err}"))?;
+        Ok(Self {
+            reader: BufReader::new(stream),
+            writer,
+        })
+    }
+
+    /// Send a request, returning the daemon state for [`Request::State`].
+    pub fn request(&mut self, request: &Request) -> Result<Option<DaemonState>, String> {
+        let mut encoded = serde_json::to_string(request)
+            .map_err(|err| format!("failed to encode request: {err}"))?;
+        encoded.push('\n');
+        self.writer
+            .write_all(encoded.as_bytes())
+            .map_err(|err| format!("failed to send request to the daemon: {err}"))?;
+
+        let mut line = String::new();
+        self.reader
+            .read_line(&mut line)
+            .map_err(|err| format!("failed to read response from the daemon: {err}"))?;
+        if line.is_empty() {
+            return Err("daemon closed the connection".to_string());
+        }
+
+        match serde_json::from_str(&line) {
+            Ok(Response::Ok) => Ok(None),
+            Ok(Response::State(state)) => Ok(Some(state)),
+            Ok(Response::Error { message }) => Err(message),
+            Err(err) => Err(format!("invalid response from the daemon: {err}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wire_format() {
+        assert_eq!(
+            serde_json::to_string(&Request::SetColors {
+                start_key: 2,
+                colors: vec!["#FF0000".to_string()],
+            })
+            .unwrap(),
+            r##"{"command":"set-colors","start_key":2,"colors":["#FF0000"]}"##
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command":"off"}"#).unwrap(),
+            Request::Off
+        );
+        assert_eq!(
+            serde_json::to_string(&Response::Error {
+                message: "nope".to_string()
+            })
+            .unwrap(),
+            r#"{"status":"error","message":"nope"}"#
+        );
+    }
+
+    #[test]
+    fn requests_over_socket() {
+        let socket = std::env::temp_dir().join(format!("fwd_rgb-test-{}.sock", std::process::id()));
+        let listener = bind(&socket, None).unwrap();
+        let shared: &'static Shared =
+            Box::leak(Box::new(Shared::new(None, ColorPipeline::default())));
+        std::thread::spawn(move || serve(&listener, shared));
+
+        // An idle connection doesn't hold up others
+        let _idle = Client::connect(&socket).unwrap();
+        let mut client = Client::connect(&socket).unwrap();
+        assert!(bind(&socket, None).is_err(), "socket is in use");
+        let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+
+        let set = Request::SetColors {
+            start_key: 4,
+            colors: vec!["0x00FF00".to_string(), "#0000FF".to_string()],
+        };
+        assert_eq!(client.request(&set), Ok(None));
+        assert_eq!(client.request(&Request::Off), Ok(None));
+        assert_eq!(
+            client.request(&Request::State),
+            Ok(Some(DaemonState {
+                enabled: false,
+                start_key: 4,
+                colors: vec!["#00FF00".to_string(), "#0000FF".to_string()],
+                effect: None,
+                color: ColorPipeline::default(),
+            }))
+        );
+
+        let dim = ColorPipeline {
+            brightness: 40,
+            ..Default::default()
+        };
+        assert_eq!(client.request(&Request::SetColorPipeline(dim)), Ok(None));
+        let state = client.request(&Request::State).unwrap().unwrap();
+        assert_eq!(state.color.brightness, 40);
+
+        let invalid = Request::SetColors {
+            start_key: 0,
+            colors: vec!["nope".to_string()],
+        };
+        assert!(client.request(&invalid).is_err());
+        let too_many = Request::SetColors {
+            start_key: 200,
+            colors: vec!["#FFFFFF".to_string(); 100],
+        };
+        assert!(client.request(&too_many).is_err());
+        // The failed request didn't change anything
+        assert_eq!(shared.state.lock().unwrap().generation, 3);
+
+        let _ = std::fs::remove_file(&socket);
+    }
+}
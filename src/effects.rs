@@ -0,0 +1,370 @@
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use framework_lib::chromium_ec::commands::RgbS;
+use framework_lib::chromium_ec::{CrosEc, EcError};
+use framework_lib::power;
+use serde::{Deserialize, Serialize};
+
+use crate::layout::{Geometry, Layout};
+use crate::{ColorPipeline, hsv_to_rgb, scale_rgb};
+
+/// Animated lighting effects that can be played on the fan ring.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EffectKind {
+    /// Fade the base color in and out.
+    Breathing,
+    /// Rotate a full rainbow around the zones.
+    Rainbow,
+    /// Chase a single bright head with a fading tail around the zones.
+    Comet,
+    /// Cycle all zones through the hue wheel together.
+    ColorCycle,
+    /// Flash the base color.
+    Strobe,
+    /// Flicker in warm fire colors.
+    Fire,
+    /// Spin two blades of the base color around the ring at a pace that follows the fan RPM.
+    FanSync,
+}
+
+impl EffectKind {
+    pub fn all() -> &'static [EffectKind] {
+        &[
+            EffectKind::Breathing,
+            EffectKind::Rainbow,
+            EffectKind::Comet,
+            EffectKind::ColorCycle,
+            EffectKind::Strobe,
+            EffectKind::Fire,
+            EffectKind::FanSync,
+        ]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EffectKind::Breathing => "Breathing",
+            EffectKind::Rainbow => "Rainbow",
+            EffectKind::Comet => "Comet",
+            EffectKind::ColorCycle => "Color cycle",
+            EffectKind::Strobe => "Strobe",
+            EffectKind::Fire => "Fire",
+            EffectKind::FanSync => "Fan sync",
+        }
+    }
+
+    /// Seconds per animation cycle at speed 1.0.
+    fn period(self) -> f32 {
+        match self {
+            EffectKind::Breathing => 4.0,
+            EffectKind::Rainbow => 3.0,
+            EffectKind::Comet => 1.5,
+            EffectKind::ColorCycle => 6.0,
+            EffectKind::Strobe => 0.5,
+            EffectKind::Fire => 0.125,
+            EffectKind::FanSync => 1.0,
+        }
+    }
+}
+
+/// Fan speed at which [`EffectKind::FanSync`] plays at its default pace.
+const FAN_SYNC_REFERENCE_RPM: f32 = 2000.0;
+const FAN_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// An effect together with its parameters.
+#[derive(Clone, Copy, Debug)]
+pub struct Effect {
+    pub kind: EffectKind,
+    /// Base color for breathing, comet and strobe.
+    pub color: RgbS,
+    /// Speed multiplier, 1.0 plays the effect at its default pace.
+    pub speed: f32,
+}
+
+impl Effect {
+    pub fn new(kind: EffectKind) -> Self {
+        Self {
+            kind,
+            color: RgbS {
+                r: 0xFF,
+                g: 0xFF,
+                b: 0xFF,
+            },
+            speed: 1.0,
+        }
+    }
+
+    /// Render the frame shown `t` seconds into the effect for a ring of `zones` zones.
+    pub fn frame(&self, t: f32, zones: usize) -> Vec<RgbS> {
+        self.render(t, zones, Geometry::Ring)
+    }
+
+    /// Render the frame shown `t` seconds into the effect for `layout`.
+    pub fn frame_for(&self, t: f32, layout: &Layout) -> Vec<RgbS> {
+        self.render(t, layout.zones, layout.geometry)
+    }
+
+    fn render(&self, t: f32, zones: usize, geometry: Geometry) -> Vec<RgbS> {
+        let phase = t * self.speed.max(0.0) / self.kind.period();
+
+        match self.kind {
+            EffectKind::Breathing => {
+                let level = 0.5 - 0.5 * (2.0 * PI * phase).cos();
+                vec![scale_rgb(self.color, level); zones]
+            }
+            EffectKind::Rainbow => (0..zones)
+                .map(|zone| hsv_to_rgb(zone as f32 / zones as f32 + phase, 1.0, 1.0))
+                .collect(),
+            EffectKind::Comet => {
+                let tail = (zones as f32 / 2.0).max(1.0);
+                let (head, length) = match geometry {
+                    Geometry::Ring => (phase.rem_euclid(1.0) * zones as f32, zones as f32),
+                    // Run off the end of the strip before starting over
+                    Geometry::Strip => (phase.rem_euclid(1.0) * (zones as f32 + tail), f32::MAX),
+                };
+                (0..zones)
+                    .map(|zone| {
+                        // Distance behind the head, wrapping around a ring
+                        let behind = (head - zone as f32).rem_euclid(length);
+                        scale_rgb(self.color, 1.0 - behind / tail)
+                    })
+                    .collect()
+            }
+            EffectKind::ColorCycle => vec![hsv_to_rgb(phase, 1.0, 1.0); zones],
+            EffectKind::Strobe => {
+                let on = phase.rem_euclid(1.0) < 0.15;
+                vec![scale_rgb(self.color, if on { 1.0 } else { 0.0 }); zones]
+            }
+            EffectKind::FanSync => (0..zones)
+                .map(|zone| {
+                    let angle = zone as f32 / zones as f32 - phase;
+                    let level = 0.5 + 0.5 * (2.0 * PI * 2.0 * angle).cos();
+                    scale_rgb(self.color, level * level)
+                })
+                .collect(),
+            EffectKind::Fire => {
+                let step = phase.floor();
+                let blend = phase - step;
+                (0..zones)
+                    .map(|zone| {
+                        let from = noise(zone as u64, step as u64);
+                        let to = noise(zone as u64, step as u64 + 1);
+                        fire_color(from + (to - from) * blend)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Playback position of an effect.
+///
+/// Runs in real time, except for [`EffectKind::FanSync`] which runs faster or slower with the fan.
+pub struct EffectClock {
+    time: f32,
+    last_tick: Instant,
+    fan_rpm: Option<u16>,
+    fan_polled: Option<Instant>,
+}
+
+impl Default for EffectClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EffectClock {
+    pub fn new() -> Self {
+        Self {
+            time: 0.0,
+            last_tick: Instant::now(),
+            fan_rpm: None,
+            fan_polled: None,
+        }
+    }
+
+    /// Advance the clock to now and return the effect time in seconds.
+    ///
+    /// `read_fan_rpm` is only called for fan-synced effects, at most every 500ms.
+    pub fn tick(&mut self, effect: &Effect, read_fan_rpm: impl FnOnce() -> Option<u16>) -> f32 {
+        let now = Instant::now();
+        if effect.kind == EffectKind::FanSync
+            && self
+                .fan_polled
+                .is_none_or(|polled| now - polled >= FAN_POLL_INTERVAL)
+        {
+            self.fan_rpm = read_fan_rpm();
+            self.fan_polled = Some(now);
+        }
+
+        self.time += (now - self.last_tick).as_secs_f32() * self.rate(effect);
+        self.last_tick = now;
+        self.time
+    }
+
+    fn rate(&self, effect: &Effect) -> f32 {
+        match (effect.kind, self.fan_rpm) {
+            (EffectKind::FanSync, Some(rpm)) => rpm as f32 / FAN_SYNC_REFERENCE_RPM,
+            // Without a fan reading, play at the default pace
+            _ => 1.0,
+        }
+    }
+}
+
+/// Speed of the fastest fan, `None` if no fan reports its speed.
+pub fn fan_rpm(ec: &CrosEc) -> Option<u16> {
+    power::get_fan_speeds(ec)?
+        .iter()
+        .filter_map(|fan| fan.rpm())
+        .max()
+}
+
+/// Deterministic pseudo-random value in `0.0..1.0` for a zone at a time step.
+fn noise(zone: u64, step: u64) -> f32 {
+    // splitmix64 finalizer
+    let mut x = zone
+        .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        .wrapping_add(step.wrapping_mul(0xBF58_476D_1CE4_E5B9));
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    (x >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Map a heat value (0.0-1.0) to a color from deep red to yellow.
+fn fire_color(heat: f32) -> RgbS {
+    let heat = heat.clamp(0.0, 1.0);
+    RgbS {
+        r: (155.0 + 100.0 * heat) as u8,
+        g: (20.0 + 150.0 * heat * heat) as u8,
+        b: (10.0 * heat * heat * heat) as u8,
+    }
+}
+
+/// Stream an effect to the EC at `fps` frames per second.
+///
+/// Runs until `duration` has elapsed (if given) or `running` is cleared.
+pub fn play(
+    ec: &CrosEc,
+    layout: &Layout,
+    effect: &Effect,
+    pipeline: &ColorPipeline,
+    fps: u32,
+    duration: Option<Duration>,
+    running: &AtomicBool,
+) -> Result<(), EcError> {
+    let frame_time = Duration::from_secs_f32(1.0 / fps.max(1) as f32);
+    let start = Instant::now();
+    let mut next_frame = start;
+    let mut clock = EffectClock::new();
+
+    while running.load(Ordering::Relaxed) {
+        if duration.is_some_and(|duration| start.elapsed() >= duration) {
+            break;
+        }
+
+        let t = clock.tick(effect, || fan_rpm(ec));
+        let frame = pipeline.apply_all(effect.frame_for(t, layout));
+        ec.rgbkbd_set_color(layout.start_key, frame)?;
+
+        next_frame += frame_time;
+        let now = Instant::now();
+        if next_frame > now {
+            std::thread::sleep(next_frame - now);
+        } else {
+            // Fell behind, don't try to catch up on missed frames
+            next_frame = now;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rgb_to_hex_string;
+
+    fn hex(frame: &[RgbS]) -> Vec<String> {
+        frame.iter().copied().map(rgb_to_hex_string).collect()
+    }
+
+    #[test]
+    fn frames_have_one_color_per_zone() {
+        for kind in EffectKind::all() {
+            let effect = Effect::new(*kind);
+            assert_eq!(effect.frame(0.7, 8).len(), 8, "{kind:?}");
+            assert_eq!(effect.frame(0.7, 0).len(), 0, "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn frames_are_deterministic() {
+        for kind in EffectKind::all() {
+            let effect = Effect::new(*kind);
+            assert_eq!(hex(&effect.frame(1.3, 12)), hex(&effect.frame(1.3, 12)));
+        }
+    }
+
+    #[test]
+    fn breathing_starts_dark_and_peaks_halfway() {
+        let effect = Effect::new(EffectKind::Breathing);
+        assert_eq!(hex(&effect.frame(0.0, 2)), ["#000000", "#000000"]);
+        assert_eq!(hex(&effect.frame(2.0, 2)), ["#FFFFFF", "#FFFFFF"]);
+    }
+
+    #[test]
+    fn rainbow_rotates_by_one_zone() {
+        let effect = Effect::new(EffectKind::Rainbow);
+        let first = effect.frame(0.0, 6);
+        // One zone further after 1/6 of the 3 second period
+        let later = effect.frame(0.5, 6);
+        assert_eq!(hex(&later[..5]), hex(&first[1..]));
+        assert_eq!(rgb_to_hex_string(first[0]), "#FF0000");
+    }
+
+    #[test]
+    fn comet_runs_off_a_strip() {
+        let effect = Effect::new(EffectKind::Comet);
+        let strip = Layout {
+            zones: 8,
+            start_key: 0,
+            geometry: Geometry::Strip,
+        };
+        // Head at the start, nothing wraps around to the far end
+        let frame = effect.frame_for(0.0, &strip);
+        assert_eq!(rgb_to_hex_string(frame[0]), "#FFFFFF");
+        assert_eq!(rgb_to_hex_string(frame[7]), "#000000");
+        // Head past the end with only the tail left on the strip
+        let frame = effect.frame_for(1.125, &strip);
+        assert!(frame.iter().all(|color| color.r < 0xFF));
+        assert!(frame[7].r > frame[6].r);
+    }
+
+    #[test]
+    fn fan_sync_follows_fan_rpm() {
+        let effect = Effect::new(EffectKind::FanSync);
+        let mut clock = EffectClock::new();
+        clock.tick(&effect, || Some(4000));
+        assert_eq!(clock.rate(&effect), 2.0);
+
+        clock.fan_rpm = Some(0);
+        assert_eq!(clock.rate(&effect), 0.0);
+        clock.fan_rpm = None;
+        assert_eq!(clock.rate(&effect), 1.0);
+        // Other effects ignore the fan
+        clock.fan_rpm = Some(4000);
+        assert_eq!(clock.rate(&Effect::new(EffectKind::Rainbow)), 1.0);
+    }
+
+    #[test]
+    fn comet_head_is_brightest() {
+        let effect = Effect::new(EffectKind::Comet);
+        let frame = effect.frame(0.0, 8);
+        assert_eq!(rgb_to_hex_string(frame[0]), "#FFFFFF");
+        assert_eq!(rgb_to_hex_string(frame[1]), "#000000");
+        assert!(frame[7].r > frame[6].r);
+    }
+}
@@ -0,0 +1,136 @@
+//! Errors as reported to users and scripts, and the exit codes of the CLI.
+
+use std::fmt;
+
+use framework_lib::chromium_ec::EcError;
+use serde::Serialize;
+
+/// Exit codes of the `fwd_rgb` CLI. Scripts can rely on these staying the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitCode {
+    Success = 0,
+    /// Invalid arguments, colors, profiles or configuration. Also used by clap for usage errors.
+    Usage = 2,
+    /// The EC or the daemon couldn't be reached, e.g. missing privileges or an unavailable driver.
+    Device = 4,
+    /// The EC or the daemon was reached but rejected the request.
+    Rejected = 5,
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// What went wrong, in broad terms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Bad input from the user, see [`ExitCode::Usage`].
+    Invalid,
+    /// [`EcError::DeviceError`], the EC couldn't be talked to.
+    Device,
+    /// [`EcError::Response`], the EC answered with an error status.
+    Response,
+    /// [`EcError::UnknownResponseCode`].
+    UnknownResponseCode,
+    /// The daemon isn't running or its socket can't be opened.
+    DaemonUnreachable,
+    /// The daemon refused the request.
+    Daemon,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> ExitCode {
+        match self {
+            ErrorKind::Invalid => ExitCode::Usage,
+            ErrorKind::Device | ErrorKind::DaemonUnreachable => ExitCode::Device,
+            ErrorKind::Response | ErrorKind::UnknownResponseCode | ErrorKind::Daemon => {
+                ExitCode::Rejected
+            }
+        }
+    }
+}
+
+/// An error with a user-friendly message, serializable for `--output json`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Error {
+    pub kind: ErrorKind,
+    /// Name of the [`EcResponseStatus`](framework_lib::chromium_ec::EcResponseStatus) for
+    /// [`ErrorKind::Response`], e.g. `AccessDenied`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Numeric EC response code for [`ErrorKind::Response`] and
+    /// [`ErrorKind::UnknownResponseCode`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<u32>,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            status: None,
+            code: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Invalid, message)
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        self.kind.exit_code()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<&EcError> for Error {
+    /// Explain the error, including privilege guidance.
+    fn from(err: &EcError) -> Self {
+        match err {
+            EcError::DeviceError(message) if message.contains("Not a Framework Laptop") => {
+                Error::new(
+                    ErrorKind::Device,
+                    "EC access denied: SMBIOS check failed. Run this tool with administrative \
+privileges (sudo/Administrator) on a Framework system so the EC can be reached.",
+                )
+            }
+            EcError::DeviceError(message) => {
+                Error::new(ErrorKind::Device, format!("EC device error: {message}"))
+            }
+            EcError::Response(status) => Error {
+                status: Some(format!("{status:?}")),
+                code: Some(*status as u32),
+                ..Error::new(
+                    ErrorKind::Response,
+                    format!("EC responded with status {status:?}."),
+                )
+            },
+            EcError::UnknownResponseCode(code) => Error {
+                code: Some(*code),
+                ..Error::new(
+                    ErrorKind::UnknownResponseCode,
+                    format!("EC returned unknown response code 0x{code:X}."),
+                )
+            },
+        }
+    }
+}
+
+impl From<EcError> for Error {
+    fn from(err: EcError) -> Self {
+        Error::from(&err)
+    }
+}
@@ -0,0 +1,330 @@
+//! Lighting that reacts to the EC: off while the lid is closed or the chassis is open, flashing
+//! when the battery becomes critical.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use framework_lib::chromium_ec::commands::RgbS;
+use framework_lib::chromium_ec::events::{EcEvent, EcStatus, EventMonitor, HostEvent};
+use framework_lib::chromium_ec::{CrosEc, EcError};
+use serde::{Deserialize, Serialize};
+
+use crate::state::Frame;
+use crate::{ColorPipeline, parse_color};
+
+/// How long the flash color is shown, followed by as long dark.
+const FLASH_HALF_PERIOD: Duration = Duration::from_millis(250);
+
+/// Settings for the event reactions, stored in the `[events]` config section.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventSettings {
+    /// Seconds between polls of the EC.
+    pub interval: f32,
+    /// Turn the lights off while the lid is closed.
+    pub lid_off: bool,
+    /// Turn the lights off while the chassis is open.
+    pub chassis_off: bool,
+    /// Flash the lights when the battery becomes critical.
+    pub battery_flash: bool,
+    pub flash_color: String,
+    /// Seconds to flash for.
+    pub flash_duration: f32,
+}
+
+impl Default for EventSettings {
+    fn default() -> Self {
+        Self {
+            interval: 0.5,
+            lid_off: true,
+            chassis_off: true,
+            battery_flash: true,
+            flash_color: "#FF0000".to_string(),
+            flash_duration: 5.0,
+        }
+    }
+}
+
+/// What the lights show at a point in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Output {
+    /// The colors set by the user.
+    Normal,
+    Off,
+    /// The flash color, alternating with [`Output::Off`].
+    Flash,
+}
+
+/// Decides what the lights show from the events seen so far.
+#[derive(Debug, Clone)]
+pub struct Reactions {
+    settings: EventSettings,
+    lid_closed: bool,
+    chassis_open: bool,
+    /// Start and end of the current battery flash.
+    flash: Option<(Instant, Instant)>,
+}
+
+impl Reactions {
+    pub fn new(settings: EventSettings) -> Self {
+        Self {
+            settings,
+            lid_closed: false,
+            chassis_open: false,
+            flash: None,
+        }
+    }
+
+    /// Take over the state of the EC when watching starts.
+    pub fn start(&mut self, status: &EcStatus, now: Instant) {
+        self.lid_closed = !status.lid_open;
+        self.chassis_open = status.chassis_open == Some(true);
+        if status.battery_critical {
+            self.start_flash(now);
+        }
+    }
+
+    pub fn handle(&mut self, event: &EcEvent, now: Instant) {
+        match *event {
+            EcEvent::Lid { open } => self.lid_closed = !open,
+            EcEvent::Chassis { open } => self.chassis_open = open,
+            EcEvent::BatteryCritical { critical: true }
+            | EcEvent::Host(HostEvent::BatteryCritical) => self.start_flash(now),
+            _ => {}
+        }
+    }
+
+    fn start_flash(&mut self, now: Instant) {
+        if self.settings.battery_flash {
+            // Checked when the config is loaded, don't flash at all if invalid anyway
+            let duration = crate::seconds(self.settings.flash_duration).unwrap_or_default();
+            self.flash = Some((now, now + duration));
+        }
+    }
+
+    /// Whether the lights are flashing and need updates more often than `interval`.
+    pub fn flashing(&self, now: Instant) -> bool {
+        self.flash.is_some_and(|(_, end)| now < end)
+    }
+
+    pub fn output(&self, now: Instant) -> Output {
+        if (self.lid_closed && self.settings.lid_off)
+            || (self.chassis_open && self.settings.chassis_off)
+        {
+            return Output::Off;
+        }
+        match self.flash {
+            Some((start, end)) if now < end => {
+                let half_periods = (now - start).as_millis() / FLASH_HALF_PERIOD.as_millis();
+                if half_periods.is_multiple_of(2) {
+                    Output::Flash
+                } else {
+                    Output::Off
+                }
+            }
+            _ => Output::Normal,
+        }
+    }
+}
+
+/// Colors written for each [`Output`], with the color pipeline already applied.
+#[derive(Debug, Clone)]
+pub struct Lights {
+    pub start_key: u8,
+    normal: Vec<RgbS>,
+    flash: RgbS,
+}
+
+impl Lights {
+    /// Lights returning to `frame` when no reaction is active.
+    pub fn new(
+        frame: &Frame,
+        settings: &EventSettings,
+        pipeline: &ColorPipeline,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            start_key: frame.start_key,
+            normal: pipeline.apply_all(frame.rgb_colors()?),
+            flash: pipeline.apply(parse_color(&settings.flash_color)?),
+        })
+    }
+
+    pub fn colors(&self, output: Output) -> Vec<RgbS> {
+        match output {
+            Output::Normal => self.normal.clone(),
+            Output::Off => vec![RgbS::default(); self.normal.len()],
+            Output::Flash => vec![self.flash; self.normal.len()],
+        }
+    }
+}
+
+/// Name of `event` in output, e.g. `lid-closed`. Host events are all named `host-event`.
+pub fn event_name(event: &EcEvent) -> &'static str {
+    let pick = |on: bool, yes, no| if on { yes } else { no };
+    match *event {
+        EcEvent::Lid { open } => pick(open, "lid-open", "lid-closed"),
+        EcEvent::PowerButton { pressed } => {
+            pick(pressed, "power-button-pressed", "power-button-released")
+        }
+        EcEvent::Ac { present } => pick(present, "ac-connected", "ac-disconnected"),
+        EcEvent::BatteryCritical { critical } => {
+            pick(critical, "battery-critical", "battery-recovered")
+        }
+        EcEvent::Chassis { open } => pick(open, "chassis-open", "chassis-closed"),
+        EcEvent::Host(_) => "host-event",
+    }
+}
+
+/// Poll the EC every `interval` of `settings` and pass each change to `on_event`. With `lights`,
+/// react to the changes as configured.
+///
+/// Runs until `duration` has elapsed (if given) or `running` is cleared. The normal colors are
+/// restored before returning.
+pub fn run(
+    ec: &CrosEc,
+    settings: &EventSettings,
+    lights: Option<&Lights>,
+    duration: Option<Duration>,
+    running: &AtomicBool,
+    mut on_event: impl FnMut(&EcEvent),
+) -> Result<(), EcError> {
+    let start = Instant::now();
+    let interval = Duration::from_secs_f32(settings.interval.max(0.1));
+
+    let mut monitor = EventMonitor::new(ec);
+    monitor.poll();
+    let status = monitor
+        .status()
+        .ok_or_else(|| EcError::DeviceError("failed to read the EC memory map".to_string()))?;
+    let mut reactions = Reactions::new(settings.clone());
+    reactions.start(&status, start);
+
+    // The normal colors are already showing
+    let mut shown = Output::Normal;
+    let mut result = Ok(());
+    while running.load(Ordering::Relaxed) {
+        if duration.is_some_and(|duration| start.elapsed() >= duration) {
+            break;
+        }
+
+        let now = Instant::now();
+        for event in monitor.poll() {
+            log::info!("{event:?}");
+            on_event(&event);
+            reactions.handle(&event, now);
+        }
+
+        if let Some(lights) = lights {
+            let output = reactions.output(now);
+            if output != shown {
+                log::debug!("Lights {output:?}");
+                result = ec.rgbkbd_set_color(lights.start_key, lights.colors(output));
+                if result.is_err() {
+                    break;
+                }
+                shown = output;
+            }
+        }
+
+        std::thread::sleep(if reactions.flashing(now) {
+            interval.min(FLASH_HALF_PERIOD)
+        } else {
+            interval
+        });
+    }
+
+    if let Some(lights) = lights
+        && shown != Output::Normal
+    {
+        let restored = ec.rgbkbd_set_color(lights.start_key, lights.colors(Output::Normal));
+        result = result.and(restored);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::mock_ec;
+    use crate::{rgb_from_u32, rgb_to_hex_string};
+    use framework_lib::chromium_ec::command::EcCommands;
+    use framework_lib::chromium_ec::{CrosEcDriverType, mock};
+
+    #[test]
+    fn reactions_follow_events() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut reactions = Reactions::new(EventSettings::default());
+        assert_eq!(reactions.output(start), Output::Normal);
+
+        reactions.handle(&EcEvent::Lid { open: false }, start);
+        reactions.handle(&EcEvent::BatteryCritical { critical: true }, start);
+        assert_eq!(reactions.output(start), Output::Off);
+        reactions.handle(&EcEvent::Lid { open: true }, at(100));
+        assert_eq!(reactions.output(at(100)), Output::Flash);
+        assert_eq!(reactions.output(at(300)), Output::Off);
+        assert_eq!(reactions.output(at(600)), Output::Flash);
+        assert!(reactions.flashing(at(4_900)));
+        assert_eq!(reactions.output(at(5_000)), Output::Normal);
+
+        let mut reactions = Reactions::new(EventSettings {
+            chassis_off: false,
+            battery_flash: false,
+            ..EventSettings::default()
+        });
+        reactions.handle(&EcEvent::Chassis { open: true }, start);
+        reactions.handle(&EcEvent::Host(HostEvent::BatteryCritical), start);
+        assert_eq!(reactions.output(start), Output::Normal);
+        assert!(!reactions.flashing(start));
+    }
+
+    #[test]
+    fn run_restores_normal_colors() {
+        let _ec = mock_ec();
+        let ec = CrosEc::with(CrosEcDriverType::Mock).unwrap();
+        let frame = Frame::new(2, &[rgb_from_u32(0x00FF00), rgb_from_u32(0x0000FF)]);
+        let lights =
+            Lights::new(&frame, &EventSettings::default(), &ColorPipeline::default()).unwrap();
+        assert_eq!(
+            lights
+                .colors(Output::Flash)
+                .into_iter()
+                .map(rgb_to_hex_string)
+                .collect::<Vec<_>>(),
+            ["#FF0000", "#FF0000"]
+        );
+
+        // Closed at startup, so the lights go off and come back on when done
+        mock::set_lid_open(false);
+        let settings = EventSettings {
+            interval: 0.1,
+            ..EventSettings::default()
+        };
+        let running = AtomicBool::new(true);
+        let mut events = vec![];
+        run(
+            &ec,
+            &settings,
+            Some(&lights),
+            Some(Duration::from_millis(200)),
+            &running,
+            |event| events.push(*event),
+        )
+        .unwrap();
+        assert!(events.is_empty());
+        let writes: Vec<_> = mock::requests()
+            .into_iter()
+            .filter(|request| request.command == EcCommands::RgbKbdSetColor as u16)
+            .map(|request| request.data)
+            .collect();
+        assert_eq!(writes.len(), 2);
+        assert_eq!(writes[0][..8], [2, 2, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            mock::rgb_colors(2, 2)
+                .into_iter()
+                .map(rgb_to_hex_string)
+                .collect::<Vec<_>>(),
+            frame.colors
+        );
+    }
+}
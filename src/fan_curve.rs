@@ -0,0 +1,329 @@
+//! User-space fan curves driving the EC fan duty from temperature.
+//!
+//! The EC's own fan control is restored with `autofanctrl` whenever a [`FanController`] is
+//! dropped, including when a sensor can't be read, so the fans are never left at a fixed duty.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use framework_lib::chromium_ec::{CrosEc, EcError};
+use framework_lib::power;
+use serde::{Deserialize, Serialize};
+
+use crate::thermal::hottest;
+
+/// Settings for the fan curve controller, stored in the `[fan_curve]` config section.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FanCurveSettings {
+    /// Sensors to follow, the hottest one wins. All sensors are used if none of them exist.
+    pub sensors: Vec<String>,
+    /// Seconds between updates.
+    pub interval: f32,
+    /// Degrees the temperature has to drop before the duty is lowered again.
+    pub hysteresis: f32,
+    pub curves: Vec<FanCurve>,
+}
+
+impl Default for FanCurveSettings {
+    fn default() -> Self {
+        let point = |temp, duty| CurvePoint { temp, duty };
+        Self {
+            sensors: vec!["F75303_APU".to_string(), "APU".to_string()],
+            interval: 2.0,
+            hysteresis: 3.0,
+            // Quieter than the EC default, still at full speed when it gets really hot
+            curves: vec![FanCurve {
+                fan: None,
+                points: vec![
+                    point(45.0, 15.0),
+                    point(65.0, 30.0),
+                    point(80.0, 60.0),
+                    point(90.0, 100.0),
+                ],
+                min_duty: 0,
+                max_duty: 100,
+            }],
+        }
+    }
+}
+
+/// Piecewise-linear temperature to duty curve for one fan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FanCurve {
+    /// Fan index, all fans if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan: Option<u32>,
+    pub points: Vec<CurvePoint>,
+    #[serde(default)]
+    pub min_duty: u32,
+    #[serde(default = "default_max_duty")]
+    pub max_duty: u32,
+}
+
+fn default_max_duty() -> u32 {
+    100
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CurvePoint {
+    /// Temperature in degrees Celsius.
+    pub temp: f32,
+    /// Fan duty in percent.
+    pub duty: f32,
+}
+
+impl FanCurve {
+    pub fn label(&self) -> String {
+        match self.fan {
+            Some(fan) => format!("fan {fan}"),
+            None => "all fans".to_string(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.points.is_empty() {
+            return Err(format!("curve for {} has no points", self.label()));
+        }
+        if self.min_duty > self.max_duty || self.max_duty > 100 {
+            return Err(format!(
+                "curve for {} needs 0 <= min_duty <= max_duty <= 100",
+                self.label()
+            ));
+        }
+        if let Some(point) = self
+            .points
+            .iter()
+            .find(|point| !(0.0..=100.0).contains(&point.duty))
+        {
+            return Err(format!(
+                "curve for {}: duty {} at {} C is outside 0-100%",
+                self.label(),
+                point.duty,
+                point.temp
+            ));
+        }
+        Ok(())
+    }
+
+    /// Duty for `temp`, linear between points and clamped to `min_duty..=max_duty`.
+    pub fn duty_at(&self, temp: f32) -> u32 {
+        let mut points = self.points.clone();
+        points.sort_by(|a, b| a.temp.total_cmp(&b.temp));
+
+        let upper = points.partition_point(|point| point.temp <= temp);
+        let duty = if upper == 0 {
+            points.first().map_or(0.0, |point| point.duty)
+        } else if upper == points.len() {
+            points[upper - 1].duty
+        } else {
+            let (from, to) = (points[upper - 1], points[upper]);
+            from.duty + (to.duty - from.duty) * (temp - from.temp) / (to.temp - from.temp)
+        };
+
+        (duty.round() as u32).clamp(self.min_duty, self.max_duty)
+    }
+}
+
+/// Drives the fans along their curves, handing control back to the EC when dropped.
+pub struct FanController {
+    ec: CrosEc,
+    settings: FanCurveSettings,
+    /// Temperature each curve is currently following, lags behind falling temperatures.
+    followed: Vec<Option<f32>>,
+    duties: Vec<Option<u32>>,
+    /// Whether the EC may have been told to use a fixed duty.
+    controlling: bool,
+}
+
+impl FanController {
+    pub fn new(ec: CrosEc, settings: FanCurveSettings) -> Result<Self, String> {
+        if settings.curves.is_empty() {
+            return Err("no fan curves configured".to_string());
+        }
+        for curve in &settings.curves {
+            curve.validate()?;
+        }
+
+        let count = settings.curves.len();
+        Ok(Self {
+            ec,
+            settings,
+            followed: vec![None; count],
+            duties: vec![None; count],
+            controlling: false,
+        })
+    }
+
+    /// Time between two updates, from the settings.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f32(self.settings.interval.max(0.1))
+    }
+
+    /// Duty currently set for each curve, `None` until the first update.
+    pub fn duties(&self) -> &[Option<u32>] {
+        &self.duties
+    }
+
+    /// Read the temperature and update the fan duties.
+    ///
+    /// On failure the EC is put back in charge of the fans before the error is returned.
+    pub fn update(&mut self) -> Result<(String, u8), EcError> {
+        let reading = power::get_thermal_snapshot(&self.ec).and_then(|snapshot| {
+            let (name, temp) = hottest(&snapshot.temperatures, &self.settings.sensors)?;
+            Some((name.to_string(), temp))
+        });
+        let Some((name, temp)) = reading else {
+            self.restore();
+            return Err(EcError::DeviceError(
+                "unable to read temperature, returned fans to automatic control".to_string(),
+            ));
+        };
+
+        if let Err(err) = self.apply(temp as f32) {
+            self.restore();
+            return Err(err);
+        }
+        Ok((name, temp))
+    }
+
+    /// Set the duty of every curve for `temp`, only talking to the EC when a duty changes.
+    pub fn apply(&mut self, temp: f32) -> Result<(), EcError> {
+        let hysteresis = self.settings.hysteresis.max(0.0);
+        for (idx, curve) in self.settings.curves.iter().enumerate() {
+            let followed = match self.followed[idx] {
+                // Only follow a falling temperature once it dropped by the hysteresis
+                Some(followed) if temp < followed && temp > followed - hysteresis => followed,
+                _ => temp,
+            };
+            self.followed[idx] = Some(followed);
+
+            let duty = curve.duty_at(followed);
+            if self.duties[idx] != Some(duty) {
+                log::info!("{} at {followed} C, setting duty {duty}%", curve.label());
+                self.controlling = true;
+                self.ec.fan_set_duty(curve.fan, duty)?;
+                self.duties[idx] = Some(duty);
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand control of the fans back to the EC.
+    pub fn restore(&mut self) {
+        if !self.controlling {
+            return;
+        }
+        for curve in &self.settings.curves {
+            let fan = curve.fan.and_then(|fan| u8::try_from(fan).ok());
+            if let Err(err) = self.ec.autofanctrl(fan) {
+                log::error!("failed to restore automatic fan control: {err:?}");
+            }
+        }
+        self.duties.fill(None);
+        self.followed.fill(None);
+        self.controlling = false;
+    }
+}
+
+impl Drop for FanController {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+/// Run the fan curves every `settings.interval` seconds.
+///
+/// Runs until `duration` has elapsed (if given) or `running` is cleared, then restores
+/// automatic fan control.
+pub fn run(
+    ec: CrosEc,
+    settings: FanCurveSettings,
+    duration: Option<Duration>,
+    running: &AtomicBool,
+) -> Result<(), String> {
+    let mut controller = FanController::new(ec, settings)?;
+    let interval = controller.interval();
+    let start = Instant::now();
+
+    while running.load(Ordering::Relaxed) {
+        if duration.is_some_and(|duration| start.elapsed() >= duration) {
+            break;
+        }
+
+        controller
+            .update()
+            .map_err(|err| crate::format_ec_error(&err).to_string())?;
+
+        // Sleep in short steps to react quickly to being stopped
+        let next = Instant::now() + interval;
+        while running.load(Ordering::Relaxed) && Instant::now() < next {
+            std::thread::sleep(Duration::from_millis(50).min(next - Instant::now()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use framework_lib::chromium_ec::CrosEcDriverType;
+    use framework_lib::chromium_ec::mock::{self, MockFanMode};
+
+    fn curve() -> FanCurve {
+        FanCurveSettings::default().curves.remove(0)
+    }
+
+    #[test]
+    fn duty_follows_curve_within_limits() {
+        let mut curve = curve();
+        assert_eq!(curve.duty_at(20.0), 15);
+        assert_eq!(curve.duty_at(55.0), 23);
+        assert_eq!(curve.duty_at(85.0), 80);
+        assert_eq!(curve.duty_at(95.0), 100);
+
+        curve.min_duty = 20;
+        curve.max_duty = 70;
+        assert_eq!(curve.duty_at(20.0), 20);
+        assert_eq!(curve.duty_at(95.0), 70);
+    }
+
+    #[test]
+    fn invalid_curves_are_rejected() {
+        let mut empty = curve();
+        empty.points.clear();
+        assert!(empty.validate().is_err());
+
+        let mut limits = curve();
+        limits.min_duty = 80;
+        limits.max_duty = 50;
+        assert!(limits.validate().is_err());
+
+        let mut duty = curve();
+        duty.points[0].duty = 120.0;
+        assert!(duty.validate().is_err());
+    }
+
+    #[test]
+    fn controller_applies_hysteresis_and_restores_auto() {
+        let _ec = crate::tests::mock_ec();
+        let mut settings = FanCurveSettings::default();
+        settings.curves[0].fan = Some(0);
+        let ec = CrosEc::with(CrosEcDriverType::Mock).unwrap();
+        let mut controller = FanController::new(ec, settings).unwrap();
+
+        controller.apply(65.0).unwrap();
+        assert_eq!(mock::fan_mode(0), Some(MockFanMode::Duty(30)));
+        // Within the 3 degree hysteresis, keep following 65 C
+        controller.apply(63.0).unwrap();
+        assert_eq!(controller.duties(), [Some(30)]);
+        controller.apply(61.0).unwrap();
+        assert_eq!(controller.duties(), [Some(27)]);
+        controller.apply(70.0).unwrap();
+        assert_eq!(controller.duties(), [Some(40)]);
+
+        drop(controller);
+        assert_eq!(mock::fan_mode(0), Some(MockFanMode::Auto));
+    }
+}
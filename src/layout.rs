@@ -0,0 +1,126 @@
+//! Where the LEDs are: how many zones there are, the first key index and how they are arranged.
+
+use framework_lib::chromium_ec::commands::EC_RGBKBD_MAX_KEY_COUNT;
+use framework_lib::smbios::{self, Platform, PlatformFamily};
+use serde::{Deserialize, Serialize};
+
+/// How the zones are physically arranged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Geometry {
+    /// A closed loop, like the LEDs around a fan. The last zone sits next to the first.
+    #[default]
+    Ring,
+    /// A line of LEDs with two ends, like a keyboard row or light bar.
+    Strip,
+}
+
+impl Geometry {
+    pub fn all() -> &'static [Geometry] {
+        &[Geometry::Ring, Geometry::Strip]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Geometry::Ring => "Ring",
+            Geometry::Strip => "Strip",
+        }
+    }
+}
+
+/// The LEDs driven by the tool, stored in the `[layout]` config section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Layout {
+    /// Number of zones (keys) to program.
+    pub zones: usize,
+    /// First key index to program.
+    #[serde(default)]
+    pub start_key: u8,
+    #[serde(default)]
+    pub geometry: Geometry,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::FAN_RING
+    }
+}
+
+impl Layout {
+    /// The eight-LED ring of the Framework Desktop fan.
+    pub const FAN_RING: Layout = Layout {
+        zones: 8,
+        start_key: 0,
+        geometry: Geometry::Ring,
+    };
+
+    /// Most keys that can be programmed, key indices are a single byte.
+    pub const MAX_KEYS: usize = 256;
+
+    /// Default layout for a platform, the fan ring if it is unknown.
+    pub fn for_platform(platform: Option<Platform>) -> Self {
+        match platform.and_then(Platform::which_family) {
+            // RGB keyboard module, one EC request worth of keys
+            Some(PlatformFamily::Framework16) => Layout {
+                zones: EC_RGBKBD_MAX_KEY_COUNT,
+                start_key: 0,
+                geometry: Geometry::Strip,
+            },
+            _ => Self::FAN_RING,
+        }
+    }
+
+    /// Default layout for the machine we are running on.
+    pub fn detect() -> Self {
+        Self::for_platform(smbios::get_platform())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.zones == 0 {
+            return Err("layout needs at least one zone".to_string());
+        }
+        if self.zones > Self::MAX_KEYS - self.start_key as usize {
+            return Err(format!(
+                "{} zones starting at key {} go past the last key ({})",
+                self.zones,
+                self.start_key,
+                Self::MAX_KEYS - 1
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platform_defaults() {
+        assert_eq!(
+            Layout::for_platform(Some(Platform::FrameworkDesktopAmdAiMax300)),
+            Layout::FAN_RING
+        );
+        assert_eq!(
+            Layout::for_platform(Some(Platform::Framework16AmdAi300)).geometry,
+            Geometry::Strip
+        );
+        assert_eq!(Layout::for_platform(None), Layout::FAN_RING);
+    }
+
+    #[test]
+    fn keys_must_fit() {
+        let mut layout = Layout {
+            zones: 56,
+            start_key: 200,
+            geometry: Geometry::Strip,
+        };
+        assert!(layout.validate().is_ok());
+        layout.zones = 57;
+        assert!(layout.validate().is_err());
+        layout.zones = 0;
+        assert!(layout.validate().is_err());
+        layout.zones = usize::MAX;
+        assert!(layout.validate().is_err());
+    }
+}
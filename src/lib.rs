@@ -1,46 +1,58 @@
+use std::time::Duration;
+
 use framework_lib::chromium_ec::commands::RgbS;
-use framework_lib::chromium_ec::{CrosEc, CrosEcDriverType, EcError};
-
-/// Convert a raw 24-bit RGB value into the EC payload struct.
-pub fn rgb_from_u32(value: u32) -> RgbS {
-    RgbS {
-        r: ((value & 0x00FF_0000) >> 16) as u8,
-        g: ((value & 0x0000_FF00) >> 8) as u8,
-        b: (value & 0x0000_00FF) as u8,
-    }
-}
+use framework_lib::chromium_ec::replay::{Recording, RecordingDriver};
+use framework_lib::chromium_ec::{
+    BuiltinDriver, CrosEc, CrosEcDriverType, EcError, available_drivers,
+};
 
-/// Parse a textual color representation into a 24-bit RGB value accepted by the EC.
-///
-/// Supports `0xRRGGBB`, `#RRGGBB`, or decimal literals (0-16777215).
-pub fn parse_color(input: &str) -> Result<RgbS, String> {
-    let trimmed = input.trim();
-    let (radix, digits) = if let Some(hex) = trimmed.strip_prefix("0x") {
-        (16, hex)
-    } else if let Some(hex) = trimmed.strip_prefix("0X") {
-        (16, hex)
-    } else if let Some(hex) = trimmed.strip_prefix('#') {
-        (16, hex)
-    } else {
-        match u32::from_str_radix(trimmed, 10) {
-            Ok(value) => return rgb_from_value(trimmed, value),
-            Err(_) => (16, trimmed),
-        }
-    };
+pub mod color;
+pub mod config;
+#[cfg(unix)]
+pub mod daemon;
+pub mod effects;
+pub mod error;
+pub mod events;
+pub mod fan_curve;
+pub mod layout;
+pub mod palette;
+pub mod preset;
+pub mod profile;
+pub mod schedule;
+pub mod state;
+pub mod thermal;
 
-    let value = u32::from_str_radix(digits, radix)
-        .map_err(|err| format!("invalid color `{input}`: {err}"))?;
+pub use color::{
+    ColorPipeline, gradient, hsv_to_rgb, parse_color, rgb_from_u32, rgb_to_hex_string, scale_rgb,
+};
 
-    rgb_from_value(input, value)
+/// Open the Framework EC, either with an explicit driver or the platform default.
+pub fn open_ec(driver: Option<CrosEcDriverType>) -> Result<CrosEc, EcError> {
+    match driver {
+        Some(driver) => CrosEc::with(driver).ok_or_else(|| {
+            EcError::DeviceError(format!(
+                "driver {driver:?} is not available on this platform"
+            ))
+        }),
+        None => Ok(CrosEc::new()),
+    }
 }
 
-fn rgb_from_value(input: &str, value: u32) -> Result<RgbS, String> {
-    if value > 0x00FF_FFFF {
-        return Err(format!(
-            "color `{input}` exceeds 24-bit RGB range (0x000000..=0xFFFFFF)"
-        ));
-    }
-    Ok(rgb_from_u32(value))
+/// Like [`open_ec`], recording the traffic of the handle and its clones into `recording`.
+pub fn open_recording_ec(
+    driver: Option<CrosEcDriverType>,
+    recording: &Recording,
+) -> Result<CrosEc, EcError> {
+    let driver = driver.unwrap_or(available_drivers()[0]);
+    let driver = BuiltinDriver::new(driver).ok_or_else(|| {
+        EcError::DeviceError(format!(
+            "driver {driver:?} is not available on this platform"
+        ))
+    })?;
+    Ok(CrosEc::from_driver(Box::new(RecordingDriver::new(
+        driver,
+        recording.clone(),
+    ))))
 }
 
 /// Apply RGB colors starting at a given key index using the Framework EC.
@@ -49,37 +61,100 @@ pub fn apply_colors(
     colors: Vec<RgbS>,
     driver: Option<CrosEcDriverType>,
 ) -> Result<(), EcError> {
-    let ec = match driver {
-        Some(driver) => CrosEc::with(driver).ok_or_else(|| {
-            EcError::DeviceError(format!(
-                "driver {driver:?} is not available on this platform"
-            ))
-        })?,
-        None => CrosEc::new(),
-    };
-
-    ec.rgbkbd_set_color(start_key, colors)
+    open_ec(driver)?.rgbkbd_set_color(start_key, colors)
 }
 
-/// Convert a `RgbS` color to a hex string (`#RRGGBB`).
-pub fn rgb_to_hex_string(color: RgbS) -> String {
-    format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+/// `seconds` from the command line or config as a [`Duration`].
+///
+/// Negative, NaN and too large values are an error instead of a panic as with
+/// [`Duration::from_secs_f32`].
+pub fn seconds(seconds: f32) -> Result<Duration, String> {
+    Duration::try_from_secs_f32(seconds)
+        .map_err(|_| format!("{seconds} is not a valid number of seconds"))
 }
 
 /// Provide a user-friendly explanation for an EC error, including privilege guidance.
-pub fn format_ec_error(err: &EcError) -> String {
-    match err {
-        EcError::DeviceError(message) if message.contains("Not a Framework Laptop") => {
-            "EC access denied: SMBIOS check failed. Run this tool with administrative \
-privileges (sudo/Administrator) on a Framework system so the EC can be reached."
-                .to_string()
-        }
-        EcError::DeviceError(message) => format!("EC device error: {message}"),
-        EcError::Response(status) => {
-            format!("EC responded with status {:?}.", status)
-        }
-        EcError::UnknownResponseCode(code) => {
-            format!("EC returned unknown response code 0x{code:X}.")
+pub fn format_ec_error(err: &EcError) -> error::Error {
+    error::Error::from(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use framework_lib::chromium_ec::EcResponseStatus;
+    use framework_lib::chromium_ec::command::EcCommands;
+    use framework_lib::chromium_ec::mock;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// The mock EC is process-global, so tests using it must not run concurrently
+    static MOCK_EC: Mutex<()> = Mutex::new(());
+
+    pub(crate) fn mock_ec() -> MutexGuard<'static, ()> {
+        let guard = MOCK_EC.lock().unwrap_or_else(|err| err.into_inner());
+        mock::reset();
+        guard
+    }
+
+    fn hex_strings(colors: &[RgbS]) -> Vec<String> {
+        colors.iter().copied().map(rgb_to_hex_string).collect()
+    }
+
+    #[test]
+    fn apply_colors_mock() {
+        let _ec = mock_ec();
+        let colors = vec![rgb_from_u32(0xFF0000), rgb_from_u32(0x00FF00)];
+
+        apply_colors(3, colors.clone(), Some(CrosEcDriverType::Mock)).unwrap();
+
+        assert_eq!(hex_strings(&mock::rgb_colors(3, 2)), hex_strings(&colors));
+        let requests = mock::requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].command, EcCommands::RgbKbdSetColor as u16);
+        assert_eq!(&requests[0].data[..2], &[3, 2]);
+    }
+
+    #[test]
+    fn apply_colors_chunked() {
+        let _ec = mock_ec();
+        let colors: Vec<RgbS> = (0..70).map(rgb_from_u32).collect();
+
+        apply_colors(10, colors.clone(), Some(CrosEcDriverType::Mock)).unwrap();
+
+        let requests = mock::requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(&requests[0].data[..2], &[10, 64]);
+        assert_eq!(&requests[1].data[..2], &[74, 6]);
+        assert_eq!(hex_strings(&mock::rgb_colors(10, 70)), hex_strings(&colors));
+    }
+
+    #[test]
+    fn apply_colors_error() {
+        let _ec = mock_ec();
+        mock::fail_command(EcCommands::RgbKbdSetColor, EcResponseStatus::AccessDenied);
+
+        let err = apply_colors(0, vec![RgbS::default()], Some(CrosEcDriverType::Mock)).unwrap_err();
+
+        assert_eq!(err, EcError::Response(EcResponseStatus::AccessDenied));
+        let details = format_ec_error(&err);
+        assert_eq!(
+            details.to_string(),
+            "EC responded with status AccessDenied."
+        );
+        assert_eq!(details.status.as_deref(), Some("AccessDenied"));
+        assert_eq!(details.code, Some(4));
+        assert_eq!(details.exit_code(), error::ExitCode::Rejected);
+        assert_eq!(
+            serde_json::to_value(&details).unwrap()["kind"],
+            serde_json::json!("response")
+        );
+    }
+
+    #[test]
+    fn seconds_are_checked() {
+        assert_eq!(seconds(0.0), Ok(Duration::ZERO));
+        assert_eq!(seconds(1.5), Ok(Duration::from_millis(1500)));
+        for invalid in [-1.0, f32::NAN, f32::INFINITY, f32::MAX] {
+            assert!(seconds(invalid).is_err(), "{invalid}");
         }
     }
 }
@@ -1,28 +1,308 @@
-use clap::{ArgAction, Parser};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use framework_lib::chromium_ec::commands::RgbS;
-use framework_lib::chromium_ec::{CrosEcDriverType, EcError};
+use framework_lib::chromium_ec::events::EcEvent;
+use framework_lib::chromium_ec::replay::{self, Recording, Trace};
+use framework_lib::chromium_ec::{CrosEc, CrosEcDriverType, EcError, available_drivers};
+use framework_lib::power::{self, FanSpeed};
+use framework_lib::smbios;
+use serde::Serialize;
 
-use fwd_rgb::{apply_colors, format_ec_error, parse_color};
+#[cfg(unix)]
+use fwd_rgb::daemon::{self, Lighting, Request};
+use fwd_rgb::effects::{self, Effect, EffectKind};
+use fwd_rgb::error::{Error, ErrorKind};
+use fwd_rgb::events::{self, Lights};
+use fwd_rgb::fan_curve;
+use fwd_rgb::layout::{Geometry, Layout};
+use fwd_rgb::preset;
+use fwd_rgb::profile::{self, Profile, driver_name};
+use fwd_rgb::schedule::{self, LocalClock, Schedule, Scheduler};
+use fwd_rgb::state::{self, Frame};
+use fwd_rgb::thermal;
+use fwd_rgb::{ColorPipeline, format_ec_error, gradient, parse_color, rgb_to_hex_string};
 
 /// Control the Framework RGB keyboard colors using the EC command directly.
+///
+/// Colors given without a subcommand are the same as `set`.
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
 struct Args {
-    /// Starting key index to program.
-    #[arg(short, long, default_value_t = 0)]
-    start: u8,
+    /// Starting key index to program, defaults to the `[layout]` config or the platform default.
+    #[arg(short, long, global = true)]
+    start: Option<u8>,
+
+    /// Arrangement of the zones for effects, defaults to the `[layout]` config.
+    #[arg(long, value_enum, global = true)]
+    geometry: Option<Geometry>,
 
     /// Optional explicit EC driver selection.
-    #[arg(long, value_enum)]
+    #[arg(long, value_enum, global = true)]
     driver: Option<CrosEcDriverType>,
 
+    /// Record every EC command and response to a trace file, to be replayed with --replay.
+    #[arg(long, value_name = "PATH", global = true)]
+    record: Option<PathBuf>,
+
+    /// Answer EC commands from a trace recorded with --record instead of the EC.
+    #[arg(long, value_name = "PATH", global = true, conflicts_with = "driver")]
+    replay: Option<PathBuf>,
+
+    /// Send commands to the background daemon instead of opening the EC.
+    #[cfg(unix)]
+    #[arg(long, global = true, conflicts_with = "driver")]
+    daemon: bool,
+
+    /// Control socket of the daemon, defaults to $FWD_RGB_SOCKET or /run/fwd_rgb.sock.
+    #[cfg(unix)]
+    #[arg(long, value_name = "PATH", global = true)]
+    socket: Option<PathBuf>,
+
+    /// Brightness in percent, overrides `brightness` in the `[color]` config section.
+    #[arg(long, value_name = "PERCENT", global = true, value_parser = clap::value_parser!(u8).range(0..=100))]
+    brightness: Option<u8>,
+
+    /// Gamma correction (1.0 is linear, 2.2 suits most LEDs), overrides the `[color]` config.
+    #[arg(long, global = true)]
+    gamma: Option<f32>,
+
     /// Enable verbose logging output.
-    #[arg(short, long, action = ArgAction::Count)]
+    #[arg(short, long, action = ArgAction::Count, global = true)]
     verbose: u8,
 
-    /// One or more colors in 0xRRGGBB or decimal form.
+    /// Format of results and errors. JSON is printed to stdout, errors included.
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    output: OutputFormat,
+
+    /// One or more colors (0xRRGGBB, #RGB, CSS names, rgb(), hsl(), hsv() or 2700K), see `set`.
     #[arg(value_name = "COLOR", num_args = 1..)]
     colors: Vec<String>,
+
+    /// Apply a saved profile instead of COLOR arguments.
+    #[arg(long, value_name = "NAME", conflicts_with = "colors")]
+    profile: Option<String>,
+
+    /// Fill the layout with a built-in or saved preset (e.g. `neon-city`) instead of COLOR arguments.
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["colors", "profile"])]
+    preset: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Set consecutive keys to the given colors, starting at --start.
+    Set {
+        /// One or more colors (0xRRGGBB, #RGB, CSS names, rgb(), hsl(), hsv() or 2700K).
+        #[arg(value_name = "COLOR", required = true, num_args = 1..)]
+        colors: Vec<String>,
+    },
+
+    /// Set every zone to one color.
+    Fill {
+        color: String,
+
+        /// Number of zones to fill, defaults to the layout.
+        #[arg(short, long)]
+        zones: Option<usize>,
+    },
+
+    /// Blend from one color to another across the zones.
+    Gradient {
+        /// Color of the first zone.
+        from: String,
+
+        /// Color of the last zone.
+        to: String,
+
+        /// Number of zones to blend over, defaults to the layout.
+        #[arg(short, long)]
+        zones: Option<usize>,
+    },
+
+    /// Turn the lights off.
+    Off {
+        /// Number of zones to turn off, defaults to the layout.
+        #[arg(short, long)]
+        zones: Option<usize>,
+    },
+
+    /// Fill the zones with a built-in or saved preset, like --preset.
+    Preset {
+        /// Name of the preset, e.g. `neon-city`.
+        #[arg(required_unless_present = "list")]
+        name: Option<String>,
+
+        /// List the available presets instead.
+        #[arg(long, conflicts_with = "name")]
+        list: bool,
+    },
+
+    /// Show the EC driver, platform, EC firmware version, fan speeds and temperatures.
+    Status,
+
+    /// List the EC drivers available on this system, the default first.
+    Drivers,
+
+    /// Play an animated lighting effect until interrupted.
+    Effect {
+        /// Effect to play.
+        #[arg(value_enum)]
+        kind: EffectKind,
+
+        /// Base color for breathing, comet and strobe.
+        #[arg(long, default_value = "#FFFFFF")]
+        color: String,
+
+        /// Speed multiplier, 1.0 is the default pace.
+        #[arg(long, default_value_t = 1.0)]
+        speed: f32,
+
+        /// Number of zones to animate, defaults to the layout.
+        #[arg(short, long)]
+        zones: Option<usize>,
+
+        /// Frames sent to the EC per second.
+        #[arg(long, default_value_t = 30)]
+        fps: u32,
+
+        /// Stop after this many seconds instead of running until interrupted.
+        #[arg(long, value_parser = parse_seconds)]
+        duration: Option<f32>,
+    },
+
+    /// Color the ring by temperature until interrupted, using the `[thermal]` config section.
+    Thermal {
+        /// Sensor to watch (repeatable), e.g. F75303_APU. The hottest one is shown.
+        #[arg(long = "sensor", value_name = "NAME")]
+        sensors: Vec<String>,
+
+        /// Seconds between updates.
+        #[arg(long, value_parser = parse_seconds)]
+        interval: Option<f32>,
+
+        /// Number of zones to color, defaults to the layout.
+        #[arg(short, long)]
+        zones: Option<usize>,
+
+        /// Stop after this many seconds instead of running until interrupted.
+        #[arg(long, value_parser = parse_seconds)]
+        duration: Option<f32>,
+    },
+
+    /// Follow the `[[schedule]]` config rules until interrupted.
+    ///
+    /// Outside of all rules the active profile is shown.
+    Schedule {
+        /// Number of zones for presets, fades and effects, defaults to the layout.
+        #[arg(short, long)]
+        zones: Option<usize>,
+
+        /// Stop after this many seconds instead of running until interrupted.
+        #[arg(long, value_parser = parse_seconds)]
+        duration: Option<f32>,
+    },
+
+    /// Drive the fans along the `[fan_curve]` config curves until interrupted.
+    ///
+    /// Automatic fan control is restored on exit and whenever the temperature can't be read.
+    FanCurve {
+        /// Seconds between updates.
+        #[arg(long, value_parser = parse_seconds)]
+        interval: Option<f32>,
+
+        /// Stop after this many seconds instead of running until interrupted.
+        #[arg(long, value_parser = parse_seconds)]
+        duration: Option<f32>,
+    },
+
+    /// Print lid, power button, AC, battery and chassis events until interrupted.
+    ///
+    /// The lights react as set in the `[events]` config section, returning to the colors last set
+    /// by the CLI or GUI.
+    Watch {
+        /// Seconds between polls of the EC.
+        #[arg(long, value_parser = parse_seconds)]
+        interval: Option<f32>,
+
+        /// Stop after this many seconds instead of running until interrupted.
+        #[arg(long, value_parser = parse_seconds)]
+        duration: Option<f32>,
+    },
+
+    /// Manage saved lighting profiles.
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommand,
+    },
+
+    /// Run or control the background lighting daemon.
+    #[cfg(unix)]
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonCommand,
+    },
+}
+
+#[cfg(unix)]
+#[derive(Debug, Subcommand)]
+enum DaemonCommand {
+    /// Run the daemon in the foreground, owning the EC until killed.
+    Run {
+        /// Profile to apply on startup, defaults to the active profile.
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// Group whose members may control the daemon, otherwise only root may.
+        #[arg(long, value_name = "GROUP")]
+        group: Option<String>,
+    },
+    /// Show what the daemon is currently displaying.
+    Status,
+    /// Turn the lights off, remembering the current colors or effect.
+    Off,
+    /// Turn the lights back on.
+    On,
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfileCommand {
+    /// Save colors, together with --start and --driver, as a named profile.
+    Save {
+        /// Name of the profile, replaces an existing profile with the same name.
+        name: String,
+
+        /// One or more colors (0xRRGGBB, #RGB, CSS names, rgb(), hsl(), hsv() or 2700K).
+        #[arg(value_name = "COLOR", required = true, num_args = 1..)]
+        colors: Vec<String>,
+
+        /// Number of zones to fill, defaults to the number of colors.
+        #[arg(short, long)]
+        zones: Option<usize>,
+    },
+    /// Apply a saved profile.
+    Apply {
+        /// Name of the profile.
+        name: String,
+    },
+    /// List saved profiles.
+    List,
+    /// Delete a saved profile.
+    Delete {
+        /// Name of the profile.
+        name: String,
+    },
 }
 
 fn configure_logging(level: u8) {
@@ -45,44 +325,789 @@ fn configure_logging(level: u8) {
         .try_init();
 }
 
-fn main() {
-    let args = Args::parse();
-    configure_logging(args.verbose);
+/// Set from `--output json`.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
 
-    if args.colors.is_empty() {
-        eprintln!("error: at least one COLOR argument is required");
-        std::process::exit(2);
+/// Set from `--record`, with the file to save the recording to.
+static RECORDING: OnceLock<(PathBuf, Recording)> = OnceLock::new();
+
+/// Write the EC traffic recorded for `--record`, if it was given.
+fn save_recording() {
+    if let Some((path, recording)) = RECORDING.get()
+        && let Err(err) = recording.trace().save(path)
+    {
+        eprintln!("warning: {err}");
     }
+}
 
-    let colors: Vec<RgbS> = match args.colors.iter().map(|value| parse_color(value)).collect() {
-        Ok(colors) => colors,
-        Err(err) => {
-            eprintln!("{err}");
-            std::process::exit(2);
+/// Open the EC, recording its traffic for `--record`.
+fn open_ec(driver: Option<CrosEcDriverType>) -> Result<CrosEc, EcError> {
+    match RECORDING.get() {
+        Some((_, recording)) => fwd_rgb::open_recording_ec(driver, recording),
+        None => fwd_rgb::open_ec(driver),
+    }
+}
+
+/// Report `err` in the `--output` format and exit with its exit code.
+fn fail(err: &Error) -> ! {
+    save_recording();
+    let exit_code = err.exit_code().code();
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        let output = serde_json::json!({ "ok": false, "exit_code": exit_code, "error": err });
+        println!("{output}");
+    } else {
+        eprintln!("{err}");
+    }
+    std::process::exit(exit_code);
+}
+
+fn exit_with_ec_error(err: &EcError) -> ! {
+    fail(&format_ec_error(err))
+}
+
+fn exit_with_error(err: &str) -> ! {
+    fail(&Error::invalid(err))
+}
+
+/// Print the outcome of a command, `text` normally or `result` with `--output json`.
+fn print_result(text: &str, result: &impl Serialize) {
+    if !JSON_OUTPUT.load(Ordering::Relaxed) {
+        if !text.is_empty() {
+            println!("{text}");
         }
+        return;
+    }
+
+    let mut output = serde_json::to_value(result).expect("results serialize to JSON");
+    if let Some(object) = output.as_object_mut() {
+        object.insert("ok".to_string(), true.into());
+    }
+    println!("{output}");
+}
+
+/// Colors written by a command.
+#[derive(Serialize)]
+struct Written {
+    /// The EC driver, or `daemon`.
+    driver: String,
+    start_key: u8,
+    colors: Vec<String>,
+}
+
+impl Written {
+    fn keys(&self) -> String {
+        keys(self.colors.len(), self.start_key)
+    }
+}
+
+/// Parse the seconds of `--duration` and `--interval`.
+fn parse_seconds(value: &str) -> Result<f32, String> {
+    let seconds = value.parse().map_err(|err| format!("{err}"))?;
+    fwd_rgb::seconds(seconds)?;
+    Ok(seconds)
+}
+
+fn parse_colors(values: &[String]) -> Vec<RgbS> {
+    match values.iter().map(|value| parse_color(value)).collect() {
+        Ok(colors) => colors,
+        Err(err) => exit_with_error(&err),
+    }
+}
+
+/// The `[layout]` config section (or the platform default) with `--start`, `--geometry` and
+/// `zones` applied on top.
+fn layout(args: &Args, zones: Option<usize>) -> Layout {
+    let mut layout = match fwd_rgb::config::Config::load() {
+        Ok(config) => config.layout(),
+        Err(err) => exit_with_error(&err),
+    };
+    if let Some(start) = args.start {
+        layout.start_key = start;
+    }
+    if let Some(geometry) = args.geometry {
+        layout.geometry = geometry;
+    }
+    if let Some(zones) = zones {
+        layout.zones = zones;
+    }
+    layout
+        .validate()
+        .unwrap_or_else(|err| exit_with_error(&err));
+    layout
+}
+
+/// The `[color]` config section with `--brightness` and `--gamma` applied on top.
+fn color_pipeline(args: &Args) -> ColorPipeline {
+    let mut pipeline = match fwd_rgb::config::Config::load() {
+        Ok(config) => config.color,
+        Err(err) => exit_with_error(&err),
     };
+    if let Some(brightness) = args.brightness {
+        pipeline.brightness = brightness;
+    }
+    if let Some(gamma) = args.gamma {
+        pipeline.gamma = gamma;
+    }
+    pipeline
+        .validate()
+        .unwrap_or_else(|err| exit_with_error(&err));
+    pipeline
+}
+
+/// Hand `--brightness`/`--gamma` to the daemon, which otherwise keeps its own settings.
+#[cfg(unix)]
+fn update_daemon_pipeline(args: &Args) {
+    if args.brightness.is_some() || args.gamma.is_some() {
+        send_to_daemon(args, &Request::SetColorPipeline(color_pipeline(args)));
+    }
+}
+
+/// Send colors to the daemon with `--daemon`, otherwise straight to the EC.
+fn write_colors(
+    args: &Args,
+    start_key: u8,
+    colors: Vec<RgbS>,
+    driver: Option<CrosEcDriverType>,
+) -> Written {
+    let hex_colors: Vec<String> = colors.iter().copied().map(rgb_to_hex_string).collect();
+
+    #[cfg(unix)]
+    if args.daemon {
+        update_daemon_pipeline(args);
+        let request = Request::SetColors {
+            start_key,
+            colors: hex_colors.clone(),
+        };
+        send_to_daemon(args, &request);
+        return Written {
+            driver: "daemon".to_string(),
+            start_key,
+            colors: hex_colors,
+        };
+    }
+
+    let shown = color_pipeline(args).apply_all(colors.clone());
+    if let Err(err) = open_ec(driver).and_then(|ec| ec.rgbkbd_set_color(start_key, shown)) {
+        exit_with_ec_error(&err);
+    }
+    if let Err(err) = state::record(driver, start_key, &colors) {
+        eprintln!("warning: {err}");
+    }
+    Written {
+        driver: state::device_name(driver),
+        start_key,
+        colors: hex_colors,
+    }
+}
+
+#[cfg(unix)]
+fn send_to_daemon(args: &Args, request: &Request) -> Option<daemon::DaemonState> {
+    let socket = args.socket.clone().unwrap_or_else(daemon::socket_path);
+    let mut client = daemon::Client::connect(&socket)
+        .unwrap_or_else(|err| fail(&Error::new(ErrorKind::DaemonUnreachable, err)));
+    client
+        .request(request)
+        .unwrap_or_else(|err| fail(&Error::new(ErrorKind::Daemon, format!("daemon: {err}"))))
+}
 
+/// "N keys starting at K" for messages about written colors.
+fn keys(count: usize, start_key: u8) -> String {
+    format!(
+        "{count} key{} starting at {start_key}",
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+fn set_colors(args: &Args, colors: &[String]) {
     if colors.is_empty() {
-        eprintln!("no colors parsed from input");
-        std::process::exit(2);
+        exit_with_error("error: at least one COLOR argument is required");
     }
 
-    let color_count = colors.len();
+    let colors = parse_colors(colors);
+    let start_key = layout(args, Some(colors.len())).start_key;
 
-    if let Err(err) = apply_colors(args.start, colors, args.driver) {
-        let exit_code = if matches!(&err, EcError::DeviceError(_)) {
-            4
-        } else {
-            5
+    let written = write_colors(args, start_key, colors, args.driver);
+    print_result(&format!("Updated {}", written.keys()), &written);
+}
+
+fn fill(args: &Args, color: &str, zones: Option<usize>) {
+    let color = parse_color(color).unwrap_or_else(|err| exit_with_error(&err));
+    let layout = layout(args, zones);
+
+    let written = write_colors(
+        args,
+        layout.start_key,
+        vec![color; layout.zones],
+        args.driver,
+    );
+    print_result(&format!("Updated {}", written.keys()), &written);
+}
+
+fn fill_gradient(args: &Args, from: &str, to: &str, zones: Option<usize>) {
+    let from = parse_color(from).unwrap_or_else(|err| exit_with_error(&err));
+    let to = parse_color(to).unwrap_or_else(|err| exit_with_error(&err));
+    let layout = layout(args, zones);
+
+    let colors = gradient(from, to, layout.zones);
+    let written = write_colors(args, layout.start_key, colors, args.driver);
+    print_result(&format!("Updated {}", written.keys()), &written);
+}
+
+fn turn_off(args: &Args, zones: Option<usize>) {
+    #[cfg(unix)]
+    if args.daemon {
+        send_to_daemon(args, &Request::Off);
+        print_result("Lights off", &serde_json::json!({ "driver": "daemon" }));
+        return;
+    }
+
+    let layout = layout(args, zones);
+    let black = RgbS { r: 0, g: 0, b: 0 };
+    let written = write_colors(
+        args,
+        layout.start_key,
+        vec![black; layout.zones],
+        args.driver,
+    );
+    print_result(&format!("Turned off {}", written.keys()), &written);
+}
+
+/// Output of `status`.
+#[derive(Serialize)]
+struct Status {
+    driver: String,
+    platform: String,
+    ec_version: String,
+    /// `None` if the EC doesn't report temperatures and fan speeds.
+    thermal: Option<Thermal>,
+    /// Colors last written by the CLI or GUI.
+    last_applied: Option<Frame>,
+}
+
+#[derive(Serialize)]
+struct Thermal {
+    /// Every present fan.
+    fans: Vec<Fan>,
+    temperatures: Vec<Temperature>,
+}
+
+#[derive(Serialize)]
+struct Fan {
+    /// `None` if stalled.
+    rpm: Option<u16>,
+    /// Only reported by old EC firmware, newer firmware reports 0 RPM.
+    stalled: bool,
+}
+
+#[derive(Serialize)]
+struct Temperature {
+    name: String,
+    celsius: u8,
+}
+
+fn print_status(args: &Args) {
+    let ec = open_ec(args.driver).unwrap_or_else(|err| exit_with_ec_error(&err));
+    let platform =
+        smbios::get_platform().map_or_else(|| "unknown".to_string(), |p| format!("{p:?}"));
+    let ec_version = ec
+        .version_info()
+        .unwrap_or_else(|err| exit_with_ec_error(&err));
+    let thermal = power::get_thermal_snapshot(&ec).map(|snapshot| Thermal {
+        fans: snapshot
+            .fans
+            .iter()
+            .filter_map(|fan| match fan {
+                FanSpeed::Rpm(rpm) => Some(Fan {
+                    rpm: Some(*rpm),
+                    stalled: false,
+                }),
+                FanSpeed::Stalled => Some(Fan {
+                    rpm: None,
+                    stalled: true,
+                }),
+                FanSpeed::NotPresent => None,
+            })
+            .collect(),
+        temperatures: snapshot
+            .temperatures
+            .into_iter()
+            .filter_map(|temp| {
+                let celsius = temp.state.celsius()?;
+                Some(Temperature {
+                    name: temp.name,
+                    celsius,
+                })
+            })
+            .collect(),
+    });
+    let last_applied = state::last_frame(args.driver).unwrap_or_else(|err| {
+        eprintln!("warning: {err}");
+        None
+    });
+    let status = Status {
+        driver: state::device_name(args.driver),
+        platform,
+        ec_version,
+        thermal,
+        last_applied,
+    };
+
+    let mut text = vec![
+        format!("Driver:     {}", status.driver),
+        format!("Platform:   {}", status.platform),
+        format!("EC version: {}", status.ec_version),
+    ];
+    match &status.thermal {
+        Some(thermal) => {
+            for (idx, fan) in thermal.fans.iter().enumerate() {
+                match fan.rpm {
+                    Some(rpm) => text.push(format!("Fan {}:      {rpm} RPM", idx + 1)),
+                    None => text.push(format!("Fan {}:      stalled", idx + 1)),
+                }
+            }
+            for temp in &thermal.temperatures {
+                text.push(format!("{}: {} °C", temp.name, temp.celsius));
+            }
+        }
+        None => text.push("Thermal:    unavailable".to_string()),
+    }
+    if let Some(frame) = &status.last_applied {
+        text.push(format!(
+            "Last set:   {} starting at {}",
+            frame.colors.join(" "),
+            frame.start_key
+        ));
+    }
+    print_result(&text.join("\n"), &status);
+}
+
+#[derive(Serialize)]
+struct Driver {
+    name: String,
+    default: bool,
+}
+
+fn list_drivers() {
+    let drivers: Vec<_> = available_drivers()
+        .into_iter()
+        .enumerate()
+        .map(|(idx, driver)| Driver {
+            name: driver_name(driver),
+            default: idx == 0,
+        })
+        .collect();
+    let text: Vec<_> = drivers
+        .iter()
+        .map(|driver| {
+            let default = if driver.default { " (default)" } else { "" };
+            format!("{}{default}", driver.name)
+        })
+        .collect();
+    print_result(&text.join("\n"), &serde_json::json!({ "drivers": drivers }));
+}
+
+fn play_effect(args: &Args, effect: Effect, zones: Option<usize>, fps: u32, duration: Option<f32>) {
+    let layout = layout(args, zones);
+
+    #[cfg(unix)]
+    if args.daemon {
+        if duration.is_some() {
+            exit_with_error("--duration can't be used with --daemon");
+        }
+        let request = Request::SetEffect {
+            start_key: layout.start_key,
+            zones: layout.zones,
+            geometry: layout.geometry,
+            kind: effect.kind,
+            color: rgb_to_hex_string(effect.color),
+            speed: effect.speed,
+        };
+        update_daemon_pipeline(args);
+        send_to_daemon(args, &request);
+        print_result(
+            &format!("Daemon is playing the {} effect", effect.kind.label()),
+            &serde_json::json!({ "driver": "daemon", "effect": effect.kind }),
+        );
+        return;
+    }
+
+    let pipeline = color_pipeline(args);
+    let ec = match open_ec(args.driver) {
+        Ok(ec) => ec,
+        Err(err) => exit_with_ec_error(&err),
+    };
+    // Ctrl-C stops between two frames instead of in the middle of a write
+    stop_on_interrupt();
+
+    let duration = duration.map(Duration::from_secs_f32);
+    if let Err(err) = effects::play(&ec, &layout, &effect, &pipeline, fps, duration, &RUNNING) {
+        exit_with_ec_error(&err);
+    }
+    print_result("", &serde_json::json!({}));
+}
+
+fn run_thermal(
+    args: &Args,
+    sensors: &[String],
+    interval: Option<f32>,
+    zones: Option<usize>,
+    duration: Option<f32>,
+) {
+    let layout = layout(args, zones);
+    let mut settings = match fwd_rgb::config::Config::load() {
+        Ok(config) => config.thermal,
+        Err(err) => exit_with_error(&err),
+    };
+    if !sensors.is_empty() {
+        settings.sensors = sensors.to_vec();
+    }
+    if let Some(interval) = interval {
+        settings.interval = interval;
+    }
+    let pipeline = color_pipeline(args);
+
+    let ec = open_ec(args.driver).unwrap_or_else(|err| exit_with_ec_error(&err));
+    // Ctrl-C stops between two updates instead of in the middle of a write
+    stop_on_interrupt();
+
+    let duration = duration.map(Duration::from_secs_f32);
+    if let Err(err) = thermal::run(&ec, &layout, &settings, &pipeline, duration, &RUNNING) {
+        fail(&err);
+    }
+    print_result("", &serde_json::json!({}));
+}
+
+/// Cleared by Ctrl-C so long-running modes can clean up before exiting.
+static RUNNING: AtomicBool = AtomicBool::new(true);
+
+/// Clear [`RUNNING`] on Ctrl-C, SIGTERM and SIGHUP instead of exiting right away.
+fn stop_on_interrupt() {
+    if let Err(err) = ctrlc::set_handler(|| RUNNING.store(false, Ordering::Relaxed)) {
+        exit_with_error(&format!("failed to install Ctrl-C handler: {err}"));
+    }
+}
+
+fn run_schedule(args: &Args, zones: Option<usize>, duration: Option<f32>) {
+    #[cfg(unix)]
+    if args.daemon {
+        exit_with_error("schedule can't be used with --daemon");
+    }
+
+    let layout = layout(args, zones);
+    let config = fwd_rgb::config::Config::load().unwrap_or_else(|err| exit_with_error(&err));
+    if config.schedule.is_empty() {
+        exit_with_error("no [[schedule]] rules in the config file");
+    }
+    let schedule = Schedule::new(&config.schedule, &config, &layout)
+        .unwrap_or_else(|err| exit_with_error(&err));
+    let mut scheduler = Scheduler::new(schedule, layout, color_pipeline(args));
+
+    let ec = open_ec(args.driver).unwrap_or_else(|err| exit_with_ec_error(&err));
+    // Ctrl-C ends the current step instead of killing us mid-write
+    stop_on_interrupt();
+
+    let duration = duration.map(Duration::from_secs_f32);
+    if let Err(err) = schedule::run(&ec, &mut scheduler, &LocalClock, duration, &RUNNING) {
+        exit_with_ec_error(&err);
+    }
+    print_result("", &serde_json::json!({}));
+}
+
+fn run_fan_curve(args: &Args, interval: Option<f32>, duration: Option<f32>) {
+    let mut settings = match fwd_rgb::config::Config::load() {
+        Ok(config) => config.fan_curve,
+        Err(err) => exit_with_error(&err),
+    };
+    if let Some(interval) = interval {
+        settings.interval = interval;
+    }
+
+    let ec = open_ec(args.driver).unwrap_or_else(|err| exit_with_ec_error(&err));
+    // Without the handler Ctrl-C would kill us with the fans stuck at a fixed duty
+    stop_on_interrupt();
+
+    let duration = duration.map(Duration::from_secs_f32);
+    if let Err(err) = fan_curve::run(ec, settings, duration, &RUNNING) {
+        fail(&Error::new(ErrorKind::Device, err));
+    }
+    print_result("", &serde_json::json!({}));
+}
+
+/// An event printed by `watch`.
+#[derive(Serialize)]
+struct WatchedEvent {
+    event: &'static str,
+    /// Name of the host event for `host-event`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host_event: Option<String>,
+}
+
+fn watch_events(args: &Args, interval: Option<f32>, duration: Option<f32>) {
+    #[cfg(unix)]
+    if args.daemon {
+        exit_with_error("watch can't be used with --daemon");
+    }
+
+    let mut settings = match fwd_rgb::config::Config::load() {
+        Ok(config) => config.events,
+        Err(err) => exit_with_error(&err),
+    };
+    if let Some(interval) = interval {
+        settings.interval = interval;
+    }
+    let frame = state::last_frame(args.driver).unwrap_or_else(|err| exit_with_error(&err));
+    let lights = match frame {
+        Some(frame) => Some(
+            Lights::new(&frame, &settings, &color_pipeline(args))
+                .unwrap_or_else(|err| exit_with_error(&err)),
+        ),
+        None => {
+            eprintln!("warning: no colors were set yet, the lights won't react to events");
+            None
+        }
+    };
+
+    let ec = open_ec(args.driver).unwrap_or_else(|err| exit_with_ec_error(&err));
+    // Ctrl-C must not leave the lights off or flashing
+    stop_on_interrupt();
+
+    let duration = duration.map(Duration::from_secs_f32);
+    let print_event = |event: &EcEvent| {
+        let watched = WatchedEvent {
+            event: events::event_name(event),
+            host_event: match event {
+                EcEvent::Host(host_event) => Some(format!("{host_event:?}")),
+                _ => None,
+            },
         };
-        eprintln!("{}", format_ec_error(&err));
-        std::process::exit(exit_code);
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            println!("{}", serde_json::json!(watched));
+        } else if let Some(host_event) = &watched.host_event {
+            println!("{} {host_event}", watched.event);
+        } else {
+            println!("{}", watched.event);
+        }
+    };
+    if let Err(err) = events::run(
+        &ec,
+        &settings,
+        lights.as_ref(),
+        duration,
+        &RUNNING,
+        print_event,
+    ) {
+        exit_with_ec_error(&err);
+    }
+    print_result("", &serde_json::json!({}));
+}
+
+fn apply_profile(args: &Args, name: &str) {
+    let profile = profile::load_profile(name).unwrap_or_else(|err| exit_with_error(&err));
+    let colors = profile
+        .rgb_colors()
+        .unwrap_or_else(|err| exit_with_error(&err));
+    // An explicit --driver wins over the one stored in the profile
+    let driver = match args.driver {
+        Some(driver) => Some(driver),
+        None => profile.driver().unwrap_or_else(|err| exit_with_error(&err)),
+    };
+
+    let written = write_colors(args, profile.start_key, colors, driver);
+    if let Err(err) = profile::set_active_profile(name) {
+        eprintln!("warning: {err}");
+    }
+
+    print_result(
+        &format!("Applied profile {name}: {}", written.keys()),
+        &written,
+    );
+}
+
+fn apply_preset(args: &Args, name: &str) {
+    let preset = preset::find_preset(name).unwrap_or_else(|err| exit_with_error(&err));
+    let layout = layout(args, None);
+    let colors = preset
+        .render(layout.zones)
+        .unwrap_or_else(|err| exit_with_error(&err));
+
+    let written = write_colors(args, layout.start_key, colors, args.driver);
+    print_result(
+        &format!("Applied preset {}: {}", preset.name, written.keys()),
+        &written,
+    );
+}
+
+fn list_presets() {
+    let config = fwd_rgb::config::Config::load().unwrap_or_else(|err| exit_with_error(&err));
+    let names: Vec<_> = preset::all_presets(&config)
+        .into_iter()
+        .map(|preset| preset.name)
+        .collect();
+    print_result(&names.join("\n"), &serde_json::json!({ "presets": names }));
+}
+
+fn run_profile_command(args: &Args, action: &ProfileCommand) {
+    match action {
+        ProfileCommand::Save {
+            name,
+            colors,
+            zones,
+        } => {
+            let colors = parse_colors(colors);
+            let start_key = layout(args, Some(colors.len())).start_key;
+            let mut profile = Profile::new(start_key, &colors, args.driver);
+            if let Some(zones) = zones {
+                profile.zones = *zones;
+            }
+            if let Err(err) = profile::save_profile(name, &profile) {
+                exit_with_error(&err);
+            }
+            print_result(
+                &format!("Saved profile {name}"),
+                &serde_json::json!({ "profile": name }),
+            );
+        }
+        ProfileCommand::Apply { name } => apply_profile(args, name),
+        ProfileCommand::List => {
+            let names = profile::list_profiles().unwrap_or_else(|err| exit_with_error(&err));
+            print_result(&names.join("\n"), &serde_json::json!({ "profiles": names }));
+        }
+        ProfileCommand::Delete { name } => {
+            if let Err(err) = profile::delete_profile(name) {
+                exit_with_error(&err);
+            }
+            print_result(
+                &format!("Deleted profile {name}"),
+                &serde_json::json!({ "profile": name }),
+            );
+        }
     }
+}
 
-    println!(
-        "Updated {} key{} starting at {}",
-        color_count,
-        if color_count == 1 { "" } else { "s" },
-        args.start
+#[cfg(unix)]
+fn run_daemon_command(args: &Args, action: &DaemonCommand) {
+    let request = match action {
+        DaemonCommand::Run { profile, group } => {
+            return run_daemon(args, profile.as_deref(), group.as_deref());
+        }
+        DaemonCommand::Status => Request::State,
+        DaemonCommand::Off => Request::Off,
+        DaemonCommand::On => Request::On,
+    };
+
+    let Some(state) = send_to_daemon(args, &request) else {
+        return print_result("", &serde_json::json!({}));
+    };
+    let lighting = match state.effect {
+        Some(kind) => format!("{} effect", kind.label()),
+        None if state.colors.is_empty() => "no colors".to_string(),
+        None => state.colors.join(" "),
+    };
+    let text = format!(
+        "Lighting {}: {lighting} starting at {} ({}% brightness, gamma {})",
+        if state.enabled { "on" } else { "off" },
+        state.start_key,
+        state.color.brightness,
+        state.color.gamma
     );
+    print_result(&text, &state);
+}
+
+#[cfg(unix)]
+fn run_daemon(args: &Args, profile: Option<&str>, group: Option<&str>) {
+    let profile = match profile {
+        Some(name) => Some(profile::load_profile(name).unwrap_or_else(|err| exit_with_error(&err))),
+        None => match fwd_rgb::config::Config::load() {
+            Ok(mut config) => config
+                .active_profile
+                .and_then(|name| config.profiles.remove(&name)),
+            Err(err) => {
+                eprintln!("warning: {err}");
+                None
+            }
+        },
+    };
+    let initial = profile.map(|profile| {
+        Lighting::from_profile(&profile).unwrap_or_else(|err| exit_with_error(&err))
+    });
+
+    let pipeline = color_pipeline(args);
+
+    let ec = open_ec(args.driver).unwrap_or_else(|err| exit_with_ec_error(&err));
+    let socket = args.socket.clone().unwrap_or_else(daemon::socket_path);
+    // The daemon only stops when signalled, write the trace before exiting
+    if RECORDING.get().is_some()
+        && let Err(err) = ctrlc::set_handler(|| {
+            save_recording();
+            std::process::exit(0);
+        })
+    {
+        exit_with_error(&format!("failed to install Ctrl-C handler: {err}"));
+    }
+    if let Err(err) = daemon::run(ec, &socket, group, initial, pipeline) {
+        fail(&Error::new(ErrorKind::Device, err));
+    }
+}
+
+fn main() {
+    let mut args = Args::parse();
+    configure_logging(args.verbose);
+    JSON_OUTPUT.store(args.output == OutputFormat::Json, Ordering::Relaxed);
+
+    if let Some(path) = &args.replay {
+        replay::load(Trace::load(path).unwrap_or_else(|err| exit_with_error(&err)));
+        args.driver = Some(CrosEcDriverType::Replay);
+    }
+    if let Some(path) = &args.record {
+        let _ = RECORDING.set((path.clone(), Recording::new()));
+    }
+
+    match &args.command {
+        None => match (&args.profile, &args.preset) {
+            (Some(name), _) => apply_profile(&args, name),
+            (None, Some(name)) => apply_preset(&args, name),
+            (None, None) => set_colors(&args, &args.colors),
+        },
+        Some(Command::Set { colors }) => set_colors(&args, colors),
+        Some(Command::Fill { color, zones }) => fill(&args, color, *zones),
+        Some(Command::Gradient { from, to, zones }) => fill_gradient(&args, from, to, *zones),
+        Some(Command::Off { zones }) => turn_off(&args, *zones),
+        Some(Command::Preset { name, list }) => match name {
+            Some(name) if !list => apply_preset(&args, name),
+            _ => list_presets(),
+        },
+        Some(Command::Status) => print_status(&args),
+        Some(Command::Drivers) => list_drivers(),
+        Some(Command::Effect {
+            kind,
+            color,
+            speed,
+            zones,
+            fps,
+            duration,
+        }) => {
+            let color = parse_color(color).unwrap_or_else(|err| exit_with_error(&err));
+            let effect = Effect {
+                kind: *kind,
+                color,
+                speed: *speed,
+            };
+            play_effect(&args, effect, *zones, *fps, *duration);
+        }
+        Some(Command::Thermal {
+            sensors,
+            interval,
+            zones,
+            duration,
+        }) => run_thermal(&args, sensors, *interval, *zones, *duration),
+        Some(Command::Schedule { zones, duration }) => run_schedule(&args, *zones, *duration),
+        Some(Command::FanCurve { interval, duration }) => {
+            run_fan_curve(&args, *interval, *duration)
+        }
+        Some(Command::Watch { interval, duration }) => watch_events(&args, *interval, *duration),
+        Some(Command::Profile { action }) => run_profile_command(&args, action),
+        #[cfg(unix)]
+        Some(Command::Daemon { action }) => run_daemon_command(&args, action),
+    }
+    save_recording();
 }
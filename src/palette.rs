@@ -0,0 +1,319 @@
+//! Import and export of color palettes: profiles (JSON or TOML), GIMP palettes and images.
+
+use std::path::Path;
+
+use framework_lib::chromium_ec::commands::RgbS;
+
+use crate::profile::Profile;
+use crate::rgb_to_hex_string;
+
+/// Image files are scaled down to at most this many pixels per side before sampling.
+const IMAGE_SAMPLE_SIZE: u32 = 64;
+
+/// File formats palettes can be read from or written to, picked by file extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// A [`Profile`] as JSON, or a plain array of colors.
+    Json,
+    /// A [`Profile`] as TOML.
+    Toml,
+    /// GIMP palette (`.gpl`).
+    Gpl,
+    /// PNG, JPEG, GIF or BMP image, only for import.
+    Image,
+}
+
+impl PaletteFormat {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("json") => Ok(PaletteFormat::Json),
+            Some("toml") => Ok(PaletteFormat::Toml),
+            Some("gpl") => Ok(PaletteFormat::Gpl),
+            Some("png" | "jpg" | "jpeg" | "gif" | "bmp") => Ok(PaletteFormat::Image),
+            _ => Err(format!(
+                "unsupported palette file `{}`, expected .json, .toml, .gpl or an image",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// Read the colors from a palette file. Images are reduced to `zones` dominant colors.
+pub fn import(path: &Path, zones: usize) -> Result<Vec<RgbS>, String> {
+    let format = PaletteFormat::from_path(path)?;
+    let bytes =
+        std::fs::read(path).map_err(|err| format!("failed to read `{}`: {err}", path.display()))?;
+    import_bytes(format, &bytes, zones).map_err(|err| format!("`{}`: {err}", path.display()))
+}
+
+/// Read the colors from the contents of a palette file.
+pub fn import_bytes(
+    format: PaletteFormat,
+    bytes: &[u8],
+    zones: usize,
+) -> Result<Vec<RgbS>, String> {
+    let text = || std::str::from_utf8(bytes).map_err(|_| "file is not valid UTF-8".to_string());
+    let colors = match format {
+        PaletteFormat::Json => {
+            let value: serde_json::Value =
+                serde_json::from_str(text()?).map_err(|err| format!("invalid JSON: {err}"))?;
+            let profile = if value.is_array() {
+                // Just the colors, as written by other tools
+                let colors: Vec<String> = serde_json::from_value(value)
+                    .map_err(|err| format!("invalid color list: {err}"))?;
+                Profile {
+                    zones: colors.len(),
+                    colors,
+                    start_key: 0,
+                    driver: None,
+                }
+            } else {
+                serde_json::from_value(value).map_err(|err| format!("invalid profile: {err}"))?
+            };
+            profile.rgb_colors()?
+        }
+        PaletteFormat::Toml => {
+            let profile: Profile =
+                toml::from_str(text()?).map_err(|err| format!("invalid profile: {err}"))?;
+            profile.rgb_colors()?
+        }
+        PaletteFormat::Gpl => parse_gpl(text()?)?,
+        PaletteFormat::Image => {
+            let mut image = image::load_from_memory(bytes)
+                .map_err(|err| format!("unable to read image: {err}"))?;
+            if image.width() > IMAGE_SAMPLE_SIZE || image.height() > IMAGE_SAMPLE_SIZE {
+                image = image.thumbnail(IMAGE_SAMPLE_SIZE, IMAGE_SAMPLE_SIZE);
+            }
+            let image = image.to_rgb8();
+            let pixels: Vec<RgbS> = image
+                .pixels()
+                .map(|pixel| RgbS {
+                    r: pixel[0],
+                    g: pixel[1],
+                    b: pixel[2],
+                })
+                .collect();
+            dominant_colors(&pixels, zones)
+        }
+    };
+
+    if colors.is_empty() {
+        return Err("palette has no colors".to_string());
+    }
+    Ok(colors)
+}
+
+/// Write `profile` to `path` in the format matching its extension.
+pub fn export(path: &Path, name: &str, profile: &Profile) -> Result<(), String> {
+    let contents = match PaletteFormat::from_path(path)? {
+        PaletteFormat::Json => serde_json::to_string_pretty(profile)
+            .map_err(|err| format!("failed to encode palette: {err}"))?,
+        PaletteFormat::Toml => toml::to_string_pretty(profile)
+            .map_err(|err| format!("failed to encode palette: {err}"))?,
+        PaletteFormat::Gpl => to_gpl(name, &profile.rgb_colors()?),
+        PaletteFormat::Image => return Err("palettes can't be exported as images".to_string()),
+    };
+    std::fs::write(path, contents)
+        .map_err(|err| format!("failed to write `{}`: {err}", path.display()))
+}
+
+/// Parse a GIMP palette.
+pub fn parse_gpl(text: &str) -> Result<Vec<RgbS>, String> {
+    let mut lines = text.lines().enumerate();
+    if lines.next().map(|(_, line)| line.trim()) != Some("GIMP Palette") {
+        return Err("not a GIMP palette, the first line must be `GIMP Palette`".to_string());
+    }
+
+    let mut colors = Vec::new();
+    for (idx, line) in lines {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        }
+
+        let mut channels = line.split_whitespace().map(str::parse::<u8>);
+        match (channels.next(), channels.next(), channels.next()) {
+            (Some(Ok(r)), Some(Ok(g)), Some(Ok(b))) => colors.push(RgbS { r, g, b }),
+            _ => {
+                return Err(format!(
+                    "line {}: expected `R G B [name]` with values from 0 to 255, got `{line}`",
+                    idx + 1
+                ));
+            }
+        }
+    }
+    Ok(colors)
+}
+
+/// Format colors as a GIMP palette named `name`.
+pub fn to_gpl(name: &str, colors: &[RgbS]) -> String {
+    let mut gpl = format!(
+        "GIMP Palette\nName: {name}\nColumns: {}\n#\n",
+        colors.len().min(16)
+    );
+    for color in colors {
+        gpl += &format!(
+            "{:3} {:3} {:3}\t{}\n",
+            color.r,
+            color.g,
+            color.b,
+            rgb_to_hex_string(*color)
+        );
+    }
+    gpl
+}
+
+/// Up to `count` colors representing `pixels`, most common first.
+pub fn dominant_colors(pixels: &[RgbS], count: usize) -> Vec<RgbS> {
+    if pixels.is_empty() || count == 0 {
+        return Vec::new();
+    }
+
+    let channel = |color: &RgbS, channel: usize| [color.r, color.g, color.b][channel];
+    // Channel with the widest spread of values in a box, and the middle of that spread
+    let widest = |pixels: &[RgbS]| {
+        (0..3)
+            .map(|idx| {
+                let values = pixels.iter().map(|color| channel(color, idx));
+                let (min, max) = (values.clone().min().unwrap_or(0), values.max().unwrap_or(0));
+                (idx, max - min, min + (max - min) / 2)
+            })
+            .max_by_key(|(_, range, _)| *range)
+            .unwrap_or((0, 0, 0))
+    };
+
+    let mut boxes = vec![pixels.to_vec()];
+    while boxes.len() < count {
+        // Split the box with the widest spread in the middle, so separate clusters end up apart
+        let Some((idx, (split_channel, range, middle))) = boxes
+            .iter()
+            .enumerate()
+            .map(|(idx, pixels)| (idx, widest(pixels)))
+            .max_by_key(|(_, (_, range, _))| *range)
+        else {
+            break;
+        };
+        if range == 0 {
+            break;
+        }
+
+        let (lower, upper) = boxes
+            .swap_remove(idx)
+            .into_iter()
+            .partition(|color| channel(color, split_channel) <= middle);
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    boxes.sort_by_key(|pixels| std::cmp::Reverse(pixels.len()));
+    boxes
+        .iter()
+        .map(|pixels| {
+            let average = |idx| {
+                let sum: usize = pixels
+                    .iter()
+                    .map(|color| channel(color, idx) as usize)
+                    .sum();
+                (sum as f32 / pixels.len() as f32).round() as u8
+            };
+            RgbS {
+                r: average(0),
+                g: average(1),
+                b: average(2),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rgb_from_u32;
+
+    fn hex(colors: &[RgbS]) -> Vec<String> {
+        colors.iter().copied().map(rgb_to_hex_string).collect()
+    }
+
+    #[test]
+    fn gpl_round_trip() {
+        let colors = [rgb_from_u32(0xF2662B), rgb_from_u32(0x00FF00)];
+        let gpl = to_gpl("Ember", &colors);
+        assert!(gpl.starts_with("GIMP Palette\nName: Ember\n"));
+        assert_eq!(hex(&parse_gpl(&gpl).unwrap()), hex(&colors));
+
+        let designer = "GIMP Palette\nName: Brand\nColumns: 4\n# comment\n\n  0 128 255 Sky blue\n";
+        assert_eq!(hex(&parse_gpl(designer).unwrap()), ["#0080FF"]);
+    }
+
+    #[test]
+    fn gpl_errors_name_the_line() {
+        assert!(parse_gpl("JASC-PAL\n").is_err());
+        assert_eq!(
+            parse_gpl("GIMP Palette\n255 0\n").unwrap_err(),
+            "line 2: expected `R G B [name]` with values from 0 to 255, got `255 0`"
+        );
+        assert!(parse_gpl("GIMP Palette\n256 0 0 Too red\n").is_err());
+    }
+
+    #[test]
+    fn imports_profiles_and_color_lists() {
+        let json = br##"{"colors": ["#FF0000", "blue"], "zones": 3}"##;
+        assert_eq!(
+            hex(&import_bytes(PaletteFormat::Json, json, 8).unwrap()),
+            ["#FF0000", "#0000FF", "#FF0000"]
+        );
+        let list = br##"["#FF0000", "hsl(120, 100%, 50%)"]"##;
+        assert_eq!(
+            hex(&import_bytes(PaletteFormat::Json, list, 8).unwrap()),
+            ["#FF0000", "#00FF00"]
+        );
+        let toml = br##"colors = ["teal"]"##;
+        assert_eq!(
+            hex(&import_bytes(PaletteFormat::Toml, toml, 8).unwrap()),
+            vec!["#008080"; 8]
+        );
+        assert_eq!(
+            import_bytes(PaletteFormat::Json, b"[]", 8).unwrap_err(),
+            "profile has no colors"
+        );
+    }
+
+    #[test]
+    fn dominant_colors_by_population() {
+        let red = rgb_from_u32(0xFF0000);
+        let blue = rgb_from_u32(0x0000FF);
+        let mut pixels = vec![red; 30];
+        pixels.extend([blue; 10]);
+
+        assert_eq!(hex(&dominant_colors(&pixels, 2)), ["#FF0000", "#0000FF"]);
+        // A flat image has only one color to offer
+        assert_eq!(hex(&dominant_colors(&[blue; 5], 4)), ["#0000FF"]);
+    }
+
+    #[test]
+    fn imports_images() {
+        let image = image::RgbImage::from_fn(8, 8, |x, _| {
+            if x < 6 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        });
+        let mut png = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+
+        assert_eq!(
+            hex(&import_bytes(PaletteFormat::Image, png.get_ref(), 8).unwrap()),
+            ["#FF0000", "#0000FF"]
+        );
+        assert!(import_bytes(PaletteFormat::Image, b"not an image", 8).is_err());
+    }
+}
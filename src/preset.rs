@@ -0,0 +1,337 @@
+//! Named color palettes: the built-in presets plus the user's own, stored in the config file.
+
+use framework_lib::chromium_ec::commands::RgbS;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::{parse_color, rgb_from_u32, rgb_to_hex_string};
+
+/// How a preset's colors are spread over the zones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fill {
+    /// Repeat the colors in order until every zone has one.
+    #[default]
+    Repeat,
+    /// A random color for every zone, the colors are ignored.
+    Random,
+}
+
+/// A named palette, stored as `[[presets]]` in the config file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+
+    /// Colors in any format accepted by [`parse_color`].
+    #[serde(default)]
+    pub colors: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "is_repeat")]
+    pub fill: Fill,
+}
+
+fn is_repeat(fill: &Fill) -> bool {
+    *fill == Fill::Repeat
+}
+
+impl Preset {
+    pub fn new(name: &str, colors: &[RgbS]) -> Self {
+        Self {
+            name: name.trim().to_string(),
+            colors: colors.iter().copied().map(rgb_to_hex_string).collect(),
+            fill: Fill::Repeat,
+        }
+    }
+
+    fn builtin(name: &str, colors: &[u32]) -> Self {
+        Self::new(
+            name,
+            &colors.iter().copied().map(rgb_from_u32).collect::<Vec<_>>(),
+        )
+    }
+
+    /// The colors of `zones` zones.
+    pub fn render(&self, zones: usize) -> Result<Vec<RgbS>, String> {
+        if self.fill == Fill::Random {
+            let mut rng = rand::thread_rng();
+            return Ok((0..zones)
+                .map(|_| RgbS {
+                    r: rng.gen_range(0..=255),
+                    g: rng.gen_range(0..=255),
+                    b: rng.gen_range(0..=255),
+                })
+                .collect());
+        }
+
+        let colors = self
+            .colors
+            .iter()
+            .map(|color| parse_color(color))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("preset {}: {err}", self.name))?;
+        if colors.is_empty() {
+            return Err(format!("preset {} has no colors", self.name));
+        }
+        Ok((0..zones).map(|idx| colors[idx % colors.len()]).collect())
+    }
+
+    /// Whether the preset is called `name`, ignoring case and treating `-` and `_` as spaces so
+    /// `neon-city` finds "Neon City".
+    pub fn is_named(&self, name: &str) -> bool {
+        let normalize = |name: &str| {
+            name.trim()
+                .to_lowercase()
+                .replace(['-', '_'], " ")
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        normalize(&self.name) == normalize(name)
+    }
+}
+
+/// Presets that ship with the tool, in the order they are shown.
+pub fn builtin_presets() -> Vec<Preset> {
+    vec![
+        Preset::builtin(
+            "Spectrum",
+            &[
+                0xFF0000, 0xFF7F00, 0xFFFF00, 0x00FF00, 0x0000FF, 0x4B0082, 0x9400D3, 0xFFFFFF,
+            ],
+        ),
+        Preset::builtin("Ember", &[0xF2662B]),
+        Preset {
+            name: "Entropy".to_string(),
+            colors: Vec::new(),
+            fill: Fill::Random,
+        },
+        Preset::builtin(
+            "Matrix",
+            &[
+                0x00FF66, 0x00CC44, 0x009933, 0x006622, 0x00FF99, 0x00CC66, 0x009944, 0x006633,
+            ],
+        ),
+        Preset::builtin(
+            "Azure",
+            &[
+                0x0C0CFF, 0x1A1AFF, 0x2B2BFF, 0x3C3CFF, 0x1A4FFF, 0x2E5FFF, 0x4370FF, 0x5880FF,
+            ],
+        ),
+        Preset::builtin(
+            "Void",
+            &[
+                0x050505, 0x0A0A0A, 0x101010, 0x161616, 0x101010, 0x0A0A0A, 0x050505, 0x080808,
+            ],
+        ),
+        Preset::builtin(
+            "Neon City",
+            &[
+                0xFF00FF, 0x00FFFF, 0x9400D3, 0xFF0099, 0x00CCFF, 0x8A2BE2, 0xFF1493, 0x00BFFF,
+            ],
+        ),
+        Preset::builtin(
+            "Solar Flare",
+            &[
+                0xFF4500, 0xFF8C00, 0xFFA500, 0xFFD700, 0xFF6347, 0xFF7F50, 0xFFD700, 0xFFFF00,
+            ],
+        ),
+        Preset::builtin(
+            "Abyss",
+            &[
+                0x000080, 0x00008B, 0x191970, 0x0000CD, 0x4169E1, 0x0000FF, 0x1E90FF, 0x00BFFF,
+            ],
+        ),
+        Preset::builtin(
+            "Canopy",
+            &[
+                0x006400, 0x228B22, 0x32CD32, 0x90EE90, 0x008000, 0x6B8E23, 0x556B2F, 0x8FBC8F,
+            ],
+        ),
+        Preset::builtin(
+            "Dreamscape",
+            &[
+                0xFFB6C1, 0xFF69B4, 0xE6E6FA, 0xD8BFD8, 0xDDA0DD, 0xEE82EE, 0xFFC0CB, 0xFFA07A,
+            ],
+        ),
+    ]
+}
+
+/// Whether `name` belongs to a built-in preset, which can't be changed.
+pub fn is_builtin(name: &str) -> bool {
+    builtin_presets().iter().any(|preset| preset.is_named(name))
+}
+
+/// Built-in presets followed by the user's presets from `config`.
+pub fn all_presets(config: &Config) -> Vec<Preset> {
+    let mut presets = builtin_presets();
+    presets.extend(config.presets.iter().cloned());
+    presets
+}
+
+/// Look up a preset by name, see [`Preset::is_named`].
+pub fn find_preset(name: &str) -> Result<Preset, String> {
+    let presets = all_presets(&Config::load()?);
+    if let Some(preset) = presets.iter().find(|preset| preset.is_named(name)) {
+        return Ok(preset.clone());
+    }
+
+    let names: Vec<_> = presets.iter().map(|preset| preset.name.as_str()).collect();
+    Err(format!(
+        "no preset named `{name}`, available presets: {}",
+        names.join(", ")
+    ))
+}
+
+/// Save a user preset, replacing an existing user preset with the same name.
+pub fn save_preset(preset: &Preset) -> Result<(), String> {
+    update_user_presets(|presets| insert(presets, preset.clone()))
+}
+
+/// Rename a user preset.
+pub fn rename_preset(name: &str, new_name: &str) -> Result<(), String> {
+    update_user_presets(|presets| rename(presets, name, new_name))
+}
+
+/// Delete a user preset.
+pub fn delete_preset(name: &str) -> Result<(), String> {
+    update_user_presets(|presets| {
+        let idx = position(presets, name)?;
+        presets.remove(idx);
+        Ok(())
+    })
+}
+
+/// Move a user preset `offset` places down the list (up if negative).
+pub fn move_preset(name: &str, offset: isize) -> Result<(), String> {
+    update_user_presets(|presets| shift(presets, name, offset))
+}
+
+fn update_user_presets(
+    update: impl FnOnce(&mut Vec<Preset>) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut config = Config::load()?;
+    update(&mut config.presets)?;
+    config.save()
+}
+
+fn position(presets: &[Preset], name: &str) -> Result<usize, String> {
+    if is_builtin(name) {
+        return Err(format!("preset {name} is built in and can't be changed"));
+    }
+    presets
+        .iter()
+        .position(|preset| preset.is_named(name))
+        .ok_or_else(|| format!("no preset named `{name}`"))
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("preset name must not be empty".to_string());
+    }
+    if is_builtin(name) {
+        return Err(format!(
+            "a built-in preset is already named {}",
+            name.trim()
+        ));
+    }
+    Ok(())
+}
+
+fn insert(presets: &mut Vec<Preset>, mut preset: Preset) -> Result<(), String> {
+    check_name(&preset.name)?;
+    preset.name = preset.name.trim().to_string();
+    match presets
+        .iter_mut()
+        .find(|existing| existing.is_named(&preset.name))
+    {
+        Some(existing) => *existing = preset,
+        None => presets.push(preset),
+    }
+    Ok(())
+}
+
+fn rename(presets: &mut [Preset], name: &str, new_name: &str) -> Result<(), String> {
+    let idx = position(presets, name)?;
+    check_name(new_name)?;
+    if presets
+        .iter()
+        .enumerate()
+        .any(|(other, preset)| other != idx && preset.is_named(new_name))
+    {
+        return Err(format!("a preset is already named {}", new_name.trim()));
+    }
+    presets[idx].name = new_name.trim().to_string();
+    Ok(())
+}
+
+fn shift(presets: &mut [Preset], name: &str, offset: isize) -> Result<(), String> {
+    let idx = position(presets, name)?;
+    let target = idx
+        .saturating_add_signed(offset)
+        .min(presets.len().saturating_sub(1));
+    if target < idx {
+        presets[target..=idx].rotate_right(1);
+    } else {
+        presets[idx..=target].rotate_left(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(presets: &[Preset]) -> Vec<&str> {
+        presets.iter().map(|preset| preset.name.as_str()).collect()
+    }
+
+    #[test]
+    fn builtins_render() {
+        for preset in builtin_presets() {
+            assert_eq!(preset.render(5).unwrap().len(), 5, "{}", preset.name);
+        }
+
+        let ember = builtin_presets().into_iter().find(|p| p.is_named("ember"));
+        let colors: Vec<_> = ember
+            .unwrap()
+            .render(3)
+            .unwrap()
+            .into_iter()
+            .map(rgb_to_hex_string)
+            .collect();
+        assert_eq!(colors, ["#F2662B"; 3]);
+    }
+
+    #[test]
+    fn names_are_forgiving() {
+        let preset = Preset::new("Neon City", &[]);
+        assert!(preset.is_named("neon-city"));
+        assert!(preset.is_named("  NEON_city "));
+        assert!(!preset.is_named("neon"));
+    }
+
+    #[test]
+    fn user_presets_can_be_managed() {
+        let mut presets = Vec::new();
+        let red = RgbS { r: 255, g: 0, b: 0 };
+        for name in ["Desk", "Night", "Party"] {
+            insert(&mut presets, Preset::new(name, &[red])).unwrap();
+        }
+        assert!(insert(&mut presets, Preset::new("spectrum", &[red])).is_err());
+        assert!(insert(&mut presets, Preset::new(" ", &[red])).is_err());
+
+        insert(&mut presets, Preset::new("night", &[])).unwrap();
+        assert_eq!(names(&presets), ["Desk", "night", "Party"]);
+
+        shift(&mut presets, "party", -2).unwrap();
+        assert_eq!(names(&presets), ["Party", "Desk", "night"]);
+        shift(&mut presets, "Party", 5).unwrap();
+        assert_eq!(names(&presets), ["Desk", "night", "Party"]);
+
+        rename(&mut presets, "night", "Late").unwrap();
+        assert!(rename(&mut presets, "Late", "Desk").is_err());
+        assert!(rename(&mut presets, "Ember", "Fire").is_err());
+        assert_eq!(names(&presets), ["Desk", "Late", "Party"]);
+    }
+}
@@ -0,0 +1,184 @@
+use clap::ValueEnum;
+use framework_lib::chromium_ec::CrosEcDriverType;
+use framework_lib::chromium_ec::commands::RgbS;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::{parse_color, rgb_to_hex_string};
+
+fn default_zones() -> usize {
+    8
+}
+
+/// A named lighting setup that can be saved and re-applied later.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    /// Colors in any format accepted by [`parse_color`], repeated to fill all zones.
+    pub colors: Vec<String>,
+
+    /// First key index to program.
+    #[serde(default)]
+    pub start_key: u8,
+
+    /// Number of zones to program.
+    #[serde(default = "default_zones")]
+    pub zones: usize,
+
+    /// EC driver (`portio`, `cros-ec`, `windows` or `mock`). Chosen automatically if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub driver: Option<String>,
+}
+
+impl Profile {
+    pub fn new(start_key: u8, colors: &[RgbS], driver: Option<CrosEcDriverType>) -> Self {
+        Self {
+            colors: colors.iter().copied().map(rgb_to_hex_string).collect(),
+            start_key,
+            zones: colors.len(),
+            driver: driver.map(driver_name),
+        }
+    }
+
+    /// Parse the colors, repeating them to fill `zones` entries.
+    pub fn rgb_colors(&self) -> Result<Vec<RgbS>, String> {
+        let colors = self
+            .colors
+            .iter()
+            .map(|color| parse_color(color))
+            .collect::<Result<Vec<_>, _>>()?;
+        if colors.is_empty() {
+            return Err("profile has no colors".to_string());
+        }
+
+        Ok((0..self.zones)
+            .map(|idx| colors[idx % colors.len()])
+            .collect())
+    }
+
+    /// The driver the profile asks for, `None` for automatic selection.
+    pub fn driver(&self) -> Result<Option<CrosEcDriverType>, String> {
+        self.driver.as_deref().map(parse_driver).transpose()
+    }
+}
+
+/// Name of a driver as accepted on the command line (e.g. `cros-ec`).
+pub fn driver_name(driver: CrosEcDriverType) -> String {
+    driver
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_else(|| format!("{driver:?}"))
+}
+
+/// Parse a driver name as produced by [`driver_name`].
+pub fn parse_driver(name: &str) -> Result<CrosEcDriverType, String> {
+    CrosEcDriverType::from_str(name, true).map_err(|_| format!("unknown EC driver `{name}`"))
+}
+
+/// Names of all saved profiles in alphabetical order.
+pub fn list_profiles() -> Result<Vec<String>, String> {
+    Ok(Config::load()?.profiles.into_keys().collect())
+}
+
+/// Load a saved profile by name.
+pub fn load_profile(name: &str) -> Result<Profile, String> {
+    Config::load()?
+        .profiles
+        .remove(name)
+        .ok_or_else(|| format!("no profile named `{name}`"))
+}
+
+/// Save a profile under `name`, replacing any existing profile with that name.
+pub fn save_profile(name: &str, profile: &Profile) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("profile name must not be empty".to_string());
+    }
+
+    let mut config = Config::load()?;
+    config.profiles.insert(name.to_string(), profile.clone());
+    config.active_profile = Some(name.to_string());
+    config.save()
+}
+
+/// Delete a saved profile.
+pub fn delete_profile(name: &str) -> Result<(), String> {
+    let mut config = Config::load()?;
+    if config.profiles.remove(name).is_none() {
+        return Err(format!("no profile named `{name}`"));
+    }
+    if config.active_profile.as_deref() == Some(name) {
+        config.active_profile = None;
+    }
+    config.save()
+}
+
+/// Remember `name` as the most recently applied profile.
+pub fn set_active_profile(name: &str) -> Result<(), String> {
+    let mut config = Config::load()?;
+    config.active_profile = Some(name.to_string());
+    config.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_repeat_to_fill_zones() {
+        let profile = Profile {
+            colors: vec!["#FF0000".to_string(), "0x0000FF".to_string()],
+            start_key: 0,
+            zones: 5,
+            driver: None,
+        };
+
+        let colors: Vec<String> = profile
+            .rgb_colors()
+            .unwrap()
+            .into_iter()
+            .map(rgb_to_hex_string)
+            .collect();
+        assert_eq!(
+            colors,
+            ["#FF0000", "#0000FF", "#FF0000", "#0000FF", "#FF0000"]
+        );
+    }
+
+    #[test]
+    fn driver_names_round_trip() {
+        for driver in CrosEcDriverType::value_variants() {
+            assert_eq!(parse_driver(&driver_name(*driver)), Ok(*driver));
+        }
+        assert!(parse_driver("serial").is_err());
+    }
+
+    #[test]
+    fn config_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("fwd_rgb-test-{}", std::process::id()))
+            .join("config.toml");
+
+        let mut config = Config::default();
+        config.profiles.insert(
+            "desk".to_string(),
+            Profile::new(
+                2,
+                &[RgbS { r: 1, g: 2, b: 3 }],
+                Some(CrosEcDriverType::CrosEc),
+            ),
+        );
+        config.active_profile = Some("desk".to_string());
+        config.save_to(&path).unwrap();
+
+        let loaded = Config::load_from(&path).unwrap();
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+
+        assert_eq!(loaded, config);
+        assert_eq!(loaded.profiles["desk"].driver.as_deref(), Some("cros-ec"));
+        assert_eq!(
+            Config::load_from(&path).unwrap(),
+            Config::default(),
+            "missing file loads defaults"
+        );
+    }
+}
@@ -0,0 +1,538 @@
+//! Lighting that follows the clock: rules that show a profile, preset, effect or fade, or turn
+//! the lights off, at certain times of day. Stored as `[[schedule]]` in the config file.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, Local, NaiveDateTime, Timelike, Weekday};
+use framework_lib::chromium_ec::commands::RgbS;
+use framework_lib::chromium_ec::{CrosEc, EcError};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::config::Config;
+use crate::effects::{self, Effect, EffectClock, EffectKind};
+use crate::layout::Layout;
+use crate::preset;
+use crate::thermal::{ColorStop, Gradient};
+use crate::{ColorPipeline, parse_color, rgb_to_hex_string};
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+/// How often static lighting is checked against the clock, fades advance at this pace.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+const EFFECT_FPS: f32 = 30.0;
+
+/// Time of day, written as `HH:MM`. `24:00` is the end of the day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeOfDay {
+    minutes: u16,
+}
+
+impl TimeOfDay {
+    pub fn new(hour: u16, minute: u16) -> Result<Self, String> {
+        let minutes = u32::from(hour) * 60 + u32::from(minute);
+        if minute >= 60 || minutes > 24 * 60 {
+            return Err(format!("invalid time of day {hour:02}:{minute:02}"));
+        }
+        Ok(Self {
+            minutes: minutes as u16,
+        })
+    }
+
+    fn seconds(self) -> u32 {
+        u32::from(self.minutes) * 60
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid time of day `{value}`, expected HH:MM");
+        let (hour, minute) = value.trim().split_once(':').ok_or_else(invalid)?;
+        let hour = hour.parse().map_err(|_| invalid())?;
+        let minute = minute.parse().map_err(|_| invalid())?;
+        Self::new(hour, minute)
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.minutes / 60, self.minutes % 60)
+    }
+}
+
+impl Serialize for TimeOfDay {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeOfDay {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+fn default_effect_color() -> String {
+    "#FFFFFF".to_string()
+}
+
+fn default_speed() -> f32 {
+    1.0
+}
+
+/// An effect as written in a rule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EffectAction {
+    pub kind: EffectKind,
+    #[serde(default = "default_effect_color")]
+    pub color: String,
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+}
+
+/// What a rule shows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// A saved profile, e.g. `action = { profile = "night" }`.
+    Profile(String),
+    /// A built-in or user preset filling the layout.
+    Preset(String),
+    Effect(EffectAction),
+    /// Blend through the colors over the rule's time span, like a sunrise.
+    Fade(Vec<String>),
+    /// Turn the lights off, `action = "off"`.
+    Off,
+}
+
+/// A time span and what to show during it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    /// Days the span starts on (e.g. `["sat", "sun"]`), every day if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+
+    /// Start of the span.
+    pub from: TimeOfDay,
+
+    /// End of the span, on the next day if it isn't after `from`. A span from a time to the
+    /// same time lasts a whole day.
+    pub to: TimeOfDay,
+
+    pub action: Action,
+
+    /// Brightness in percent during the span, overrides the `[color]` config section.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+}
+
+impl Rule {
+    /// How far through its span the rule is at `now`, from 0 to 1, or `None` outside of it.
+    pub fn progress(&self, now: NaiveDateTime) -> Option<f32> {
+        let (from, to) = (self.from.seconds(), self.to.seconds());
+        let length = if to > from {
+            to - from
+        } else {
+            to + SECONDS_PER_DAY - from
+        };
+
+        let time = now.num_seconds_from_midnight();
+        let (elapsed, day) = if time >= from {
+            (time - from, now.weekday())
+        } else {
+            // Started the day before
+            (time + SECONDS_PER_DAY - from, now.weekday().pred())
+        };
+        if elapsed >= length || !(self.days.is_empty() || self.days.contains(&day)) {
+            return None;
+        }
+        Some(elapsed as f32 / length as f32)
+    }
+
+    fn describe(&self) -> String {
+        let action = match &self.action {
+            Action::Profile(name) => format!("profile {name}"),
+            Action::Preset(name) => format!("preset {name}"),
+            Action::Effect(effect) => format!("{} effect", effect.kind.label()),
+            Action::Fade(colors) => format!("fade {}", colors.join(" → ")),
+            Action::Off => "lights off".to_string(),
+        };
+        format!("{action} ({}–{})", self.from, self.to)
+    }
+}
+
+/// Lighting of a rule with its profile, preset or colors resolved.
+#[derive(Debug, Clone)]
+enum Lighting {
+    Colors {
+        start_key: u8,
+        colors: Vec<RgbS>,
+    },
+    Effect(Effect),
+    /// Colors blended over positions 0 to 1.
+    Fade(Gradient),
+    Off,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    lighting: Lighting,
+    brightness: Option<u8>,
+    description: String,
+}
+
+impl Entry {
+    fn new(lighting: Lighting, brightness: Option<u8>, description: String) -> Self {
+        Self {
+            lighting,
+            brightness,
+            description,
+        }
+    }
+}
+
+/// Rules ready to be followed, see [`Scheduler`].
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    rules: Vec<Rule>,
+    entries: Vec<Entry>,
+    /// Shown outside of all rules: the active profile, if there is one.
+    fallback: Option<Entry>,
+}
+
+impl Schedule {
+    /// Look up the profiles and presets of `rules` in `config`, filling `layout`.
+    pub fn new(rules: &[Rule], config: &Config, layout: &Layout) -> Result<Self, String> {
+        let entries = rules
+            .iter()
+            .map(|rule| {
+                let lighting = resolve(&rule.action, config, layout)
+                    .map_err(|err| format!("schedule rule {}: {err}", rule.describe()))?;
+                if rule.brightness.is_some_and(|brightness| brightness > 100) {
+                    return Err(format!(
+                        "schedule rule {}: brightness must be between 0 and 100",
+                        rule.describe()
+                    ));
+                }
+                Ok(Entry::new(lighting, rule.brightness, rule.describe()))
+            })
+            .collect::<Result<_, String>>()?;
+
+        let fallback = match &config.active_profile {
+            Some(name) => {
+                let action = Action::Profile(name.clone());
+                let lighting = resolve(&action, config, layout)
+                    .map_err(|err| format!("active profile: {err}"))?;
+                Some(Entry::new(lighting, None, format!("profile {name}")))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            rules: rules.to_vec(),
+            entries,
+            fallback,
+        })
+    }
+
+    /// The rule in effect at `now` and how far through it `now` is. Later rules win over
+    /// earlier ones, so exceptions go after the rules they override.
+    pub fn active(&self, now: NaiveDateTime) -> Option<(usize, f32)> {
+        self.rules
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(idx, rule)| Some((idx, rule.progress(now)?)))
+    }
+}
+
+fn resolve(action: &Action, config: &Config, layout: &Layout) -> Result<Lighting, String> {
+    Ok(match action {
+        Action::Profile(name) => {
+            let profile = config
+                .profiles
+                .get(name)
+                .ok_or_else(|| format!("no profile named `{name}`"))?;
+            Lighting::Colors {
+                start_key: profile.start_key,
+                colors: profile.rgb_colors()?,
+            }
+        }
+        Action::Preset(name) => {
+            let preset = preset::all_presets(config)
+                .into_iter()
+                .find(|preset| preset.is_named(name))
+                .ok_or_else(|| format!("no preset named `{name}`"))?;
+            Lighting::Colors {
+                start_key: layout.start_key,
+                colors: preset.render(layout.zones)?,
+            }
+        }
+        Action::Effect(effect) => Lighting::Effect(Effect {
+            kind: effect.kind,
+            color: parse_color(&effect.color)?,
+            speed: effect.speed,
+        }),
+        Action::Fade(colors) => {
+            if colors.is_empty() {
+                return Err("a fade needs at least one color".to_string());
+            }
+            let last = colors.len().saturating_sub(1).max(1) as f32;
+            let stops: Vec<_> = colors
+                .iter()
+                .enumerate()
+                .map(|(idx, color)| ColorStop {
+                    temp: idx as f32 / last,
+                    color: color.clone(),
+                })
+                .collect();
+            Lighting::Fade(Gradient::new(&stops)?)
+        }
+        Action::Off => Lighting::Off,
+    })
+}
+
+/// Source of the local time, so a schedule can be tested at any time of day.
+pub trait Clock {
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// The local time of the system.
+pub struct LocalClock;
+
+impl Clock for LocalClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+/// Shows the lighting of a [`Schedule`] as time passes.
+pub struct Scheduler {
+    schedule: Schedule,
+    layout: Layout,
+    pipeline: ColorPipeline,
+    /// Rule shown last, `None` for the fallback, and the colors written for it.
+    shown: Option<(Option<usize>, Vec<[u8; 3]>)>,
+    effect_clock: EffectClock,
+}
+
+impl Scheduler {
+    /// Follow `schedule` on `layout`, showing everything through `pipeline`.
+    pub fn new(schedule: Schedule, layout: Layout, pipeline: ColorPipeline) -> Self {
+        Self {
+            schedule,
+            layout,
+            pipeline,
+            shown: None,
+            effect_clock: EffectClock::new(),
+        }
+    }
+
+    /// Show the lighting for `now`, returning how long until the next update is due.
+    pub fn update(&mut self, ec: &CrosEc, now: NaiveDateTime) -> Result<Duration, EcError> {
+        let (rule, progress) = match self.schedule.active(now) {
+            Some((idx, progress)) => (Some(idx), progress),
+            None => (None, 0.0),
+        };
+        let entry = match rule {
+            Some(idx) => &self.schedule.entries[idx],
+            None => match &self.schedule.fallback {
+                Some(fallback) => fallback,
+                // Nothing to show, leave the lights as they are
+                None => return Ok(UPDATE_INTERVAL),
+            },
+        };
+
+        let changed = self.shown.as_ref().is_none_or(|(shown, _)| *shown != rule);
+        if changed {
+            log::info!("showing {}", entry.description);
+            self.effect_clock = EffectClock::new();
+        }
+
+        let zones = self.layout.zones;
+        let (start_key, colors, next_update) = match &entry.lighting {
+            Lighting::Colors { start_key, colors } => (*start_key, colors.clone(), UPDATE_INTERVAL),
+            Lighting::Effect(effect) => {
+                let t = self.effect_clock.tick(effect, || effects::fan_rpm(ec));
+                let frame = effect.frame_for(t, &self.layout);
+                let frame_time = Duration::from_secs_f32(1.0 / EFFECT_FPS);
+                (self.layout.start_key, frame, frame_time)
+            }
+            Lighting::Fade(gradient) => {
+                let color = gradient.color_at(progress);
+                (self.layout.start_key, vec![color; zones], UPDATE_INTERVAL)
+            }
+            Lighting::Off => (
+                self.layout.start_key,
+                vec![RgbS::default(); zones],
+                UPDATE_INTERVAL,
+            ),
+        };
+
+        let mut pipeline = self.pipeline;
+        if let Some(brightness) = entry.brightness {
+            pipeline.brightness = brightness;
+        }
+        let colors = pipeline.apply_all(colors);
+        let bytes: Vec<_> = colors
+            .iter()
+            .map(|color| [color.r, color.g, color.b])
+            .collect();
+        if !changed
+            && self
+                .shown
+                .as_ref()
+                .is_some_and(|(_, shown)| *shown == bytes)
+        {
+            return Ok(next_update);
+        }
+
+        log::debug!(
+            "writing {}",
+            colors
+                .iter()
+                .copied()
+                .map(rgb_to_hex_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        ec.rgbkbd_set_color(start_key, colors)?;
+        self.shown = Some((rule, bytes));
+        Ok(next_update)
+    }
+}
+
+/// Follow the schedule by `clock`.
+///
+/// Runs until `duration` has elapsed (if given) or `running` is cleared.
+pub fn run(
+    ec: &CrosEc,
+    scheduler: &mut Scheduler,
+    clock: &impl Clock,
+    duration: Option<Duration>,
+    running: &AtomicBool,
+) -> Result<(), EcError> {
+    let start = Instant::now();
+
+    while running.load(Ordering::Relaxed) {
+        if duration.is_some_and(|duration| start.elapsed() >= duration) {
+            break;
+        }
+
+        let wait = scheduler.update(ec, clock.now())?;
+        std::thread::sleep(wait);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Profile;
+    use crate::rgb_from_u32;
+    use crate::tests::mock_ec;
+    use framework_lib::chromium_ec::CrosEcDriverType;
+    use framework_lib::chromium_ec::mock;
+
+    /// 2026-10-17 is a Saturday.
+    fn at(date: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn rule(days: &[Weekday], from: &str, to: &str, action: Action) -> Rule {
+        Rule {
+            days: days.to_vec(),
+            from: from.parse().unwrap(),
+            to: to.parse().unwrap(),
+            action,
+            brightness: None,
+        }
+    }
+
+    #[test]
+    fn times_of_day_parse() {
+        let time: TimeOfDay = "07:30".parse().unwrap();
+        assert_eq!(time.to_string(), "07:30");
+        assert_eq!("24:00".parse::<TimeOfDay>().unwrap().to_string(), "24:00");
+        for invalid in ["7", "24:01", "12:60", "noon"] {
+            assert!(invalid.parse::<TimeOfDay>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn rules_span_midnight_and_days() {
+        let night = rule(&[], "22:00", "07:00", Action::Off);
+        assert_eq!(night.progress(at("2026-10-17 22:00")), Some(0.0));
+        assert_eq!(night.progress(at("2026-10-18 02:30")), Some(0.5));
+        assert_eq!(night.progress(at("2026-10-18 07:00")), None);
+        assert_eq!(night.progress(at("2026-10-18 12:00")), None);
+
+        // Friday night counts as Friday, Saturday all day as Saturday
+        let friday = rule(&[Weekday::Fri], "22:00", "07:00", Action::Off);
+        assert!(friday.progress(at("2026-10-17 03:00")).is_some());
+        assert!(friday.progress(at("2026-10-17 23:00")).is_none());
+        let weekend = rule(&[Weekday::Sat, Weekday::Sun], "00:00", "00:00", Action::Off);
+        assert!(weekend.progress(at("2026-10-17 12:00")).is_some());
+        assert!(weekend.progress(at("2026-10-19 12:00")).is_none());
+    }
+
+    #[test]
+    fn scheduler_follows_the_clock() {
+        let _ec = mock_ec();
+        let ec = crate::open_ec(Some(CrosEcDriverType::Mock)).unwrap();
+        let layout = Layout {
+            zones: 2,
+            ..Layout::FAN_RING
+        };
+
+        let mut config = Config::default();
+        let warm = [rgb_from_u32(0xFF8800), rgb_from_u32(0x884400)];
+        config
+            .profiles
+            .insert("day".to_string(), Profile::new(0, &warm, None));
+        config.active_profile = Some("day".to_string());
+        let rules = [
+            rule(
+                &[],
+                "06:00",
+                "07:00",
+                Action::Fade(vec!["#000000".into(), "#FF0000".into()]),
+            ),
+            Rule {
+                brightness: Some(50),
+                ..rule(&[], "22:00", "06:00", Action::Preset("ember".to_string()))
+            },
+            rule(&[Weekday::Sat], "23:00", "24:00", Action::Off),
+        ];
+        let schedule = Schedule::new(&rules, &config, &layout).unwrap();
+        let mut scheduler = Scheduler::new(schedule, layout, ColorPipeline::default());
+        let mut shown = |time| {
+            scheduler.update(&ec, at(time)).unwrap();
+            let colors = mock::rgb_colors(0, 2);
+            colors
+                .into_iter()
+                .map(rgb_to_hex_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(shown("2026-10-16 06:30"), ["#800000", "#800000"]);
+        assert_eq!(shown("2026-10-16 12:00"), ["#FF8800", "#884400"]);
+        assert_eq!(shown("2026-10-16 22:30"), ["#793316", "#793316"]);
+        assert_eq!(shown("2026-10-17 23:30"), ["#000000", "#000000"]);
+
+        // Unchanged lighting isn't written again
+        mock::clear_requests();
+        shown("2026-10-16 12:00");
+        shown("2026-10-16 12:01");
+        assert_eq!(mock::requests().len(), 1);
+
+        let missing = [rule(&[], "00:00", "01:00", Action::Profile("gone".into()))];
+        assert!(Schedule::new(&missing, &config, &layout).is_err());
+    }
+}
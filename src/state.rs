@@ -0,0 +1,168 @@
+//! What the lights were last set to, so the CLI and GUI can show and resume it.
+//!
+//! Most firmware can't report the colors it shows, so colors written by the CLI and GUI are
+//! remembered per device in a state file. The daemon keeps its own state, see
+//! [`DaemonState`](crate::daemon::DaemonState).
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use framework_lib::chromium_ec::commands::RgbS;
+use framework_lib::chromium_ec::{CrosEcDriverType, available_drivers};
+use serde::{Deserialize, Serialize};
+
+use crate::profile::driver_name;
+use crate::{parse_color, rgb_to_hex_string};
+
+/// Environment variable that overrides the location of the state file.
+pub const STATE_PATH_ENV: &str = "FWD_RGB_STATE";
+
+/// Colors last written to a device.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub start_key: u8,
+
+    /// Colors as they were requested, before brightness and gamma.
+    pub colors: Vec<String>,
+
+    /// When the colors were written, in seconds since the Unix epoch.
+    pub applied_at: u64,
+}
+
+impl Frame {
+    pub fn new(start_key: u8, colors: &[RgbS]) -> Self {
+        Self {
+            start_key,
+            colors: colors.iter().copied().map(rgb_to_hex_string).collect(),
+            applied_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+        }
+    }
+
+    pub fn rgb_colors(&self) -> Result<Vec<RgbS>, String> {
+        self.colors.iter().map(|color| parse_color(color)).collect()
+    }
+}
+
+/// Contents of the state file.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    /// Last frame of each device, keyed by [`device_name`].
+    #[serde(default)]
+    pub devices: BTreeMap<String, Frame>,
+}
+
+impl State {
+    /// Location of the state file.
+    ///
+    /// `$FWD_RGB_STATE` if set, otherwise `fwd_rgb/state.toml` in the platform state directory
+    /// (e.g. `~/.local/state` on Linux) or local data directory.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(STATE_PATH_ENV) {
+            return Some(PathBuf::from(path));
+        }
+        dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|dir| dir.join("fwd_rgb").join("state.toml"))
+    }
+
+    /// Load the state from the default location.
+    pub fn load() -> Result<Self, String> {
+        let path = Self::default_path().ok_or("unable to determine the state directory")?;
+        Self::load_from(&path)
+    }
+
+    /// Load the state from `path`. A missing file yields an empty state.
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(format!("failed to read `{}`: {err}", path.display())),
+        };
+
+        toml::from_str(&contents)
+            .map_err(|err| format!("invalid state file `{}`: {err}", path.display()))
+    }
+
+    /// Save the state to the default location.
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::default_path().ok_or("unable to determine the state directory")?;
+        self.save_to(&path)
+    }
+
+    /// Save the state to `path`, creating parent directories as needed.
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|err| format!("failed to create `{}`: {err}", parent.display()))?;
+        }
+
+        let contents =
+            toml::to_string_pretty(self).map_err(|err| format!("failed to encode state: {err}"))?;
+        std::fs::write(path, contents)
+            .map_err(|err| format!("failed to write `{}`: {err}", path.display()))
+    }
+}
+
+/// Name a device is remembered by: its driver, or the platform default driver for `None`.
+pub fn device_name(driver: Option<CrosEcDriverType>) -> String {
+    driver
+        .or_else(|| available_drivers().first().copied())
+        .map_or_else(|| "none".to_string(), driver_name)
+}
+
+/// Remember `colors` as shown on the device behind `driver`.
+pub fn record(
+    driver: Option<CrosEcDriverType>,
+    start_key: u8,
+    colors: &[RgbS],
+) -> Result<(), String> {
+    let mut state = State::load()?;
+    state
+        .devices
+        .insert(device_name(driver), Frame::new(start_key, colors));
+    state.save()
+}
+
+/// The colors last written to the device behind `driver`, if any were recorded.
+pub fn last_frame(driver: Option<CrosEcDriverType>) -> Result<Option<Frame>, String> {
+    Ok(State::load()?.devices.remove(&device_name(driver)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rgb_from_u32;
+
+    #[test]
+    fn state_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("fwd_rgb-state-test-{}", std::process::id()))
+            .join("state.toml");
+
+        let colors = [rgb_from_u32(0xFF0000), rgb_from_u32(0x0000FF)];
+        let mut state = State::default();
+        state
+            .devices
+            .insert("mock".to_string(), Frame::new(3, &colors));
+        state.save_to(&path).unwrap();
+
+        let loaded = State::load_from(&path).unwrap();
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+        assert_eq!(loaded, state);
+        let frame = &loaded.devices["mock"];
+        assert_eq!(frame.colors, ["#FF0000", "#0000FF"]);
+        assert_eq!(
+            frame
+                .rgb_colors()
+                .unwrap()
+                .into_iter()
+                .map(rgb_to_hex_string)
+                .collect::<Vec<_>>(),
+            frame.colors
+        );
+        assert!(frame.applied_at > 0);
+    }
+}
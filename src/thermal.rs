@@ -0,0 +1,187 @@
+//! Lighting that follows the temperature reported by the EC.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use framework_lib::chromium_ec::commands::RgbS;
+use framework_lib::chromium_ec::{CrosEc, EcError};
+use framework_lib::power::{self, TempReading};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::layout::Layout;
+use crate::{ColorPipeline, parse_color, rgb_to_hex_string};
+
+/// Settings for the thermal lighting mode, stored in the `[thermal]` config section.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThermalSettings {
+    /// Sensors to watch, the hottest one wins. All sensors are used if none of them exist.
+    pub sensors: Vec<String>,
+    /// Seconds between updates.
+    pub interval: f32,
+    /// Colors at given temperatures, blended in between.
+    pub gradient: Vec<ColorStop>,
+}
+
+impl Default for ThermalSettings {
+    fn default() -> Self {
+        let stop = |temp, color: &str| ColorStop {
+            temp,
+            color: color.to_string(),
+        };
+        Self {
+            // Framework Desktop, APU die and ambient
+            sensors: vec!["F75303_APU".to_string(), "F75303_AMB".to_string()],
+            interval: 2.0,
+            gradient: vec![
+                stop(40.0, "#0000FF"),
+                stop(60.0, "#00FF00"),
+                stop(75.0, "#FFA500"),
+                stop(90.0, "#FF0000"),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop {
+    /// Temperature in degrees Celsius.
+    pub temp: f32,
+    pub color: String,
+}
+
+/// Parsed gradient mapping temperatures to colors.
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    stops: Vec<(f32, RgbS)>,
+}
+
+impl Gradient {
+    pub fn new(stops: &[ColorStop]) -> Result<Self, String> {
+        let mut stops = stops
+            .iter()
+            .map(|stop| Ok((stop.temp, parse_color(&stop.color)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        if stops.is_empty() {
+            return Err("thermal gradient needs at least one color".to_string());
+        }
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self { stops })
+    }
+
+    /// Color for `temp`, clamped to the first and last stop.
+    pub fn color_at(&self, temp: f32) -> RgbS {
+        let upper = self.stops.partition_point(|(stop, _)| *stop <= temp);
+        if upper == 0 {
+            return self.stops[0].1;
+        }
+        if upper == self.stops.len() {
+            return self.stops[upper - 1].1;
+        }
+
+        let (from_temp, from) = self.stops[upper - 1];
+        let (to_temp, to) = self.stops[upper];
+        let t = (temp - from_temp) / (to_temp - from_temp);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        RgbS {
+            r: mix(from.r, to.r),
+            g: mix(from.g, to.g),
+            b: mix(from.b, to.b),
+        }
+    }
+}
+
+/// The hottest reading among `sensors`, or among all sensors if none of them report a
+/// temperature.
+pub fn hottest<'a>(readings: &'a [TempReading], sensors: &[String]) -> Option<(&'a str, u8)> {
+    let hottest_of = |wanted: &dyn Fn(&TempReading) -> bool| {
+        readings
+            .iter()
+            .filter(|reading| wanted(reading))
+            .filter_map(|reading| Some((reading.name.as_str(), reading.state.celsius()?)))
+            .max_by_key(|(_, temp)| *temp)
+    };
+
+    hottest_of(&|reading| sensors.contains(&reading.name)).or_else(|| hottest_of(&|_| true))
+}
+
+/// Color the zones of `layout` by temperature as configured in `settings`.
+///
+/// Runs until `duration` has elapsed (if given) or `running` is cleared.
+pub fn run(
+    ec: &CrosEc,
+    layout: &Layout,
+    settings: &ThermalSettings,
+    pipeline: &ColorPipeline,
+    duration: Option<Duration>,
+    running: &AtomicBool,
+) -> Result<(), Error> {
+    let gradient = Gradient::new(&settings.gradient).map_err(Error::invalid)?;
+    let interval = Duration::from_secs_f32(settings.interval.max(0.1));
+    let start = Instant::now();
+
+    while running.load(Ordering::Relaxed) {
+        if duration.is_some_and(|duration| start.elapsed() >= duration) {
+            break;
+        }
+
+        let snapshot = power::get_thermal_snapshot(ec).ok_or_else(|| {
+            EcError::DeviceError("failed to read temperature sensors".to_string())
+        })?;
+        let (name, temp) = hottest(&snapshot.temperatures, &settings.sensors).ok_or_else(|| {
+            EcError::DeviceError("no temperature sensor is reporting".to_string())
+        })?;
+
+        let color = gradient.color_at(temp as f32);
+        log::info!("{name} at {temp} C, showing {}", rgb_to_hex_string(color));
+        ec.rgbkbd_set_color(layout.start_key, vec![pipeline.apply(color); layout.zones])?;
+
+        // Sleep in short steps to react quickly to being stopped
+        let next = Instant::now() + interval;
+        while running.load(Ordering::Relaxed) && Instant::now() < next {
+            std::thread::sleep(Duration::from_millis(50).min(next - Instant::now()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use framework_lib::power::TempSensor;
+
+    #[test]
+    fn gradient_blends_and_clamps() {
+        let gradient = Gradient::new(&ThermalSettings::default().gradient).unwrap();
+        let hex = |temp| rgb_to_hex_string(gradient.color_at(temp));
+
+        assert_eq!(hex(20.0), "#0000FF");
+        assert_eq!(hex(50.0), "#008080");
+        assert_eq!(hex(60.0), "#00FF00");
+        assert_eq!(hex(120.0), "#FF0000");
+        assert!(Gradient::new(&[]).is_err());
+    }
+
+    #[test]
+    fn hottest_prefers_selected_sensors() {
+        let reading = |name: &str, state| TempReading {
+            name: name.to_string(),
+            state,
+        };
+        let readings = [
+            reading("F75303_APU", TempSensor::Ok(55)),
+            reading("F75303_DDR", TempSensor::Ok(70)),
+            reading("F75303_AMB", TempSensor::NotPowered),
+        ];
+        let sensors = ThermalSettings::default().sensors;
+
+        assert_eq!(hottest(&readings, &sensors), Some(("F75303_APU", 55)));
+        assert_eq!(
+            hottest(&readings, &["Battery".to_string()]),
+            Some(("F75303_DDR", 70))
+        );
+        assert_eq!(hottest(&readings[2..], &sensors), None);
+    }
+}